@@ -0,0 +1,1095 @@
+/*!board_editor.rs
+ * Edits the parts of a board and renders the editor UI with a live preview.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use crate::hardware::{self, Board, Button, Encoder, Keypad, Lcd, LedBar, LedMatrix, Part};
+use crate::hardware::{Potentiometer, RgbLed, Servo, SevenSeg, Stepper};
+use crate::hardware::dht::DhtSensor;
+use crossterm::event::{KeyCode, MouseButton, MouseEvent};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::widgets::{Block, Borders, Paragraph, Row, Table, Text, Widget};
+use tui::Frame;
+
+#[derive(PartialEq)]
+enum EditorMode {
+    List,
+    Properties,
+    Move,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Prop {
+    Type,
+    Pin,
+    Name,
+    Hotkey,
+    Mode,
+    HoldTime,
+    Bounce,
+    Segments,
+    Digits,
+    Common,
+    Rs,
+    E,
+    Data,
+    Rows,
+    Cols,
+    Red,
+    Green,
+    Blue,
+    Channel,
+    HotkeyUp,
+    HotkeyDown,
+    Value,
+    Step,
+    RowPins,
+    ColPins,
+    Keys,
+    Pull,
+    PinA,
+    PinB,
+    PinSw,
+    HotkeyCw,
+    HotkeyCcw,
+    HotkeySw,
+    StepTime,
+    MinPulse,
+    MaxPulse,
+    MaxAngle,
+    Coils,
+    Pins,
+    Sensor,
+    Temperature,
+    Humidity,
+    ColorOff,
+    ColorOn,
+    PosX,
+    PosY,
+}
+
+impl Prop {
+    fn label(&self) -> &str {
+        match self {
+            Prop::Type      => "Type",
+            Prop::Pin       => "Pin",
+            Prop::Name      => "Name",
+            Prop::Hotkey    => "Hotkey",
+            Prop::Mode      => "Mode",
+            Prop::HoldTime  => "Hold time",
+            Prop::Bounce    => "Bounce",
+            Prop::Segments  => "Segments",
+            Prop::Digits    => "Digits",
+            Prop::Common    => "Common",
+            Prop::Rs        => "RS",
+            Prop::E         => "E",
+            Prop::Data      => "Data",
+            Prop::Rows      => "Rows",
+            Prop::Cols      => "Columns",
+            Prop::Red       => "Red",
+            Prop::Green     => "Green",
+            Prop::Blue      => "Blue",
+            Prop::Channel   => "Channel",
+            Prop::HotkeyUp  => "Key up",
+            Prop::HotkeyDown => "Key down",
+            Prop::Value     => "Value",
+            Prop::Step      => "Step",
+            Prop::RowPins   => "Row pins",
+            Prop::ColPins   => "Col pins",
+            Prop::Keys      => "Keys",
+            Prop::Pull      => "Pull",
+            Prop::PinA      => "Pin A",
+            Prop::PinB      => "Pin B",
+            Prop::PinSw     => "Pin SW",
+            Prop::HotkeyCw  => "Key cw",
+            Prop::HotkeyCcw => "Key ccw",
+            Prop::HotkeySw  => "Key SW",
+            Prop::StepTime  => "Step time",
+            Prop::MinPulse  => "Min pulse",
+            Prop::MaxPulse  => "Max pulse",
+            Prop::MaxAngle  => "Max angle",
+            Prop::Coils     => "Coils",
+            Prop::Pins      => "Pins",
+            Prop::Sensor    => "Sensor",
+            Prop::Temperature => "Temp.",
+            Prop::Humidity  => "Humidity",
+            Prop::ColorOff  => "Color off",
+            Prop::ColorOn   => "Color on",
+            Prop::PosX      => "X",
+            Prop::PosY      => "Y",
+        }
+    }
+    fn for_part(part: &Part) -> Vec<Prop> {
+        match part {
+            Part::Button(_) => vec![
+                Prop::Type, Prop::Pin, Prop::Name, Prop::Hotkey,
+                Prop::Mode, Prop::HoldTime, Prop::Bounce,
+                Prop::ColorOff, Prop::ColorOn, Prop::PosX, Prop::PosY,
+            ],
+            Part::Led(_) => vec![
+                Prop::Type, Prop::Pin, Prop::Name,
+                Prop::ColorOff, Prop::ColorOn, Prop::PosX, Prop::PosY,
+            ],
+            Part::SevenSeg(_) => vec![
+                Prop::Type, Prop::Name, Prop::Segments, Prop::Digits, Prop::Common,
+                Prop::ColorOff, Prop::ColorOn, Prop::PosX, Prop::PosY,
+            ],
+            Part::Lcd(_) => vec![
+                Prop::Type, Prop::Name, Prop::Rs, Prop::E, Prop::Data, Prop::Rows, Prop::Cols,
+                Prop::ColorOff, Prop::ColorOn, Prop::PosX, Prop::PosY,
+            ],
+            Part::RgbLed(_) => vec![
+                Prop::Type, Prop::Name, Prop::Red, Prop::Green, Prop::Blue, Prop::Common,
+                Prop::ColorOff, Prop::PosX, Prop::PosY,
+            ],
+            Part::Potentiometer(_) => vec![
+                Prop::Type, Prop::Name, Prop::Channel, Prop::HotkeyUp, Prop::HotkeyDown,
+                Prop::Value, Prop::Step, Prop::ColorOff, Prop::ColorOn, Prop::PosX, Prop::PosY,
+            ],
+            Part::Keypad(_) => vec![
+                Prop::Type, Prop::Name, Prop::RowPins, Prop::ColPins, Prop::Keys, Prop::Pull,
+                Prop::HoldTime, Prop::ColorOff, Prop::ColorOn, Prop::PosX, Prop::PosY,
+            ],
+            Part::Encoder(_) => vec![
+                Prop::Type, Prop::Name, Prop::PinA, Prop::PinB, Prop::PinSw,
+                Prop::HotkeyCw, Prop::HotkeyCcw, Prop::HotkeySw, Prop::StepTime, Prop::HoldTime,
+                Prop::ColorOff, Prop::ColorOn, Prop::PosX, Prop::PosY,
+            ],
+            Part::Servo(_) => vec![
+                Prop::Type, Prop::Pin, Prop::Name, Prop::MinPulse, Prop::MaxPulse, Prop::MaxAngle,
+                Prop::ColorOff, Prop::ColorOn, Prop::PosX, Prop::PosY,
+            ],
+            Part::Stepper(_) => vec![
+                Prop::Type, Prop::Name, Prop::Coils,
+                Prop::ColorOff, Prop::ColorOn, Prop::PosX, Prop::PosY,
+            ],
+            Part::Dht(_) => vec![
+                Prop::Type, Prop::Pin, Prop::Name, Prop::Sensor, Prop::Temperature, Prop::Humidity,
+                Prop::ColorOff, Prop::ColorOn, Prop::PosX, Prop::PosY,
+            ],
+            Part::LedBar(_) => vec![
+                Prop::Type, Prop::Name, Prop::Pins,
+                Prop::ColorOff, Prop::ColorOn, Prop::PosX, Prop::PosY,
+            ],
+            Part::LedMatrix(_) => vec![
+                Prop::Type, Prop::Name, Prop::RowPins, Prop::ColPins, Prop::Common,
+                Prop::ColorOff, Prop::ColorOn, Prop::PosX, Prop::PosY,
+            ],
+        }
+    }
+}
+
+// Part types in the order the type property cycles through them
+const PART_TYPES: [&str; 13] = [
+    "led", "button", "sevenseg", "lcd_hd44780", "rgbled", "potentiometer", "keypad", "encoder",
+    "servo", "stepper", "dht", "ledbar", "ledmatrix",
+];
+
+// (key, type, name prefix, label) of the parts which can be added from the
+// part list
+const ADD_PART_KEYS: [(char, &str, &str, &str); 13] = [
+    ('a', "led", "LED", "LED"),
+    ('b', "button", "BTN", "button"),
+    ('s', "sevenseg", "7SEG", "7-segment"),
+    ('l', "lcd_hd44780", "LCD", "LCD"),
+    ('r', "rgbled", "RGB", "RGB LED"),
+    ('p', "potentiometer", "POT", "potentiometer"),
+    ('k', "keypad", "KEYPAD", "keypad"),
+    ('e', "encoder", "ENC", "encoder"),
+    ('v', "servo", "SERVO", "servo"),
+    ('t', "stepper", "STEPPER", "stepper"),
+    ('h', "dht", "DHT", "DHT sensor"),
+    ('g', "ledbar", "BAR", "LED bar"),
+    ('x', "ledmatrix", "MATRIX", "LED matrix"),
+];
+
+pub struct BoardEditor {
+    active_part: usize,
+    active_prop: usize,
+    // Offset of the mouse pointer to the origin of the dragged part
+    drag_offset: Option<(u16, u16)>,
+    mode: EditorMode,
+    // Area the board preview has been rendered into last
+    preview_area: Rect,
+}
+
+impl Default for BoardEditor {
+    fn default() -> Self {
+        Self {
+            active_part: 0,
+            active_prop: 0,
+            drag_offset: None,
+            mode: EditorMode::List,
+            preview_area: Rect::default(),
+        }
+    }
+}
+
+impl BoardEditor {
+    fn style_normal() -> Style {
+        Style::default()
+    }
+    fn style_hover() -> Style {
+        Style::default()
+            .fg(Color::White).bg(Color::Red)
+    }
+    fn style_selected() -> Style {
+        Style::default()
+            .fg(Color::Black).bg(Color::White)
+    }
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+    // Parts of included files are shown, but cannot be edited
+    fn is_editable(board: &Board, index: usize) -> bool {
+        board.hardware.get(index).map_or(false, |part| part.included_from().is_none())
+    }
+    // Moves a part to the given position, keeping it completely on the board.
+    fn move_part(board: &mut Board, index: usize, x: u16, y: u16) {
+        let (w, h) = board.hardware[index].size();
+        let max_x = board.width.saturating_sub(w + 2);
+        let max_y = board.height.saturating_sub(h + 2);
+        let pos = (x.min(max_x), y.min(max_y));
+        if board.hardware[index].pos() != pos {
+            board.hardware[index].set_pos(pos.0, pos.1);
+            board.is_modified = true;
+        }
+    }
+    fn add_part(&mut self, board: &mut Board, part: Part) {
+        board.hardware.push(part);
+        board.is_modified = true;
+        self.active_part = board.hardware.len() - 1;
+        let (x, y) = board.hardware[self.active_part].pos();
+        Self::move_part(board, self.active_part, x, y);
+    }
+    fn list_help() -> String {
+        let mut help: Vec<String> = ADD_PART_KEYS.iter()
+            .map(|(key, _, _, label)| format!("<{}> Add {}", key, label))
+            .collect();
+        help.push(String::from("<d> Remove  <m> Move  <Enter> Edit  <Esc> Back"));
+        help.join("  ")
+    }
+    // Converts the part into the next (or previous) part type, keeping its
+    // name, position and pin.
+    fn convert_part(part: &Part, forward: bool) -> Part {
+        let i = PART_TYPES.iter().position(|&t| t == part.type_name()).unwrap_or(0);
+        let i = if forward {
+            (i + 1) % PART_TYPES.len()
+        } else {
+            (i + PART_TYPES.len() - 1) % PART_TYPES.len()
+        };
+        // All entries of PART_TYPES are known part types
+        let mut new_part = Part::default_for(PART_TYPES[i], part.name().to_owned()).unwrap();
+        let (x, y) = part.pos();
+        new_part.set_pos(x, y);
+        let pins = (Self::pin(part, Prop::Pin), Self::pin_mut(&mut new_part, Prop::Pin));
+        if let (Some(pin), Some(new_pin)) = pins {
+            *new_pin = pin;
+        }
+        new_part
+    }
+    // Pin of a property which holds a single pin
+    fn pin(part: &Part, prop: Prop) -> Option<u8> {
+        match (part, prop) {
+            (Part::Button(button), Prop::Pin) => Some(button.pin),
+            (Part::Led(led), Prop::Pin) => Some(led.pin),
+            (Part::Servo(servo), Prop::Pin) => Some(servo.pin),
+            (Part::Dht(dht), Prop::Pin) => Some(dht.pin),
+            (Part::Lcd(lcd), Prop::Rs) => Some(lcd.rs),
+            (Part::Lcd(lcd), Prop::E) => Some(lcd.e),
+            (Part::RgbLed(rgbled), Prop::Red) => Some(rgbled.red),
+            (Part::RgbLed(rgbled), Prop::Green) => Some(rgbled.green),
+            (Part::RgbLed(rgbled), Prop::Blue) => Some(rgbled.blue),
+            (Part::Encoder(encoder), Prop::PinA) => Some(encoder.pin_a),
+            (Part::Encoder(encoder), Prop::PinB) => Some(encoder.pin_b),
+            (Part::Encoder(encoder), Prop::PinSw) => encoder.pin_sw,
+            _ => None,
+        }
+    }
+    fn pin_mut(part: &mut Part, prop: Prop) -> Option<&mut u8> {
+        match (part, prop) {
+            (Part::Button(button), Prop::Pin) => Some(&mut button.pin),
+            (Part::Led(led), Prop::Pin) => Some(&mut led.pin),
+            (Part::Servo(servo), Prop::Pin) => Some(&mut servo.pin),
+            (Part::Dht(dht), Prop::Pin) => Some(&mut dht.pin),
+            (Part::Lcd(lcd), Prop::Rs) => Some(&mut lcd.rs),
+            (Part::Lcd(lcd), Prop::E) => Some(&mut lcd.e),
+            (Part::RgbLed(rgbled), Prop::Red) => Some(&mut rgbled.red),
+            (Part::RgbLed(rgbled), Prop::Green) => Some(&mut rgbled.green),
+            (Part::RgbLed(rgbled), Prop::Blue) => Some(&mut rgbled.blue),
+            (Part::Encoder(encoder), Prop::PinA) => Some(&mut encoder.pin_a),
+            (Part::Encoder(encoder), Prop::PinB) => Some(&mut encoder.pin_b),
+            (Part::Encoder(encoder), Prop::PinSw) => encoder.pin_sw.as_mut(),
+            _ => None,
+        }
+    }
+    fn pin_list(part: &Part, prop: Prop) -> Option<&Vec<u8>> {
+        match (part, prop) {
+            (Part::SevenSeg(sevenseg), Prop::Segments) => Some(&sevenseg.segments),
+            (Part::SevenSeg(sevenseg), Prop::Digits) => Some(&sevenseg.digits),
+            (Part::Lcd(lcd), Prop::Data) => Some(&lcd.data),
+            (Part::Keypad(keypad), Prop::RowPins) => Some(&keypad.rows),
+            (Part::Keypad(keypad), Prop::ColPins) => Some(&keypad.cols),
+            (Part::Stepper(stepper), Prop::Coils) => Some(&stepper.pins),
+            (Part::LedBar(ledbar), Prop::Pins) => Some(&ledbar.pins),
+            (Part::LedMatrix(ledmatrix), Prop::RowPins) => Some(&ledmatrix.rows),
+            (Part::LedMatrix(ledmatrix), Prop::ColPins) => Some(&ledmatrix.cols),
+            _ => None,
+        }
+    }
+    // Pin list of the property and its maximum length
+    fn pin_list_mut(part: &mut Part, prop: Prop) -> Option<(&mut Vec<u8>, usize)> {
+        match (part, prop) {
+            (Part::SevenSeg(sevenseg), Prop::Segments) => Some((&mut sevenseg.segments, 8)),
+            (Part::SevenSeg(sevenseg), Prop::Digits) => Some((&mut sevenseg.digits, 8)),
+            (Part::Lcd(lcd), Prop::Data) => Some((&mut lcd.data, 8)),
+            (Part::Keypad(keypad), Prop::RowPins) => Some((&mut keypad.rows, Keypad::MAX_ROWS)),
+            (Part::Keypad(keypad), Prop::ColPins) => Some((&mut keypad.cols, Keypad::MAX_COLS)),
+            (Part::Stepper(stepper), Prop::Coils) => Some((&mut stepper.pins, Stepper::PIN_COUNT)),
+            (Part::LedBar(ledbar), Prop::Pins) => Some((&mut ledbar.pins, LedBar::MAX_PINS)),
+            (Part::LedMatrix(ledmatrix), Prop::RowPins) => Some((&mut ledmatrix.rows, LedMatrix::MAX_ROWS)),
+            (Part::LedMatrix(ledmatrix), Prop::ColPins) => Some((&mut ledmatrix.cols, LedMatrix::MAX_COLS)),
+            _ => None,
+        }
+    }
+    // Fits the keys of a keypad to its pin lists and keeps the part, whose
+    // size may depend on them, on the board.
+    fn fit_part(board: &mut Board, index: usize) {
+        if let Part::Keypad(keypad) = &mut board.hardware[index] {
+            keypad.fit_keys();
+        }
+        let (x, y) = board.hardware[index].pos();
+        Self::move_part(board, index, x, y);
+    }
+    // Types a digit into a pin number; None deletes the last digit.
+    fn edit_pin(pin: &mut u8, c: Option<char>) {
+        Self::edit_number(pin, 31, c);
+    }
+    // Types a digit into a number, starting over if it would exceed max.
+    fn edit_number(value: &mut u8, max: u8, c: Option<char>) {
+        match c {
+            Some(c) => {
+                if let Some(d) = c.to_digit(10) {
+                    let new_value = *value as u32 * 10 + d;
+                    *value = if new_value <= max as u32 { new_value as u8 } else { (d as u8).min(max) };
+                }
+            },
+            None => { *value /= 10; },
+        }
+    }
+    // Edits the last pin of a pin list, ',' starts a new pin. Deleting the
+    // last digit of a pin removes it.
+    fn edit_pin_list(pins: &mut Vec<u8>, max_len: usize, c: Option<char>) {
+        match c {
+            Some(',') => {
+                if pins.len() < max_len {
+                    pins.push(0);
+                }
+            },
+            Some(c) => {
+                if pins.is_empty() {
+                    pins.push(0);
+                }
+                Self::edit_pin(pins.last_mut().unwrap(), Some(c));
+            },
+            None => {
+                match pins.last_mut() {
+                    Some(0) => { pins.pop(); },
+                    Some(pin) => Self::edit_pin(pin, None),
+                    None => {},
+                }
+            },
+        }
+    }
+    fn active_prop(&self, board: &Board) -> Option<Prop> {
+        let part = board.hardware.get(self.active_part)?;
+        return Prop::for_part(part).get(self.active_prop).cloned();
+    }
+    fn change_prop(&mut self, board: &mut Board, forward: bool) {
+        let prop = match self.active_prop(board) {
+            Some(prop) => prop,
+            None => return,
+        };
+        board.is_modified = true;
+        let index = self.active_part;
+        match prop {
+            Prop::Type => {
+                board.hardware[index] = Self::convert_part(&board.hardware[index], forward);
+                let (x, y) = board.hardware[index].pos();
+                Self::move_part(board, index, x, y);
+                self.active_prop = 0;
+            },
+            Prop::PosX | Prop::PosY => {
+                let (x, y) = board.hardware[index].pos();
+                let step = |v: u16| if forward { v + 1 } else { v.saturating_sub(1) };
+                if prop == Prop::PosX {
+                    Self::move_part(board, index, step(x), y);
+                } else {
+                    Self::move_part(board, index, x, step(y));
+                }
+            },
+            // The switch pin is optional, it cycles through "none" and all pins
+            Prop::PinSw => {
+                if let Part::Encoder(encoder) = &mut board.hardware[index] {
+                    encoder.pin_sw = match (encoder.pin_sw, forward) {
+                        (None, true) => Some(0),
+                        (None, false) => Some(31),
+                        (Some(31), true) | (Some(0), false) => None,
+                        (Some(pin), true) => Some(pin + 1),
+                        (Some(pin), false) => Some(pin - 1),
+                    };
+                }
+            },
+            Prop::Pin | Prop::Rs | Prop::E | Prop::Red | Prop::Green | Prop::Blue
+            | Prop::PinA | Prop::PinB
+            | Prop::Segments | Prop::Digits | Prop::Data | Prop::RowPins | Prop::ColPins | Prop::Coils
+            | Prop::Pins => {
+                let part = &mut board.hardware[index];
+                let pin = match Self::pin_list_mut(part, prop) {
+                    Some((pins, _)) => pins.last_mut(),
+                    None => Self::pin_mut(part, prop),
+                };
+                if let Some(pin) = pin {
+                    *pin = if forward { (*pin + 1) % 32 } else { (*pin + 31) % 32 };
+                }
+                Self::fit_part(board, index);
+            },
+            Prop::Common => {
+                match &mut board.hardware[index] {
+                    Part::SevenSeg(sevenseg) => sevenseg.common_anode = !sevenseg.common_anode,
+                    Part::RgbLed(rgbled) => rgbled.common_anode = !rgbled.common_anode,
+                    Part::LedMatrix(ledmatrix) => ledmatrix.common_anode = !ledmatrix.common_anode,
+                    _ => {},
+                }
+            },
+            Prop::Channel | Prop::Value | Prop::Step => {
+                if let Part::Potentiometer(pot) = &mut board.hardware[index] {
+                    match prop {
+                        Prop::Channel => pot.channel = if forward {
+                            (pot.channel + 1) % (Potentiometer::MAX_CHANNEL + 1)
+                        } else {
+                            (pot.channel + Potentiometer::MAX_CHANNEL) % (Potentiometer::MAX_CHANNEL + 1)
+                        },
+                        Prop::Value => pot.turn(forward),
+                        _ => pot.step = if forward { (pot.step + 1).min(100) } else { pot.step.saturating_sub(1).max(1) },
+                    }
+                }
+            },
+            Prop::HoldTime if !matches!(board.hardware[index], Part::Button(_)) => {
+                let hold_time_ms = match &mut board.hardware[index] {
+                    Part::Keypad(keypad) => &mut keypad.hold_time_ms,
+                    Part::Encoder(encoder) => &mut encoder.hold_time_ms,
+                    _ => return,
+                };
+                *hold_time_ms = if forward {
+                    (*hold_time_ms + 50).min(Button::MAX_HOLD_TIME_MS)
+                } else {
+                    hold_time_ms.saturating_sub(50).max(Button::MIN_HOLD_TIME_MS)
+                };
+            },
+            Prop::StepTime => {
+                if let Part::Encoder(encoder) = &mut board.hardware[index] {
+                    encoder.step_time_ms = if forward {
+                        (encoder.step_time_ms + 1).min(Encoder::MAX_STEP_TIME_MS)
+                    } else {
+                        encoder.step_time_ms.saturating_sub(1).max(Encoder::MIN_STEP_TIME_MS)
+                    };
+                }
+            },
+            Prop::Pull => {
+                if let Part::Keypad(keypad) = &mut board.hardware[index] {
+                    keypad.pull_up = !keypad.pull_up;
+                }
+            },
+            Prop::Sensor | Prop::Temperature | Prop::Humidity => {
+                if let Part::Dht(dht) = &mut board.hardware[index] {
+                    match prop {
+                        Prop::Sensor => {
+                            dht.sensor = match dht.sensor {
+                                DhtSensor::Dht11 => DhtSensor::Dht22,
+                                DhtSensor::Dht22 => DhtSensor::Dht11,
+                            };
+                            dht.fit_values();
+                        },
+                        Prop::Temperature => dht.change_temperature(forward),
+                        _ => dht.change_humidity(forward),
+                    }
+                }
+            },
+            Prop::MinPulse | Prop::MaxPulse | Prop::MaxAngle => {
+                if let Part::Servo(servo) = &mut board.hardware[index] {
+                    match prop {
+                        Prop::MaxAngle => servo.max_angle = if forward {
+                            (servo.max_angle + 5).min(Servo::MAX_ANGLE)
+                        } else {
+                            servo.max_angle.saturating_sub(5).max(1)
+                        },
+                        _ => {
+                            let pulse_us = if prop == Prop::MinPulse {
+                                &mut servo.min_pulse_us
+                            } else {
+                                &mut servo.max_pulse_us
+                            };
+                            *pulse_us = if forward {
+                                (*pulse_us + 50).min(Servo::MAX_PULSE_US)
+                            } else {
+                                pulse_us.saturating_sub(50).max(Servo::MIN_PULSE_US)
+                            };
+                        },
+                    }
+                }
+            },
+            Prop::Mode | Prop::HoldTime | Prop::Bounce => {
+                if let Part::Button(button) = &mut board.hardware[index] {
+                    match prop {
+                        Prop::Mode => button.momentary = !button.momentary,
+                        Prop::HoldTime => button.hold_time_ms = if forward {
+                            (button.hold_time_ms + 50).min(Button::MAX_HOLD_TIME_MS)
+                        } else {
+                            button.hold_time_ms.saturating_sub(50).max(Button::MIN_HOLD_TIME_MS)
+                        },
+                        _ => button.bounce_ms = if forward {
+                            (button.bounce_ms + 1).min(Button::MAX_BOUNCE_MS)
+                        } else {
+                            button.bounce_ms.saturating_sub(1)
+                        },
+                    }
+                }
+            },
+            Prop::Rows | Prop::Cols => {
+                if let Part::Lcd(lcd) = &mut board.hardware[index] {
+                    let (value, max) = if prop == Prop::Rows {
+                        (&mut lcd.rows, Lcd::MAX_ROWS)
+                    } else {
+                        (&mut lcd.cols, Lcd::MAX_COLS)
+                    };
+                    *value = if forward { (*value + 1).min(max) } else { value.saturating_sub(1).max(1) };
+                }
+                // Keep the resized part on the board
+                let (x, y) = board.hardware[index].pos();
+                Self::move_part(board, index, x, y);
+            },
+            Prop::ColorOff => {
+                let (color_off, _) = board.hardware[index].colors_mut();
+                *color_off = hardware::helper_cycle_color(*color_off, forward);
+            },
+            Prop::ColorOn => {
+                if let (_, Some(color_on)) = board.hardware[index].colors_mut() {
+                    *color_on = hardware::helper_cycle_color(*color_on, forward);
+                }
+            },
+            _ => {},
+        }
+    }
+    // Edits the active text property; None deletes the last character.
+    fn edit_prop(&mut self, board: &mut Board, c: Option<char>) {
+        let prop = match self.active_prop(board) {
+            Some(prop) => prop,
+            None => return,
+        };
+        board.is_modified = true;
+        let part = &mut board.hardware[self.active_part];
+        match (prop, part) {
+            (Prop::Name, part) => {
+                match c {
+                    Some(c) => part.name_mut().push(c),
+                    None => { part.name_mut().pop(); },
+                }
+            },
+            (Prop::Hotkey, Part::Button(button)) => {
+                button.hotkey = c.map(|c| c.to_string()).unwrap_or_default();
+            },
+            (Prop::HotkeyUp, Part::Potentiometer(pot)) => {
+                pot.hotkey_up = c.map(|c| c.to_string()).unwrap_or_default();
+            },
+            (Prop::HotkeyDown, Part::Potentiometer(pot)) => {
+                pot.hotkey_down = c.map(|c| c.to_string()).unwrap_or_default();
+            },
+            (Prop::HotkeyCw, Part::Encoder(encoder)) => {
+                encoder.hotkey_cw = c.map(|c| c.to_string()).unwrap_or_default();
+            },
+            (Prop::HotkeyCcw, Part::Encoder(encoder)) => {
+                encoder.hotkey_ccw = c.map(|c| c.to_string()).unwrap_or_default();
+            },
+            (Prop::HotkeySw, Part::Encoder(encoder)) => {
+                encoder.hotkey_sw = c.map(|c| c.to_string()).unwrap_or_default();
+            },
+            // Deleting the last digit of the switch pin removes the switch
+            (Prop::PinSw, Part::Encoder(encoder)) => {
+                encoder.pin_sw = match (encoder.pin_sw, c) {
+                    (pin, Some(c)) => {
+                        let mut pin = pin.unwrap_or(0);
+                        Self::edit_pin(&mut pin, Some(c));
+                        Some(pin)
+                    },
+                    (Some(pin), None) if pin >= 10 => Some(pin / 10),
+                    (_, None) => None,
+                };
+            },
+            (Prop::Channel, Part::Potentiometer(pot)) => {
+                Self::edit_number(&mut pot.channel, Potentiometer::MAX_CHANNEL, c);
+            },
+            (Prop::Value, Part::Potentiometer(pot)) => {
+                Self::edit_number(&mut pot.value, 100, c);
+            },
+            (Prop::Step, Part::Potentiometer(pot)) => {
+                Self::edit_number(&mut pot.step, 100, c);
+                pot.step = pot.step.max(1);
+            },
+            (Prop::Pin, part) | (Prop::Rs, part) | (Prop::E, part)
+            | (Prop::Red, part) | (Prop::Green, part) | (Prop::Blue, part)
+            | (Prop::PinA, part) | (Prop::PinB, part) => {
+                if let Some(pin) = Self::pin_mut(part, prop) {
+                    Self::edit_pin(pin, c);
+                }
+            },
+            (Prop::Segments, part) | (Prop::Digits, part) | (Prop::Data, part)
+            | (Prop::RowPins, part) | (Prop::ColPins, part) | (Prop::Coils, part)
+            | (Prop::Pins, part) => {
+                if let Some((pins, max_len)) = Self::pin_list_mut(part, prop) {
+                    Self::edit_pin_list(pins, max_len, c);
+                }
+                Self::fit_part(board, self.active_part);
+            },
+            _ => {}
+        }
+    }
+    pub fn event_keypress(&mut self, board: &mut Board, c: char) -> bool {
+        match self.mode {
+            EditorMode::List => {
+                match c {
+                    'd' => {
+                        return self.event_keypress_special(board, KeyCode::Delete);
+                    },
+                    'm' => {
+                        if Self::is_editable(board, self.active_part) {
+                            self.mode = EditorMode::Move;
+                        }
+                    },
+                    _ => match ADD_PART_KEYS.iter().find(|(key, ..)| *key == c) {
+                        Some((_, type_name, prefix, _)) => {
+                            let name = format!("{} {}", prefix, board.hardware.len() + 1);
+                            if let Some(part) = Part::default_for(type_name, name) {
+                                self.add_part(board, part);
+                            }
+                        },
+                        None => return false,
+                    },
+                }
+                return true;
+            },
+            EditorMode::Properties => {
+                self.edit_prop(board, Some(c));
+                return true;
+            },
+            EditorMode::Move => {
+                return false;
+            },
+        }
+    }
+    // Returns false if the key has not been handled, e.g. <Esc> in the part
+    // list, which closes the editor.
+    pub fn event_keypress_special(&mut self, board: &mut Board, c: KeyCode) -> bool {
+        match self.mode {
+            EditorMode::List => {
+                match c {
+                    KeyCode::Up => {
+                        if self.active_part > 0 {
+                            self.active_part -= 1;
+                        }
+                    },
+                    KeyCode::Down => {
+                        if self.active_part + 1 < board.hardware.len() {
+                            self.active_part += 1;
+                        }
+                    },
+                    KeyCode::Enter => {
+                        if Self::is_editable(board, self.active_part) {
+                            self.mode = EditorMode::Properties;
+                            self.active_prop = 0;
+                        }
+                    },
+                    KeyCode::Delete => {
+                        if Self::is_editable(board, self.active_part) {
+                            board.hardware.remove(self.active_part);
+                            board.is_modified = true;
+                            if self.active_part > 0 && self.active_part >= board.hardware.len() {
+                                self.active_part -= 1;
+                            }
+                        }
+                    },
+                    _ => return false,
+                }
+                return true;
+            },
+            EditorMode::Properties => {
+                let items = board.hardware.get(self.active_part)
+                    .map_or(0, |part| Prop::for_part(part).len());
+                match c {
+                    KeyCode::Up => {
+                        if self.active_prop > 0 {
+                            self.active_prop -= 1;
+                        }
+                    },
+                    KeyCode::Down => {
+                        if self.active_prop + 1 < items {
+                            self.active_prop += 1;
+                        }
+                    },
+                    KeyCode::Left => self.change_prop(board, false),
+                    KeyCode::Right => self.change_prop(board, true),
+                    KeyCode::Backspace => self.edit_prop(board, None),
+                    KeyCode::Enter | KeyCode::Esc => {
+                        self.mode = EditorMode::List;
+                    },
+                    _ => return false,
+                }
+                return true;
+            },
+            EditorMode::Move => {
+                if self.active_part >= board.hardware.len() {
+                    self.mode = EditorMode::List;
+                    return true;
+                }
+                let (x, y) = board.hardware[self.active_part].pos();
+                match c {
+                    KeyCode::Up => Self::move_part(board, self.active_part, x, y.saturating_sub(1)),
+                    KeyCode::Down => Self::move_part(board, self.active_part, x, y + 1),
+                    KeyCode::Left => Self::move_part(board, self.active_part, x.saturating_sub(1), y),
+                    KeyCode::Right => Self::move_part(board, self.active_part, x + 1, y),
+                    KeyCode::Enter | KeyCode::Esc => {
+                        self.mode = EditorMode::List;
+                    },
+                    _ => return false,
+                }
+                return true;
+            },
+        }
+    }
+    // Clicking a part in the preview selects it, dragging moves it. In move
+    // mode, clicking an empty spot moves the selected part there.
+    pub fn event_mouse(&mut self, board: &mut Board, event: MouseEvent) -> bool {
+        let area = self.preview_area;
+        let to_board = |col: u16, row: u16| -> Option<(u16, u16)> {
+            if col > area.x && row > area.y
+                && col < area.x + area.width.min(board.width)
+                && row < area.y + area.height.min(board.height) {
+                Some((col - area.x - 1, row - area.y - 1))
+            } else {
+                None
+            }
+        };
+        match event {
+            MouseEvent::Down(MouseButton::Left, col, row, _) => {
+                let (x, y) = match to_board(col, row) {
+                    Some(pos) => pos,
+                    None => return false,
+                };
+                let hit = board.hardware.iter().rposition(|part| {
+                    let (px, py) = part.pos();
+                    let (w, h) = part.size();
+                    x >= px && x < px + w && y >= py && y < py + h
+                });
+                if let Some(index) = hit {
+                    let (px, py) = board.hardware[index].pos();
+                    self.active_part = index;
+                    if Self::is_editable(board, index) {
+                        self.drag_offset = Some((x - px, y - py));
+                    } else {
+                        self.mode = EditorMode::List;
+                    }
+                    if self.mode == EditorMode::Properties {
+                        self.active_prop = 0;
+                    }
+                } else if self.mode == EditorMode::Move && Self::is_editable(board, self.active_part) {
+                    Self::move_part(board, self.active_part, x, y);
+                }
+                return true;
+            },
+            MouseEvent::Drag(MouseButton::Left, col, row, _) => {
+                if let (Some((off_x, off_y)), Some((x, y))) = (self.drag_offset, to_board(col, row)) {
+                    if self.active_part < board.hardware.len() {
+                        Self::move_part(board, self.active_part,
+                            x.saturating_sub(off_x), y.saturating_sub(off_y)
+                        );
+                    }
+                }
+                return true;
+            },
+            MouseEvent::Up(MouseButton::Left, _, _, _) => {
+                self.drag_offset = None;
+                return true;
+            },
+            _ => {
+                return false;
+            }
+        }
+    }
+    fn prop_value(part: &Part, prop: Prop) -> String {
+        let (x, y) = part.pos();
+        let (color_off, color_on) = part.colors();
+        match prop {
+            Prop::Type => format!("< {} >", part.type_name()),
+            Prop::Pin | Prop::Rs | Prop::E | Prop::Red | Prop::Green | Prop::Blue
+            | Prop::PinA | Prop::PinB => {
+                Self::pin(part, prop).map(|p| p.to_string()).unwrap_or_default()
+            },
+            Prop::PinSw => Self::pin(part, prop).map_or(String::from("none"), |p| p.to_string()),
+            Prop::HotkeyCw | Prop::HotkeyCcw | Prop::HotkeySw => match part {
+                Part::Encoder(encoder) => match prop {
+                    Prop::HotkeyCw => encoder.hotkey_cw.clone(),
+                    Prop::HotkeyCcw => encoder.hotkey_ccw.clone(),
+                    _ => encoder.hotkey_sw.clone(),
+                },
+                _ => String::new(),
+            },
+            Prop::StepTime => match part {
+                Part::Encoder(encoder) => format!("< {} ms >", encoder.step_time_ms),
+                _ => String::new(),
+            },
+            Prop::Sensor | Prop::Temperature | Prop::Humidity => match part {
+                Part::Dht(dht) => match prop {
+                    Prop::Sensor => format!("< {} >", dht.sensor.name()),
+                    Prop::Temperature => format!("< {:.1} °C >", dht.temperature as f64 / 10.0),
+                    _ => format!("< {:.1} % >", dht.humidity as f64 / 10.0),
+                },
+                _ => String::new(),
+            },
+            Prop::MinPulse | Prop::MaxPulse | Prop::MaxAngle => match part {
+                Part::Servo(servo) => match prop {
+                    Prop::MinPulse => format!("< {} us >", servo.min_pulse_us),
+                    Prop::MaxPulse => format!("< {} us >", servo.max_pulse_us),
+                    _ => format!("< {}° >", servo.max_angle),
+                },
+                _ => String::new(),
+            },
+            Prop::Name => part.name().to_owned(),
+            Prop::Hotkey => match part {
+                Part::Button(button) => button.hotkey.clone(),
+                _ => String::new(),
+            },
+            Prop::HotkeyUp | Prop::HotkeyDown => match part {
+                Part::Potentiometer(pot) => {
+                    if prop == Prop::HotkeyUp { pot.hotkey_up.clone() } else { pot.hotkey_down.clone() }
+                },
+                _ => String::new(),
+            },
+            Prop::Channel | Prop::Value | Prop::Step => match part {
+                Part::Potentiometer(pot) => match prop {
+                    Prop::Channel => format!("< {} >", pot.channel),
+                    Prop::Value => format!("< {}% >", pot.value),
+                    _ => format!("< {}% >", pot.step),
+                },
+                _ => String::new(),
+            },
+            Prop::RowPins | Prop::ColPins | Prop::Coils | Prop::Pins => match Self::pin_list(part, prop) {
+                Some(pins) => pins.iter().map(|p| p.to_string()).collect::<Vec<String>>().join(","),
+                None => String::new(),
+            },
+            Prop::Keys => match part {
+                Part::Keypad(keypad) => keypad.keys.join(" "),
+                _ => String::new(),
+            },
+            Prop::Pull => match part {
+                Part::Keypad(keypad) => format!("< {} >", if keypad.pull_up { "up" } else { "down" }),
+                _ => String::new(),
+            },
+            Prop::Mode | Prop::HoldTime | Prop::Bounce => match part {
+                Part::Keypad(keypad) => format!("< {} ms >", keypad.hold_time_ms),
+                Part::Encoder(encoder) => format!("< {} ms >", encoder.hold_time_ms),
+                Part::Button(button) => match prop {
+                    Prop::Mode => format!("< {} >", if button.momentary { "momentary" } else { "toggle" }),
+                    Prop::HoldTime => format!("< {} ms >", button.hold_time_ms),
+                    _ if button.bounce_ms == 0 => String::from("< off >"),
+                    _ => format!("< {} ms >", button.bounce_ms),
+                },
+                _ => String::new(),
+            },
+            Prop::Segments | Prop::Digits | Prop::Data => match Self::pin_list(part, prop) {
+                Some(pins) => pins.iter().map(|p| p.to_string()).collect::<Vec<String>>().join(","),
+                None => String::new(),
+            },
+            Prop::Common => match part {
+                Part::SevenSeg(SevenSeg { common_anode, .. })
+                | Part::RgbLed(RgbLed { common_anode, .. })
+                | Part::LedMatrix(LedMatrix { common_anode, .. }) => format!("< {} >",
+                    if *common_anode { "anode" } else { "cathode" }
+                ),
+                _ => String::new(),
+            },
+            Prop::Rows | Prop::Cols => match part {
+                Part::Lcd(lcd) => format!("< {} >", if prop == Prop::Rows { lcd.rows } else { lcd.cols }),
+                _ => String::new(),
+            },
+            Prop::ColorOff => format!("< {} >", hardware::helper_color_to_str(color_off)),
+            Prop::ColorOn => color_on
+                .map(|c| format!("< {} >", hardware::helper_color_to_str(c)))
+                .unwrap_or_default(),
+            Prop::PosX => x.to_string(),
+            Prop::PosY => y.to_string(),
+        }
+    }
+    pub fn render(
+        &mut self, board: &Board, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>, area: Rect
+    ) {
+        let editor_layout = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Length(40),    // Parts and properties
+                Constraint::Length(1),     // Padding
+                Constraint::Min(1),        // Preview
+            ].as_ref())
+            .split(area);
+        let left_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(3),        // Part list
+                Constraint::Length(16),    // Properties
+                Constraint::Length(7),     // Key help
+            ].as_ref())
+            .split(editor_layout[0]);
+        let table_header = [
+            "#", "Type", "Pin", "Name"
+        ];
+        let table_rows = board.hardware.iter().enumerate().map(|(i, part)| {
+            let style = if i != self.active_part {
+                Self::style_normal()
+            } else if self.mode == EditorMode::List {
+                Self::style_hover()
+            } else {
+                Self::style_selected()
+            };
+            // Parts with more than one pin show the first one
+            let pins: Vec<u8> = part.driven_pins().into_iter().chain(part.sensed_pins()).collect();
+            let pin = match pins.len() {
+                0 => String::new(),
+                1 => pins[0].to_string(),
+                _ => format!("{}+", pins[0]),
+            };
+            Row::StyledData(vec![
+                (i+1).to_string(),
+                part.type_name().to_owned(),
+                pin,
+                match part.included_from() {
+                    Some(_) => format!("{} (included)", part.name()),
+                    None => part.name().to_owned(),
+                },
+            ].into_iter(), style)
+        });
+        Table::new(table_header.iter(), table_rows)
+            .block(Block::default().title(" Parts ").borders(Borders::ALL))
+            .widths(&[
+                Constraint::Length(3),
+                Constraint::Length(13),
+                Constraint::Length(3),
+                Constraint::Min(10),
+            ])
+            .header_style(Style::default().modifier(Modifier::BOLD))
+            .column_spacing(1)
+            .render(f, left_layout[0]);
+        let mut props_text = vec![];
+        if let Some(part) = board.hardware.get(self.active_part) {
+            for (i, prop) in Prop::for_part(part).iter().enumerate() {
+                let style = if self.mode == EditorMode::Properties && i == self.active_prop {
+                    Self::style_hover()
+                } else {
+                    Self::style_normal()
+                };
+                props_text.push(Text::raw(format!("{:<11}", prop.label())));
+                props_text.push(Text::styled(format!("{}\n", Self::prop_value(part, *prop)), style));
+            }
+        } else {
+            props_text.push(Text::styled("No part selected", Style::default().fg(Color::Gray)));
+        }
+        Paragraph::new(props_text.iter())
+            .block(Block::default().title(" Properties ").borders(Borders::ALL))
+            .render(f, left_layout[1]);
+        Paragraph::new([
+            match self.mode {
+                EditorMode::List => Text::raw(Self::list_help()),
+                EditorMode::Properties => Text::raw("<Left/Right Arrow> to change value, type to edit text, <Esc> to go back"),
+                EditorMode::Move => Text::raw("<Arrow keys> or mouse to move the part, <Enter> to finish"),
+            },
+        ].iter())
+            .wrap(true)
+            .render(f, left_layout[2]);
+        self.preview_area = editor_layout[2];
+        board.render(f, editor_layout[2]);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn BoardEditor__event_keypress__add_part() {
+        let help = BoardEditor::list_help();
+        for (i, (key, type_name, prefix, label)) in ADD_PART_KEYS.iter().enumerate() {
+            let mut board = Board::default();
+            board.width = 100;
+            board.height = 100;
+            let mut editor = BoardEditor::default();
+            assert!(editor.event_keypress(&mut board, *key));
+            assert_eq!(board.hardware.len(), 1);
+            assert_eq!(board.hardware[0].type_name(), *type_name);
+            assert_eq!(board.hardware[0].name(), format!("{} 1", prefix));
+            assert!(help.contains(&format!("<{}> Add {}  ", key, label)));
+            assert!(!ADD_PART_KEYS[i + 1..].iter().any(|(k, ..)| k == key));
+        }
+        assert!(PART_TYPES.iter().all(|t| ADD_PART_KEYS.iter().any(|(_, type_name, ..)| type_name == t)));
+    }
+
+    #[test]
+    fn BoardEditor__edit_prop__step() {
+        let mut board = Board::default();
+        board.hardware.push(Part::Potentiometer(Potentiometer::default()));
+        let mut editor = BoardEditor::default();
+        editor.event_keypress_special(&mut board, KeyCode::Enter);
+        while editor.active_prop(&board) != Some(Prop::Step) {
+            editor.event_keypress_special(&mut board, KeyCode::Down);
+        }
+        let step = |board: &Board| match &board.hardware[0] {
+            Part::Potentiometer(pot) => pot.step,
+            _ => unreachable!(),
+        };
+        // Deleting all digits keeps the step at 1
+        editor.event_keypress_special(&mut board, KeyCode::Backspace);
+        assert_eq!(step(&board), 1);
+        editor.event_keypress_special(&mut board, KeyCode::Left);
+        assert_eq!(step(&board), 1);
+        editor.event_keypress(&mut board, '2');
+        assert_eq!(step(&board), 12);
+        editor.event_keypress_special(&mut board, KeyCode::Left);
+        assert_eq!(step(&board), 11);
+    }
+
+    #[test]
+    fn BoardEditor__edit_prop__lcd_size() {
+        let mut board = Board::default();
+        let mut lcd = Lcd::default();
+        lcd.rows = 0;
+        board.hardware.push(Part::Lcd(lcd));
+        let mut editor = BoardEditor::default();
+        editor.event_keypress_special(&mut board, KeyCode::Enter);
+        while editor.active_prop(&board) != Some(Prop::Rows) {
+            editor.event_keypress_special(&mut board, KeyCode::Down);
+        }
+        let rows = |board: &Board| match &board.hardware[0] {
+            Part::Lcd(lcd) => lcd.rows,
+            _ => unreachable!(),
+        };
+        // Stepping down from an invalid size must not underflow
+        editor.event_keypress_special(&mut board, KeyCode::Left);
+        assert_eq!(rows(&board), 1);
+        editor.event_keypress_special(&mut board, KeyCode::Left);
+        assert_eq!(rows(&board), 1);
+        editor.event_keypress_special(&mut board, KeyCode::Right);
+        assert_eq!(rows(&board), 2);
+    }
+}
@@ -5,8 +5,9 @@
  * License: GPL 3.0 (see LICENSE file for details)
  */
 
+use super::BoardEditor;
 use crate::hardware;
-use crossterm::event::KeyCode;
+use crossterm::event::{KeyCode, MouseEvent};
 use std::io::{Error};
 use tui::backend::CrosstermBackend;
 use tui::layout::{Alignment, Constraint, Direction, Layout, Rect};
@@ -20,17 +21,25 @@ enum FocusArea {
     Field,
 }
 
+const NEW_BOARD_MIN_SIZE: u16 = 8;
+const NEW_BOARD_MAX_SIZE: u16 = 200;
+
 pub struct BoardManager {
     active_tab: usize,
     active_content: usize,
     active_field: usize,
     pub boards: Vec<hardware::Board>,
     current_focus_area: FocusArea,
+    editor: BoardEditor,
     items_tab: usize,
     items_content: usize,
     items_field: usize,
+    tmp_new_board: hardware::Board,
     tmp_open_board: Result<hardware::Board, Error>,
+    tmp_open_board_issues: Vec<hardware::validate::Issue>,
     tmp_open_board_str: String,
+    tmp_save_board_str: String,
+    tmp_save_status: Option<Result<String, String>>,
 }
 
 impl Default for BoardManager {
@@ -41,11 +50,16 @@ impl Default for BoardManager {
             active_field: 0,
             boards: vec![],
             current_focus_area: FocusArea::Tab,
+            editor: BoardEditor::default(),
             items_tab: 6,
             items_content: 0,
             items_field: 0,
+            tmp_new_board: hardware::Board::default(),
             tmp_open_board: Ok(hardware::Board::default()),
+            tmp_open_board_issues: vec![],
             tmp_open_board_str: String::new(),
+            tmp_save_board_str: String::new(),
+            tmp_save_status: None,
         }
     }
 }
@@ -62,8 +76,53 @@ impl BoardManager {
         Style::default()
             .fg(Color::Black).bg(Color::White)
     }
+    fn reset_new_board(&mut self) {
+        self.tmp_new_board = hardware::Board::default();
+        self.tmp_new_board.name = format!("Board {}", self.boards.len() + 1);
+    }
+    // Changes the selected field of the new board form, returns false if the
+    // field cannot be changed by arrow keys.
+    fn change_new_board_field(&mut self, forward: bool) -> bool {
+        let b = &mut self.tmp_new_board;
+        match self.active_content {
+            2 => { b.background_color = hardware::helper_cycle_color(b.background_color, forward); },
+            3 => { b.foreground_color = hardware::helper_cycle_color(b.foreground_color, forward); },
+            4 => {
+                b.width = if forward { b.width + 1 } else { b.width.saturating_sub(1) }
+                    .max(NEW_BOARD_MIN_SIZE).min(NEW_BOARD_MAX_SIZE);
+            },
+            5 => {
+                b.height = if forward { b.height + 1 } else { b.height.saturating_sub(1) }
+                    .max(NEW_BOARD_MIN_SIZE).min(NEW_BOARD_MAX_SIZE);
+            },
+            _ => return false,
+        }
+        return true;
+    }
+    fn save_board(&mut self, index: usize, file_name: &str) {
+        let board = &mut self.boards[index];
+        self.tmp_save_status = Some(match board.to_file(file_name) {
+            Ok(_) => Ok(format!("Saved '{}' to '{}'", board.name, file_name)),
+            Err(err) => Err(format!("Cannot save '{}' to '{}': {}", board.name, file_name, err)),
+        });
+    }
     pub fn event_keypress(&mut self, c: char) -> bool {
         match self.active_tab {
+            2 => {
+                if let FocusArea::Content = self.current_focus_area {
+                    let b = &mut self.tmp_new_board;
+                    match self.active_content {
+                        1 => { b.name.push(c); },
+                        4 | 5 => {
+                            if let Some(d) = c.to_digit(10) {
+                                let size = if self.active_content == 4 { &mut b.width } else { &mut b.height };
+                                *size = (*size as u32 * 10 + d).min(NEW_BOARD_MAX_SIZE as u32) as u16;
+                            }
+                        },
+                        _ => {}
+                    }
+                }
+            },
             3 => {
                 if let FocusArea::Content = self.current_focus_area {
                     self.tmp_open_board_str.push(c);
@@ -75,6 +134,28 @@ impl BoardManager {
                     }
                 }
             },
+            4 => {
+                if let FocusArea::Field = self.current_focus_area {
+                    if let Some(board) = self.boards.get_mut(self.active_content-1) {
+                        return self.editor.event_keypress(board, c);
+                    }
+                }
+            },
+            5 => {
+                if let FocusArea::Content = self.current_focus_area {
+                    // Save directly to the source file, if there is one
+                    if c == 's' && self.active_content > 0 {
+                        let index = self.active_content - 1;
+                        match self.boards.get(index).map(|b| b.source_file.clone()) {
+                            Some(Some(file_name)) => self.save_board(index, &file_name),
+                            Some(None) => { self.event_keypress_special(KeyCode::Enter); },
+                            None => {},
+                        }
+                    }
+                } else if let FocusArea::Field = self.current_focus_area {
+                    self.tmp_save_board_str.push(c);
+                }
+            },
             6 => {
                 if let FocusArea::Field = self.current_focus_area {
                     if c == 'y' {
@@ -91,6 +172,15 @@ impl BoardManager {
         return false;
     }
     pub fn event_keypress_special(&mut self, c: KeyCode) -> bool {
+        if let FocusArea::Field = self.current_focus_area {
+            if self.active_tab == 4 {
+                if let Some(board) = self.boards.get_mut(self.active_content-1) {
+                    if self.editor.event_keypress_special(board, c) {
+                        return true;
+                    }
+                }
+            }
+        }
         match c {
             KeyCode::Up => {
                 if let FocusArea::Tab = self.current_focus_area {
@@ -128,19 +218,55 @@ impl BoardManager {
                 if let FocusArea::Tab = self.current_focus_area {
                     self.current_focus_area = FocusArea::Content;
                     self.active_content = 1;
+                    self.tmp_save_status = None;
                 } else if let FocusArea::Content = self.current_focus_area {
+                    if self.active_tab == 2 {
+                        let b = &mut self.tmp_new_board;
+                        b.width = b.width.max(NEW_BOARD_MIN_SIZE);
+                        b.height = b.height.max(NEW_BOARD_MIN_SIZE);
+                        if b.name.is_empty() {
+                            b.name = format!("Board {}", self.boards.len() + 1);
+                        }
+                        self.boards.push(self.tmp_new_board.clone());
+                        self.current_focus_area = FocusArea::Tab;
+                        self.active_tab = 1;
+                        self.active_content = 0;
+                        self.items_content = 0;
+                        return true;
+                    }
                     if self.active_tab == 3 && self.tmp_open_board_str.len() > 0 {
                         self.tmp_open_board = hardware::Board::from_file(
                             self.tmp_open_board_str.as_str()
                         );
+                        self.tmp_open_board_issues = hardware::validate::check_board_file(
+                            self.tmp_open_board_str.as_str()
+                        ).unwrap_or_default();
                         self.current_focus_area = FocusArea::Field;
                         self.active_field = 1;
                     }
                     if self.active_tab != 3 && self.items_content > 0 {
                         self.current_focus_area = FocusArea::Field;
                         self.active_field = 1;
+                        if self.active_tab == 4 {
+                            self.editor.reset();
+                        }
+                        if self.active_tab == 5 {
+                            if let Some(b) = self.boards.get(self.active_content.wrapping_sub(1)) {
+                                self.tmp_save_board_str = match &b.source_file {
+                                    Some(file_name) => file_name.clone(),
+                                    None => format!("{}.json", b.name.to_lowercase().replace(' ', "-")),
+                                };
+                            }
+                        }
                     }
                 } else if let FocusArea::Field = self.current_focus_area {
+                    if self.active_tab == 5 && self.tmp_save_board_str.len() > 0 {
+                        let file_name = self.tmp_save_board_str.clone();
+                        self.save_board(self.active_content-1, &file_name);
+                        self.current_focus_area = FocusArea::Content;
+                        self.active_field = 0;
+                        self.items_field = 0;
+                    }
                     if self.active_tab == 3 {
                         if self.tmp_open_board.as_ref().is_ok() {
                             self.boards.push(self.tmp_open_board.as_ref().unwrap().clone());
@@ -167,12 +293,34 @@ impl BoardManager {
                 }
                 return true;
             },
+            KeyCode::Left | KeyCode::Right => {
+                if let FocusArea::Content = self.current_focus_area {
+                    if self.active_tab == 2 {
+                        return self.change_new_board_field(c == KeyCode::Right);
+                    }
+                }
+                return false;
+            },
             KeyCode::Backspace => {
                 if let FocusArea::Content = self.current_focus_area {
+                    if self.active_tab == 2 {
+                        let b = &mut self.tmp_new_board;
+                        match self.active_content {
+                            1 => { b.name.pop(); },
+                            4 => { b.width /= 10; },
+                            5 => { b.height /= 10; },
+                            _ => {}
+                        }
+                    }
                     if self.active_tab == 3 && self.tmp_open_board_str.len() > 0 {
                         self.tmp_open_board_str.pop();
                     }
                 }
+                if let FocusArea::Field = self.current_focus_area {
+                    if self.active_tab == 5 {
+                        self.tmp_save_board_str.pop();
+                    }
+                }
                 return true;
             },
             _ => {
@@ -180,6 +328,16 @@ impl BoardManager {
             }
         }
     }
+    pub fn event_mouse(&mut self, event: MouseEvent) -> bool {
+        if let FocusArea::Field = self.current_focus_area {
+            if self.active_tab == 4 {
+                if let Some(board) = self.boards.get_mut(self.active_content-1) {
+                    return self.editor.event_mouse(board, event);
+                }
+            }
+        }
+        return false;
+    }
     fn render_list(
         &mut self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>, area: Rect,
         show_selection: bool
@@ -200,7 +358,11 @@ impl BoardManager {
             };
             Row::StyledData(vec![
                 (i+1).to_string(),
-                board.name.clone(),
+                if board.source_file.is_some() {
+                    board.name.clone()
+                } else {
+                    format!("{} (temporary)", board.name)
+                },
                 board.width.to_string() + "x" + &board.height.to_string(),
                 board.get_hardware_summary(),
             ].into_iter(), style)
@@ -295,16 +457,78 @@ impl BoardManager {
                     self.render_list(f, tc_layout[1], true);
                 }
             },
+            2 => {
+                self.items_content = 5;
+                if let FocusArea::Tab = self.current_focus_area {
+                    self.reset_new_board();
+                }
+                let form_layout = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints([
+                        Constraint::Length(9),     // Form
+                        Constraint::Min(1),        // Preview
+                    ].as_ref())
+                    .split(tc_layout[1]);
+                let mut field_styles = vec![];
+                for i in 0..5 {
+                    if let FocusArea::Content = self.current_focus_area {
+                        if i == self.active_content - 1 {
+                            field_styles.push(Self::style_hover());
+                            continue;
+                        }
+                    }
+                    field_styles.push(Self::style_normal());
+                }
+                let b = &self.tmp_new_board;
+                Paragraph::new([
+                    Text::styled("Create a new empty board\n\n",
+                        Style::default().modifier(Modifier::BOLD)
+                    ),
+                    Text::raw("Name:              "),
+                    Text::styled(format!("{} \n", b.name), field_styles[0]),
+                    Text::raw("Background color:  "),
+                    Text::styled(format!("< {} >\n", hardware::helper_color_to_str(b.background_color)), field_styles[1]),
+                    Text::raw("Foreground color:  "),
+                    Text::styled(format!("< {} >\n", hardware::helper_color_to_str(b.foreground_color)), field_styles[2]),
+                    Text::raw("Width:             "),
+                    Text::styled(format!("{} \n", b.width), field_styles[3]),
+                    Text::raw("Height:            "),
+                    Text::styled(format!("{} \n", b.height), field_styles[4]),
+                    Text::raw("\n<Left/Right Arrow> to change value, <Enter> to create the board"),
+                ].iter())
+                    .wrap(true)
+                    .render(f, form_layout[0]);
+                if let FocusArea::Content = self.current_focus_area {
+                    b.render(f, form_layout[1]);
+                }
+            },
             3 => {
                 if let FocusArea::Field = self.current_focus_area {
+                    let issues_height = if self.tmp_open_board_issues.is_empty() {
+                        0
+                    } else {
+                        std::cmp::min(self.tmp_open_board_issues.len(), 8) as u16 + 1
+                    };
                     let preview_layout = Layout::default()
                         .direction(Direction::Vertical)
                         .constraints([
                             Constraint::Length(2),     // Heading
+                            Constraint::Length(issues_height), // Validation issues
                             Constraint::Min(1),        // Content
                             Constraint::Length(2),     // Bottom Question
                         ].as_ref())
                         .split(tc_layout[1]);
+                    let mut issues_text = vec![];
+                    for issue in self.tmp_open_board_issues.iter().take(8) {
+                        issues_text.push(Text::styled(format!("{}\n", issue), Style::default().fg(
+                            match issue.severity {
+                                hardware::erc::Severity::Error => Color::LightRed,
+                                hardware::erc::Severity::Warning => Color::LightYellow,
+                            }
+                        )));
+                    }
+                    Paragraph::new(issues_text.iter())
+                        .render(f, preview_layout[1]);
                     if self.tmp_open_board.is_ok() {
                         let b = self.tmp_open_board.as_ref().unwrap();
                         Paragraph::new([
@@ -320,13 +544,13 @@ impl BoardManager {
                         ].iter())
                             .wrap(true)
                             .render(f, preview_layout[0]);
-                        b.render(f, preview_layout[1]);
+                        b.render(f, preview_layout[2]);
                         Paragraph::new([
                             Text::raw("Do you want to add this board?\n"),
                             Text::raw("<y> Yes        <n> No\n"),
                         ].iter())
                             .wrap(true)
-                            .render(f, preview_layout[2]);
+                            .render(f, preview_layout[3]);
                     } else {
                         let err = self.tmp_open_board.as_ref().err().unwrap();
                         Paragraph::new([
@@ -346,12 +570,12 @@ impl BoardManager {
                             Text::raw(format!("{}", err)),
                         ].iter())
                             .wrap(true)
-                            .render(f, preview_layout[1]);
+                            .render(f, preview_layout[2]);
                         Paragraph::new([
                             Text::raw("<Enter> Exit        <Esc> Modify input file path\n"),
                         ].iter())
                             .wrap(true)
-                            .render(f, preview_layout[2]);
+                            .render(f, preview_layout[3]);
                     }
                 } else {
                     Paragraph::new([
@@ -374,6 +598,50 @@ impl BoardManager {
                         .render(f, tc_layout[1]);
                 }
             },
+            4 => {
+                if let FocusArea::Field = self.current_focus_area {
+                    let b = &self.boards[self.active_content-1];
+                    self.editor.render(b, f, tc_layout[1]);
+                } else {
+                    self.render_list(f, tc_layout[1], true);
+                }
+            },
+            5 => {
+                if let FocusArea::Field = self.current_focus_area {
+                    let b = &self.boards[self.active_content-1];
+                    Paragraph::new([
+                        Text::styled(format!("Save board '{}' as JSON file\n\n", b.name),
+                            Style::default().modifier(Modifier::BOLD)
+                        ),
+                        Text::raw("File: "),
+                        Text::styled(self.tmp_save_board_str.clone(),
+                            Style::default().modifier(Modifier::UNDERLINED)
+                        ),
+                        Text::styled(" ", Self::style_hover()),
+                        Text::raw("\n\n<Enter> Save        <Esc> Cancel"),
+                    ].iter())
+                        .wrap(true)
+                        .render(f, tc_layout[1]);
+                } else {
+                    let save_layout = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([
+                            Constraint::Min(1),        // Board list
+                            Constraint::Length(2),     // Status
+                        ].as_ref())
+                        .split(tc_layout[1]);
+                    self.render_list(f, save_layout[0], true);
+                    Paragraph::new([
+                        match &self.tmp_save_status {
+                            Some(Ok(msg)) => Text::styled(msg.clone(), Style::default().fg(Color::LightGreen)),
+                            Some(Err(msg)) => Text::styled(msg.clone(), Style::default().fg(Color::LightRed)),
+                            None => Text::raw("<Enter> Save as        <s> Save to source file"),
+                        },
+                    ].iter())
+                        .wrap(true)
+                        .render(f, save_layout[1]);
+                }
+            },
             6 => {
                 if let FocusArea::Field = self.current_focus_area {
                     Paragraph::new([
@@ -408,3 +676,29 @@ impl BoardManager {
             .render(f, tc_layout[2]);
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn BoardManager__event_keypress__save() {
+        let mut manager = BoardManager::default();
+        manager.active_tab = 5;
+        manager.current_focus_area = FocusArea::Content;
+        manager.active_content = 1;
+        // The board list may have shrunk since the tab has been opened
+        manager.items_content = 1;
+        manager.event_keypress('s');
+        manager.event_keypress_special(KeyCode::Enter);
+        assert!(manager.tmp_save_status.is_none());
+        assert!(manager.tmp_save_board_str.is_empty());
+        // Boards without source file ask for a file name
+        manager.boards.push(hardware::Board::default());
+        manager.current_focus_area = FocusArea::Content;
+        manager.event_keypress('s');
+        assert!(matches!(manager.current_focus_area, FocusArea::Field));
+        assert_eq!(manager.tmp_save_board_str, "board-$n.json");
+    }
+}
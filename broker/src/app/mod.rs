@@ -1,12 +1,18 @@
 /*!mod.rs
- * App lib. Contains BoardManager and Help.
+ * App lib. Contains BoardEditor, BoardManager, Help, Preferences and Sessions.
  * 
  * Author: Patrick Goldinger
  * License: GPL 3.0 (see LICENSE file for details)
  */
 
+pub mod board_editor;
 pub mod board_manager;
 pub mod help;
+pub mod preferences;
+pub mod sessions;
 
+pub use board_editor::BoardEditor;
 pub use board_manager::BoardManager;
 pub use help::Help;
+pub use preferences::Preferences;
+pub use sessions::Sessions;
@@ -0,0 +1,405 @@
+/*!preferences.rs
+ * Loads, edits and persists the broker preferences and renders the
+ * preferences UI. The available preferences are described by
+ * config/prefs_config.json.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use crossterm::event::KeyCode;
+use serde_json::{Map, Value as SerdeValue};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Write};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
+use tui::Frame;
+
+const PREFS_CONFIG: &str = include_str!("../../config/prefs_config.json");
+const PREFS_FILE_NAME: &str = "prefs.json";
+
+enum PrefKind {
+    Dropdown { options: Vec<String> },
+    Slider { min: u64, max: u64, step: u64, unit: String },
+    Text,
+}
+
+struct Pref {
+    key: String,
+    label: String,
+    kind: PrefKind,
+    default_value: SerdeValue,
+}
+
+impl Pref {
+    fn from_json(map: &Map<String, SerdeValue>) -> Option<Self> {
+        let key = map.get("key")?.as_str()?.to_owned();
+        let label = map.get("label").and_then(|v| v.as_str()).unwrap_or(&key).to_owned();
+        let default_value = map.get("defaultValue")?.clone();
+        let kind = match map.get("type")?.as_str()? {
+            "pref/dropdown" => PrefKind::Dropdown {
+                options: map.get("options")?.as_array()?.iter()
+                    .filter_map(|o| o.as_str().map(|o| o.to_owned()))
+                    .collect(),
+            },
+            "pref/slider" => PrefKind::Slider {
+                min: map.get("min")?.as_u64()?,
+                max: map.get("max")?.as_u64()?,
+                step: map.get("step").and_then(|v| v.as_u64()).unwrap_or(1).max(1),
+                unit: map.get("unit").and_then(|v| v.as_str()).unwrap_or("").to_owned(),
+            },
+            "pref/text" => PrefKind::Text,
+            _ => return None,
+        };
+        Some(Self { key, label, kind, default_value })
+    }
+    fn is_valid(&self, value: &SerdeValue) -> bool {
+        match &self.kind {
+            PrefKind::Dropdown { options } => {
+                value.as_str().map_or(false, |v| options.iter().any(|o| o == v))
+            },
+            PrefKind::Slider { min, max, .. } => {
+                value.as_u64().map_or(false, |v| v >= *min && v <= *max)
+            },
+            PrefKind::Text => value.is_string(),
+        }
+    }
+}
+
+enum PrefEntry {
+    Heading { text: String, depth: usize },
+    Pref { pref: Pref, depth: usize },
+}
+
+pub struct Preferences {
+    active_pref: usize,
+    entries: Vec<PrefEntry>,
+    file_path: Option<String>,
+    last_error: Option<String>,
+    values: Map<String, SerdeValue>,
+}
+
+impl Default for Preferences {
+    fn default() -> Self {
+        let mut entries = vec![];
+        if let Ok(config) = serde_json::from_str::<SerdeValue>(PREFS_CONFIG) {
+            Self::parse_config(&config, 0, &mut entries);
+        }
+        let mut prefs = Self {
+            active_pref: 0,
+            entries,
+            file_path: utils::get_simpi_dir().map(|dir| {
+                format!("{}{}{}", dir, std::path::MAIN_SEPARATOR, PREFS_FILE_NAME)
+            }),
+            last_error: None,
+            values: Map::new(),
+        };
+        prefs.active_pref = prefs.pref_indices().first().cloned().unwrap_or(0);
+        if let Err(err) = prefs.load() {
+            prefs.last_error = Some(format!("Cannot load preferences: {}", err));
+        }
+        prefs
+    }
+}
+
+impl Preferences {
+    fn style_normal() -> Style {
+        Style::default()
+    }
+    fn style_hover() -> Style {
+        Style::default()
+            .fg(Color::White).bg(Color::Red)
+    }
+    fn parse_config(json: &SerdeValue, depth: usize, entries: &mut Vec<PrefEntry>) {
+        let map = match json.as_object() {
+            Some(map) => map,
+            None => return,
+        };
+        match map.get("type").and_then(|v| v.as_str()) {
+            Some("group") => {
+                if let Some(heading) = map.get("heading").and_then(|v| v.as_str()) {
+                    entries.push(PrefEntry::Heading { text: heading.to_owned(), depth });
+                }
+                if let Some(children) = map.get("children").and_then(|v| v.as_array()) {
+                    for child in children.iter() {
+                        Self::parse_config(child, depth + 1, entries);
+                    }
+                }
+            },
+            Some(_) => {
+                if let Some(pref) = Pref::from_json(map) {
+                    entries.push(PrefEntry::Pref { pref, depth });
+                }
+            },
+            None => {}
+        }
+    }
+    fn pref_indices(&self) -> Vec<usize> {
+        self.entries.iter().enumerate()
+            .filter(|(_, e)| if let PrefEntry::Pref { .. } = e { true } else { false })
+            .map(|(i, _)| i)
+            .collect()
+    }
+    fn find_pref(&self, key: &str) -> Option<&Pref> {
+        self.entries.iter().find_map(|e| match e {
+            PrefEntry::Pref { pref, .. } if pref.key == key => Some(pref),
+            _ => None,
+        })
+    }
+    // Returns the current value of the given preference, falling back to its
+    // default value if the stored value is missing or invalid.
+    pub fn get(&self, key: &str) -> Option<&SerdeValue> {
+        let pref = self.find_pref(key)?;
+        match self.values.get(key) {
+            Some(value) if pref.is_valid(value) => Some(value),
+            _ => Some(&pref.default_value),
+        }
+    }
+    pub fn get_u64(&self, key: &str) -> Option<u64> {
+        self.get(key).and_then(|v| v.as_u64())
+    }
+    pub fn get_str(&self, key: &str) -> Option<&str> {
+        self.get(key).and_then(|v| v.as_str())
+    }
+    fn set(&mut self, key: &str, value: SerdeValue) {
+        self.values.insert(key.to_owned(), value);
+        self.last_error = match self.save() {
+            Ok(_) => None,
+            Err(err) => Some(format!("Cannot save preferences: {}", err)),
+        };
+    }
+    fn load(&mut self) -> Result<(), Error> {
+        let file_path = match &self.file_path {
+            Some(file_path) => file_path,
+            None => return Ok(()),
+        };
+        let mut data = String::new();
+        match File::open(file_path) {
+            Ok(mut file) => { file.read_to_string(&mut data)?; },
+            Err(ref err) if err.kind() == ErrorKind::NotFound => return Ok(()),
+            Err(err) => return Err(err),
+        }
+        match serde_json::from_str(data.as_ref()) {
+            Ok(SerdeValue::Object(values)) => {
+                self.values = values;
+                return Ok(());
+            },
+            Ok(_) => {
+                return Err(Error::new(ErrorKind::InvalidData, "Expected a JSON object"));
+            },
+            Err(err) => {
+                return Err(Error::new(ErrorKind::InvalidData, format!("{}", err)));
+            },
+        }
+    }
+    fn save(&self) -> Result<(), Error> {
+        let file_path = match &self.file_path {
+            Some(file_path) => file_path,
+            None => {
+                return Err(Error::new(ErrorKind::NotFound, "No user config directory available"));
+            },
+        };
+        let data = serde_json::to_string_pretty(&self.values)?;
+        let mut file = File::create(file_path)?;
+        file.write_all(data.as_bytes())?;
+        return Ok(());
+    }
+    // Changes the selected preference by the given number of steps/options.
+    fn change_active(&mut self, forward: bool) {
+        let (key, value) = match self.entries.get(self.active_pref) {
+            Some(PrefEntry::Pref { pref, .. }) => {
+                let current = self.get(&pref.key).cloned().unwrap_or(SerdeValue::Null);
+                let value = match &pref.kind {
+                    PrefKind::Dropdown { options } => {
+                        if options.is_empty() {
+                            return;
+                        }
+                        let i = options.iter().position(|o| Some(o.as_str()) == current.as_str())
+                            .unwrap_or(0);
+                        let i = if forward {
+                            (i + 1) % options.len()
+                        } else {
+                            (i + options.len() - 1) % options.len()
+                        };
+                        SerdeValue::from(options[i].clone())
+                    },
+                    PrefKind::Slider { min, max, step, .. } => {
+                        let v = current.as_u64().unwrap_or(*min);
+                        let v = if forward {
+                            std::cmp::min(v.saturating_add(*step), *max)
+                        } else {
+                            std::cmp::max(v.saturating_sub(*step), *min)
+                        };
+                        SerdeValue::from(v)
+                    },
+                    PrefKind::Text => return,
+                };
+                (pref.key.clone(), value)
+            },
+            _ => return,
+        };
+        self.set(&key, value);
+    }
+    fn edit_active_text(&mut self, c: Option<char>) -> bool {
+        let (key, mut text) = match self.entries.get(self.active_pref) {
+            Some(PrefEntry::Pref { pref, .. }) => match pref.kind {
+                PrefKind::Text => {
+                    (pref.key.clone(), self.get_str(&pref.key).unwrap_or("").to_owned())
+                },
+                _ => return false,
+            },
+            _ => return false,
+        };
+        match c {
+            Some(c) => text.push(c),
+            None => { text.pop(); },
+        }
+        self.set(&key, SerdeValue::from(text));
+        return true;
+    }
+    pub fn event_keypress(&mut self, c: char) -> bool {
+        return self.edit_active_text(Some(c));
+    }
+    pub fn event_keypress_special(&mut self, c: KeyCode) -> bool {
+        let indices = self.pref_indices();
+        let pos = indices.iter().position(|&i| i == self.active_pref);
+        match c {
+            KeyCode::Up => {
+                if let Some(pos) = pos {
+                    if pos > 0 {
+                        self.active_pref = indices[pos - 1];
+                    }
+                }
+                return true;
+            },
+            KeyCode::Down => {
+                if let Some(pos) = pos {
+                    if pos + 1 < indices.len() {
+                        self.active_pref = indices[pos + 1];
+                    }
+                }
+                return true;
+            },
+            KeyCode::Left => {
+                self.change_active(false);
+                return true;
+            },
+            KeyCode::Right => {
+                self.change_active(true);
+                return true;
+            },
+            KeyCode::Backspace => {
+                return self.edit_active_text(None);
+            },
+            _ => {
+                return false;
+            }
+        }
+    }
+    fn value_to_styled(&self, pref: &Pref, is_active: bool, data: &mut Vec<Text>) {
+        let style = if is_active { Self::style_hover() } else { Self::style_normal() };
+        let value = self.get(&pref.key).cloned().unwrap_or(SerdeValue::Null);
+        match &pref.kind {
+            PrefKind::Dropdown { .. } => {
+                data.push(Text::styled(
+                    format!("< {} >", value.as_str().unwrap_or("")), style
+                ));
+            },
+            PrefKind::Slider { min, max, unit, .. } => {
+                let v = value.as_u64().unwrap_or(*min);
+                let width = 20;
+                let filled = if max > min {
+                    ((v - min) * width / (max - min)) as usize
+                } else {
+                    width as usize
+                };
+                data.push(Text::raw("["));
+                data.push(Text::styled("=".repeat(filled), style));
+                data.push(Text::styled("-".repeat(width as usize - filled),
+                    Style::default().fg(Color::DarkGray)
+                ));
+                data.push(Text::raw(format!("] {} {}", v, unit)));
+            },
+            PrefKind::Text => {
+                data.push(Text::styled(value.as_str().unwrap_or("").to_owned(),
+                    Style::default().modifier(Modifier::UNDERLINED)
+                ));
+                if is_active {
+                    data.push(Text::styled(" ", Self::style_hover()));
+                }
+            },
+        }
+        data.push(Text::raw("\n"));
+    }
+    pub fn render(
+        &mut self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>, area: Rect
+    ) {
+        let prefs_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([
+                Constraint::Min(1),        // Preference list
+                Constraint::Length(2),     // Bottom toolbar
+            ].as_ref())
+            .split(area);
+        Block::default()
+            .title(" Preferences ")
+            .borders(Borders::ALL)
+            .render(f, area);
+        let mut data = vec![];
+        for (i, entry) in self.entries.iter().enumerate() {
+            match entry {
+                PrefEntry::Heading { text, depth } => {
+                    data.push(Text::styled(
+                        format!("{}{}\n", "  ".repeat(depth.saturating_sub(1)), text),
+                        Style::default().modifier(Modifier::BOLD)
+                    ));
+                },
+                PrefEntry::Pref { pref, depth } => {
+                    let indent = "  ".repeat(depth.saturating_sub(1));
+                    data.push(Text::raw(format!("{}{:<24}", indent, pref.label)));
+                    self.value_to_styled(pref, i == self.active_pref, &mut data);
+                },
+            }
+        }
+        Paragraph::new(data.iter())
+            .block(Block::default())
+            .render(f, prefs_layout[0]);
+        // Draw footer
+        let footer = match &self.last_error {
+            Some(err) => Text::styled(err.clone(), Style::default().fg(Color::LightRed)),
+            None => Text::raw(
+                "<Up/Down Arrow> to select, <Left/Right Arrow> to change value, type to edit text"
+            ),
+        };
+        Paragraph::new([footer].iter())
+            .block(Block::default().borders(Borders::TOP))
+            .render(f, prefs_layout[1]);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefs() -> Preferences {
+        let mut prefs = Preferences::default();
+        prefs.file_path = None;
+        prefs.values = Map::new();
+        prefs
+    }
+
+    #[test]
+    fn Preferences__get() {
+        let mut prefs = prefs();
+        assert_eq!(prefs.get_str("general__theme"), Some("auto"));
+        prefs.values.insert("sync__update_timeout_ms".to_owned(), SerdeValue::from(5000));
+        assert_eq!(prefs.get_u64("sync__update_timeout_ms"), Some(50));
+        prefs.values.insert("sync__update_timeout_ms".to_owned(), SerdeValue::from(200));
+        assert_eq!(prefs.get_u64("sync__update_timeout_ms"), Some(200));
+        assert_eq!(prefs.get("unknown__key"), None);
+    }
+}
@@ -0,0 +1,114 @@
+/*!sessions.rs
+ * Lists all shared memory sessions and renders the sessions UI.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use crossterm::event::KeyCode;
+use tui::backend::CrosstermBackend;
+use tui::layout::{Constraint, Direction, Layout, Rect};
+use tui::style::{Color, Modifier, Style};
+use tui::widgets::{Block, Borders, Paragraph, Row, Table, Text, Widget};
+use tui::Frame;
+use utils::SessionInfo;
+
+pub struct Sessions {
+    current: String,
+    sessions: Vec<SessionInfo>,
+}
+
+impl Sessions {
+    pub fn new(current: &str) -> Self {
+        Self {
+            current: current.to_owned(),
+            sessions: vec![],
+        }
+    }
+    fn style_normal() -> Style {
+        Style::default()
+    }
+    fn style_current() -> Style {
+        Style::default()
+            .fg(Color::White).bg(Color::Red)
+    }
+    fn display_name(name: &str) -> String {
+        if name.is_empty() {
+            String::from("(default)")
+        } else {
+            name.to_owned()
+        }
+    }
+    pub fn refresh(&mut self) {
+        self.sessions = utils::list_sessions();
+    }
+    pub fn event_keypress(&mut self, c: char) -> bool {
+        if c == 'r' {
+            self.refresh();
+            return true;
+        }
+        return false;
+    }
+    pub fn event_keypress_special(&mut self, _c: KeyCode) -> bool {
+        return false;
+    }
+    pub fn render(
+        &mut self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>, area: Rect
+    ) {
+        let sessions_layout = Layout::default()
+            .direction(Direction::Vertical)
+            .margin(1)
+            .constraints([
+                Constraint::Length(2),     // Heading
+                Constraint::Min(1),        // Session list
+                Constraint::Length(2),     // Bottom toolbar
+            ].as_ref())
+            .split(area);
+        Block::default()
+            .title(" Sessions ")
+            .borders(Borders::ALL)
+            .render(f, area);
+        Paragraph::new([
+            Text::styled("This broker is attached to session ",
+                Style::default().modifier(Modifier::BOLD)
+            ),
+            Text::styled(Self::display_name(&self.current),
+                Style::default().modifier(Modifier::BOLD)
+            ),
+        ].iter())
+            .wrap(true)
+            .render(f, sessions_layout[0]);
+        let table_header = [
+            "Session", "Status", "Link file"
+        ];
+        let current = self.current.clone();
+        let table_rows = self.sessions.iter().map(|session| {
+            let style = if session.name == current {
+                Self::style_current()
+            } else {
+                Self::style_normal()
+            };
+            Row::StyledData(vec![
+                Self::display_name(&session.name),
+                String::from(if session.is_active { "active" } else { "stale" }),
+                session.link_path.clone(),
+            ].into_iter(), style)
+        });
+        Table::new(table_header.iter(), table_rows)
+            .block(Block::default())
+            .widths(&[
+                Constraint::Length(34),
+                Constraint::Length(8),
+                Constraint::Min(20),
+            ])
+            .header_style(Style::default().modifier(Modifier::BOLD))
+            .column_spacing(1)
+            .render(f, sessions_layout[1]);
+        // Draw footer
+        Paragraph::new([
+            Text::raw("<r> Refresh list    Start a program with WPISIM_SESSION=<name> to attach it to a session"),
+        ].iter())
+            .block(Block::default().borders(Borders::TOP))
+            .render(f, sessions_layout[2]);
+    }
+}
@@ -1,14 +1,18 @@
 /*!board.rs
- * Board lib. Provides support for unpacking simpi json files
+ * Board lib. Provides support for unpacking simpi board files
  * 
  * Author: Patrick Goldinger
  * License: GPL 3.0 (see LICENSE file for details)
  */
 
-use super::{button::Button, led::Led, part::Part};
-use serde_json::{Value as SerdeValue};
-use std::fs::File;
-use std::io::{Error, ErrorKind, Read};
+use super::include;
+use super::model::{self, BoardModel, IncludeModel, PartModel, PositionModel, SizeModel};
+use super::part::Part;
+use crossterm::event::{MouseButton, MouseEvent};
+use serde_json::{Map, Value as SerdeValue};
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::time::SystemTime;
 use tui::backend::CrosstermBackend;
 use tui::layout::{Rect};
 use tui::style::{Color, Style};
@@ -16,6 +20,9 @@ use tui::widgets::{Block, Borders, Widget};
 use tui::Frame;
 use utils::gpioregs::RegMemory;
 
+// Gap between boards placed by the automatic layout
+const BOARD_LAYOUT_GAP: u16 = 1;
+
 #[derive(Clone)]
 pub struct Board {
     pub name: String,
@@ -24,6 +31,23 @@ pub struct Board {
     pub width: u16,
     pub height: u16,
     pub hardware: Vec<Part>,
+    // Position relative to the board area, None for automatic layout
+    pub position: Option<(u16, u16)>,
+    // File the board has been loaded from, None for temporary boards
+    pub source_file: Option<String>,
+    // Edited in the board editor since the board has been loaded or saved
+    pub is_modified: bool,
+    // Files included by the source file
+    include_files: Vec<String>,
+    // Latest modification time of the source and included files when the
+    // board has been loaded
+    source_mtime: Option<SystemTime>,
+    // Part defaults and includes, kept for writing the board back
+    defaults: Map<String, SerdeValue>,
+    include: Vec<IncludeModel>,
+    // Fields and parts unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
+    extra_hardware: Vec<SerdeValue>,
 }
 
 impl Default for Board {
@@ -35,143 +59,241 @@ impl Default for Board {
             width: 64,
             height: 24,
             hardware: vec![],
+            position: None,
+            source_file: None,
+            is_modified: false,
+            include_files: vec![],
+            source_mtime: None,
+            defaults: Map::new(),
+            include: vec![],
+            extra: Map::new(),
+            extra_hardware: vec![],
         }
     }
 }
 
 impl Board {
+    // Reads a board file, the format is selected by the file extension. The
+    // parts of included files are added after the parts of the board itself.
     pub fn from_file(file_name: &str) -> Result<Self, Error> {
-        match File::open(file_name) {
-            Ok(mut file) => {
-                let mut data = String::new();
-                match file.read_to_string(&mut data) {
-                    Ok(_) => {
-                        match serde_json::from_str(data.as_ref()) {
-                            Ok(data) => {
-                                return Board::from_json(data);
-                            },
-                            Err(err) => {
-                                return Err(Error::new(
-                                    ErrorKind::InvalidData,
-                                    format!("Error while deserializing given board: {}", err)
-                                ));
-                            }
-                        }
-                    },
-                    Err(err) => {
-                        return Err(err);
-                    }
-                }
-            },
-            Err(err) => {
-                return Err(err);
+        let resolved = include::resolve_file(file_name)?;
+        // Parts of unknown types are not part of the hardware
+        let is_known: Vec<bool> = resolved.board.get("hardware").and_then(|h| h.as_array())
+            .map_or(vec![], |parts| parts.iter().map(|part| {
+                part.get("type").and_then(|t| t.as_str()).map_or(false, |t| model::PART_TYPES.contains(&t))
+            }).collect());
+        let mut board = Board::from_json(resolved.board)?;
+        let defaulted = resolved.defaulted.into_iter().zip(is_known).filter(|(_, is_known)| *is_known);
+        for (part, (keys, _)) in board.hardware.iter_mut().zip(defaulted) {
+            part.set_defaulted(keys);
+        }
+        for (origin, part) in resolved.included.into_iter() {
+            match serde_json::from_value(part) {
+                Ok(PartModel::Known(part)) => {
+                    let mut part = Part::from_model(part);
+                    part.set_included_from(Some(origin));
+                    board.hardware.push(part);
+                },
+                // Included parts of unknown types are not shown
+                Ok(PartModel::Unknown(_)) => {},
+                Err(err) => return Err(Error::new(
+                    ErrorKind::InvalidData, format!("Cannot include '{}': {}", origin, err)
+                )),
             }
         }
+        board.source_file = Some(file_name.to_owned());
+        board.include_files = resolved.files;
+        board.source_mtime = board.latest_mtime();
+        return Ok(board);
     }
     pub fn from_json(json: SerdeValue) -> Result<Self, Error> {
-        match json {
-            SerdeValue::Object(map) => {
-                let mut board = Self::default();
-                let mut is_valid = false;
-                for (k, v) in map.iter() {
-                    match k.as_ref() {
-                        "type" => {
-                            if v.is_string() {
-                                if v.as_str().unwrap() == "simpi/board" {
-                                    is_valid = true;
-                                }
-                            }
-                        },
-                        "name" => {
-                            if v.is_string() {
-                                board.name = v.as_str().unwrap().to_owned();
-                            }
-                        },
-                        "backgroundColor" => {
-                            if v.is_string() {
-                                let c = super::helper_str_to_color(v.as_str().unwrap().to_owned());
-                                if c.is_ok() {
-                                    board.background_color = c.unwrap();
-                                }
-                            }
-                        },
-                        "foregroundColor" => {
-                            if v.is_string() {
-                                let c = super::helper_str_to_color(v.as_str().unwrap().to_owned());
-                                if c.is_ok() {
-                                    board.foreground_color = c.unwrap();
-                                }
-                            }
-                        },
-                        "size" => {
-                            if v.is_object() {
-                                let s = v.as_object().unwrap();
-                                for (k, v) in s.iter() {
-                                    match k.as_ref() {
-                                        "width" => {
-                                            if v.is_u64() {
-                                                board.width = v.as_u64().unwrap() as u16;
-                                            }
-                                        },
-                                        "height" => {
-                                            if v.is_u64() {
-                                                board.height = v.as_u64().unwrap() as u16;
-                                            }
-                                        },
-                                        _ => {}
-                                    }
-                                }
-                            }
-                        },
-                        "hardware" => {
-                            if v.is_array() {
-                                let h = v.as_array().unwrap();
-                                for part in h.iter() {
-                                    if part.is_object() {
-                                        let part_e = part.as_object().unwrap();
-                                        let ptype = part_e.get("type");
-                                        if ptype.is_some() {
-                                            let ptype = ptype.unwrap();
-                                            if ptype.is_string() {
-                                                match ptype.as_str().unwrap() {
-                                                    "simpi/led" => {
-                                                        let p = Led::from_json(part.clone());
-                                                        if p.is_ok() {
-                                                            board.hardware.push(Part::Led(p.unwrap()));
-                                                        }
-                                                    },
-                                                    "simpi/button" => {
-                                                        let p = Button::from_json(part.clone());
-                                                        if p.is_ok() {
-                                                            board.hardware.push(Part::Button(p.unwrap()));
-                                                        }
-                                                    },
-                                                    _ => {}
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                        },
-                        _ => {}
-                    }
-                }
-                if is_valid {
-                    Ok(board)
-                } else {
-                    Err(Error::new(ErrorKind::InvalidData, "Input data is invalid!"))
+        match serde_json::from_value(json) {
+            Ok(model) => Board::from_model(model),
+            Err(err) => Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("Error while deserializing given board: {}", err)
+            )),
+        }
+    }
+    pub fn from_model(model: BoardModel) -> Result<Self, Error> {
+        if model.kind != "simpi/board" {
+            return Err(Error::new(ErrorKind::InvalidData, "Input data is invalid!"));
+        }
+        let mut board = Self::default();
+        if let Some(name) = model.name {
+            board.name = name;
+        }
+        if let Some(c) = model.background_color.and_then(|c| super::helper_str_to_color(c).ok()) {
+            board.background_color = c;
+        }
+        if let Some(c) = model.foreground_color.and_then(|c| super::helper_str_to_color(c).ok()) {
+            board.foreground_color = c;
+        }
+        if let Some(size) = model.size {
+            board.width = size.width.unwrap_or(board.width);
+            board.height = size.height.unwrap_or(board.height);
+        }
+        board.position = model.position.map(|p| (p.x, p.y));
+        for part in model.hardware.into_iter() {
+            match part {
+                PartModel::Known(part) => {
+                    board.hardware.push(Part::from_model(part));
+                },
+                PartModel::Unknown(part) => {
+                    board.extra_hardware.push(part);
+                },
+            }
+        }
+        board.defaults = model.defaults;
+        board.include = model.include;
+        board.extra = model.extra;
+        return Ok(board);
+    }
+    pub fn to_json(&self) -> SerdeValue {
+        serde_json::to_value(self.to_model()).unwrap_or(SerdeValue::Null)
+    }
+    // Parts of included files are not part of the model, only the includes.
+    // Fields of the parts filled in from the defaults are left out if they
+    // have not been changed, unless the part cannot be read without them.
+    pub fn to_model(&self) -> BoardModel {
+        let own_parts = self.hardware.iter().filter(|part| part.included_from().is_none());
+        let mut hardware: Vec<PartModel> = own_parts.map(|part| {
+            let model = part.to_model();
+            let mut json = serde_json::to_value(&model).unwrap_or(SerdeValue::Null);
+            include::strip_defaults(&mut json, &self.defaults, part.defaulted());
+            PartModel::Known(serde_json::from_value(json).unwrap_or(model))
+        }).collect();
+        hardware.extend(self.extra_hardware.iter().cloned().map(PartModel::Unknown));
+        BoardModel {
+            kind: String::from("simpi/board"),
+            name: Some(self.name.clone()),
+            background_color: Some(super::helper_color_to_str(self.background_color)),
+            foreground_color: Some(super::helper_color_to_str(self.foreground_color)),
+            size: Some(SizeModel { width: Some(self.width), height: Some(self.height) }),
+            position: self.position.map(|(x, y)| PositionModel { x, y }),
+            defaults: self.defaults.clone(),
+            include: self.include.clone(),
+            hardware,
+            extra: self.extra.clone(),
+        }
+    }
+    // Writes the board to the given file and remembers it as source file. The
+    // format is selected by the file extension.
+    pub fn to_file(&mut self, file_name: &str) -> Result<(), Error> {
+        model::write_file(file_name, &self.to_model())?;
+        self.source_file = Some(file_name.to_owned());
+        self.is_modified = false;
+        self.source_mtime = self.latest_mtime();
+        return Ok(());
+    }
+    fn latest_mtime(&self) -> Option<SystemTime> {
+        self.source_file.iter().chain(self.include_files.iter())
+            .filter_map(|file_name| fs::metadata(file_name).and_then(|m| m.modified()).ok())
+            .max()
+    }
+    // Re-reads the board from its source file if the file or one of its
+    // included files has been modified since it was loaded. Button states are
+    // kept for parts with the same type and name. On error or if the board
+    // has unsaved changes of the board editor, the board stays untouched.
+    // Returns None if the file has not changed.
+    pub fn reload_if_changed(&mut self) -> Option<Result<(), Error>> {
+        let file_name = self.source_file.clone()?;
+        let mtime = self.latest_mtime()?;
+        if self.source_mtime == Some(mtime) {
+            return None;
+        }
+        self.source_mtime = Some(mtime);
+        if self.is_modified {
+            return Some(Err(Error::new(
+                ErrorKind::Other, "the board has unsaved changes, save it to overwrite the file"
+            )));
+        }
+        let mut board = match Board::from_file(&file_name) {
+            Ok(board) => board,
+            Err(err) => return Some(Err(err)),
+        };
+        for part in board.hardware.iter_mut() {
+            let old_part = self.hardware.iter().find(|p| {
+                p.type_name() == part.type_name() && p.name() == part.name()
+            });
+            if let (Part::Button(button), Some(Part::Button(old_button))) = (part, old_part) {
+                button.set(old_button.get());
+            }
+        }
+        *self = board;
+        return Some(Ok(()));
+    }
+    // Computes the area of each board. Boards with a position are placed
+    // relative to the given area, all others flow from left to right around
+    // them and wrap into the next row. Areas are clipped to the given area,
+    // boards outside of it get an empty area.
+    pub fn layout(boards: &[Board], area: Rect) -> Vec<Rect> {
+        let fixed: Vec<Rect> = boards.iter().filter_map(|b| b.position.map(|(x, y)| {
+            Rect { x, y, width: b.width, height: b.height }
+        })).collect();
+        let mut x = 0;
+        let mut y = 0;
+        let mut row_height = 0;
+        let mut rects = vec![];
+        for board in boards.iter() {
+            let rect = match board.position {
+                Some((x, y)) => Rect { x, y, width: board.width, height: board.height },
+                None => {
+                    let rect = loop {
+                        if x > 0 && x + board.width > area.width {
+                            x = 0;
+                            y += row_height + BOARD_LAYOUT_GAP;
+                            row_height = 0;
+                        }
+                        let rect = Rect { x, y, width: board.width, height: board.height };
+                        match fixed.iter().find(|f| f.intersects(rect)) {
+                            Some(f) => { x = f.x + f.width + BOARD_LAYOUT_GAP; },
+                            None => break rect,
+                        }
+                    };
+                    x += board.width + BOARD_LAYOUT_GAP;
+                    row_height = std::cmp::max(row_height, board.height);
+                    rect
+                },
+            };
+            rects.push(if rect.x >= area.width || rect.y >= area.height {
+                Rect { x: area.x, y: area.y, width: 0, height: 0 }
+            } else {
+                Rect {
+                    x: area.x + rect.x,
+                    y: area.y + rect.y,
+                    width: std::cmp::min(rect.width, area.width - rect.x),
+                    height: std::cmp::min(rect.height, area.height - rect.y),
                 }
-            },
-            _ => Err(Error::new(ErrorKind::InvalidInput, "Input must be map!"))
+            });
         }
+        rects
     }
     pub fn event_keypress(&mut self, c: char) -> &mut Self {
         for part in self.hardware.iter_mut() {
             match part {
                 Part::Button(button) => {
                     if button.hotkey == c.to_string() {
-                        button.set(!button.get());
+                        button.press();
+                    }
+                },
+                Part::Potentiometer(pot) => {
+                    if pot.hotkey_up == c.to_string() {
+                        pot.turn(true);
+                    } else if pot.hotkey_down == c.to_string() {
+                        pot.turn(false);
+                    }
+                },
+                Part::Keypad(keypad) => {
+                    keypad.press(c);
+                },
+                Part::Encoder(encoder) => {
+                    if encoder.hotkey_cw == c.to_string() {
+                        encoder.turn(true);
+                    } else if encoder.hotkey_ccw == c.to_string() {
+                        encoder.turn(false);
+                    } else if encoder.hotkey_sw == c.to_string() {
+                        encoder.press();
                     }
                 },
                 _ => {},
@@ -179,11 +301,97 @@ impl Board {
         }
         self
     }
+    // Presses the button, key or encoder under the mouse or sets the
+    // potentiometer, area is the area the board has been rendered to. Returns
+    // true if the event has been handled.
+    pub fn event_mouse(&mut self, event: MouseEvent, area: Rect) -> bool {
+        match event {
+            MouseEvent::Down(MouseButton::Left, col, row, _)
+            | MouseEvent::Drag(MouseButton::Left, col, row, _) => {
+                if col <= area.x || row <= area.y
+                    || col >= area.x + area.width.min(self.width)
+                    || row >= area.y + area.height.min(self.height) {
+                    return false;
+                }
+                let (x, y) = (col - area.x - 1, row - area.y - 1);
+                for part in self.hardware.iter_mut() {
+                    let (px, py) = part.pos();
+                    let (w, h) = part.size();
+                    if x >= px && x < px + w && y >= py && y < py + h {
+                        match part {
+                            Part::Button(button) => {
+                                if let MouseEvent::Down(..) = event {
+                                    button.mouse_down();
+                                }
+                                return true;
+                            },
+                            Part::Potentiometer(pot) => {
+                                pot.click(x - px, y - py);
+                                return true;
+                            },
+                            Part::Keypad(keypad) => {
+                                if let MouseEvent::Down(..) = event {
+                                    keypad.mouse_down(x - px, y - py);
+                                }
+                                return true;
+                            },
+                            Part::Encoder(encoder) => {
+                                if let MouseEvent::Down(..) = event {
+                                    encoder.mouse_down(x - px, y - py);
+                                }
+                                return true;
+                            },
+                            Part::Dht(dht) => {
+                                if let MouseEvent::Down(..) = event {
+                                    dht.mouse_down(x - px, y - py);
+                                }
+                                return true;
+                            },
+                            _ => {},
+                        }
+                    }
+                }
+                return false;
+            },
+            MouseEvent::Up(MouseButton::Left, _, _, _) => {
+                for part in self.hardware.iter_mut() {
+                    match part {
+                        Part::Button(button) => button.mouse_up(),
+                        Part::Keypad(keypad) => keypad.mouse_up(),
+                        Part::Encoder(encoder) => encoder.mouse_up(),
+                        _ => {},
+                    }
+                }
+                return true;
+            },
+            _ => {
+                return false;
+            }
+        }
+    }
+    // Pins of the parts which answer the program with a responder slot
+    pub fn responder_pins(&self) -> u32 {
+        self.hardware.iter().fold(0, |mask, part| match part {
+            Part::Dht(dht) => mask | (0x1 << dht.pin),
+            _ => mask,
+        })
+    }
     pub fn sync(&mut self, reg_memory: &mut RegMemory) -> &mut Self {
         for part in self.hardware.iter_mut() {
             match part {
                 Part::Led(led) => { led.sync(reg_memory); },
                 Part::Button(button) => { button.sync(reg_memory); },
+                Part::SevenSeg(sevenseg) => { sevenseg.sync(reg_memory); },
+                Part::Lcd(lcd) => { lcd.sync(reg_memory); },
+                Part::RgbLed(rgbled) => { rgbled.sync(reg_memory); },
+                Part::Potentiometer(pot) => { pot.sync(reg_memory); },
+                Part::Keypad(keypad) => { keypad.sync(reg_memory); },
+                Part::Encoder(encoder) => { encoder.sync(reg_memory); },
+                Part::Servo(servo) => { servo.sync(reg_memory); },
+                Part::Stepper(stepper) => { stepper.sync(reg_memory); },
+                Part::Dht(dht) => { dht.sync(reg_memory); },
+                Part::LedBar(ledbar) => { ledbar.sync(reg_memory); },
+                Part::LedMatrix(ledmatrix) => { ledmatrix.sync(reg_memory); },
             }
         }
         self
@@ -194,13 +402,64 @@ impl Board {
         }
         let mut led_count = 0;
         let mut btn_count = 0;
+        let mut sevenseg_count = 0;
+        let mut lcd_count = 0;
+        let mut rgbled_count = 0;
+        let mut pot_count = 0;
+        let mut keypad_count = 0;
+        let mut encoder_count = 0;
+        let mut motor_count = 0;
+        let mut sensor_count = 0;
+        let mut ledbar_count = 0;
+        let mut ledmatrix_count = 0;
         for part in self.hardware.iter() {
             match part {
                 Part::Led(_) => { led_count += 1; } ,
                 Part::Button(_) => { btn_count += 1; },
+                Part::SevenSeg(_) => { sevenseg_count += 1; },
+                Part::Lcd(_) => { lcd_count += 1; },
+                Part::RgbLed(_) => { rgbled_count += 1; },
+                Part::Potentiometer(_) => { pot_count += 1; },
+                Part::Keypad(_) => { keypad_count += 1; },
+                Part::Encoder(_) => { encoder_count += 1; },
+                Part::Servo(_) | Part::Stepper(_) => { motor_count += 1; },
+                Part::Dht(_) => { sensor_count += 1; },
+                Part::LedBar(_) => { ledbar_count += 1; },
+                Part::LedMatrix(_) => { ledmatrix_count += 1; },
             }
         }
-        format!("{}x Leds | {}x Buttons", led_count, btn_count)
+        let mut summary = format!("{}x Leds | {}x Buttons", led_count, btn_count);
+        if ledbar_count > 0 {
+            summary += &format!(" | {}x Led Bars", ledbar_count);
+        }
+        if ledmatrix_count > 0 {
+            summary += &format!(" | {}x Led Matrices", ledmatrix_count);
+        }
+        if sevenseg_count > 0 {
+            summary += &format!(" | {}x 7-Segs", sevenseg_count);
+        }
+        if lcd_count > 0 {
+            summary += &format!(" | {}x LCDs", lcd_count);
+        }
+        if rgbled_count > 0 {
+            summary += &format!(" | {}x RGB Leds", rgbled_count);
+        }
+        if pot_count > 0 {
+            summary += &format!(" | {}x Pots", pot_count);
+        }
+        if keypad_count > 0 {
+            summary += &format!(" | {}x Keypads", keypad_count);
+        }
+        if encoder_count > 0 {
+            summary += &format!(" | {}x Encoders", encoder_count);
+        }
+        if motor_count > 0 {
+            summary += &format!(" | {}x Motors", motor_count);
+        }
+        if sensor_count > 0 {
+            summary += &format!(" | {}x Sensors", sensor_count);
+        }
+        summary
     }
     pub fn render(
         &self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>, area: Rect
@@ -221,7 +480,166 @@ impl Board {
             match part {
                 Part::Led(led) => { led.render(f, board_area, &self); },
                 Part::Button(button) => { button.render(f, board_area, &self); },
+                Part::SevenSeg(sevenseg) => { sevenseg.render(f, board_area, &self); },
+                Part::Lcd(lcd) => { lcd.render(f, board_area, &self); },
+                Part::RgbLed(rgbled) => { rgbled.render(f, board_area, &self); },
+                Part::Potentiometer(pot) => { pot.render(f, board_area, &self); },
+                Part::Keypad(keypad) => { keypad.render(f, board_area, &self); },
+                Part::Encoder(encoder) => { encoder.render(f, board_area, &self); },
+                Part::Servo(servo) => { servo.render(f, board_area, &self); },
+                Part::Stepper(stepper) => { stepper.render(f, board_area, &self); },
+                Part::Dht(dht) => { dht.render(f, board_area, &self); },
+                Part::LedBar(ledbar) => { ledbar.render(f, board_area, &self); },
+                Part::LedMatrix(ledmatrix) => { ledmatrix.render(f, board_area, &self); },
             }
         }
     }
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn Board__to_json() {
+        let json: SerdeValue = serde_json::from_str(
+            include_str!("../../config/gpiotest-board.json")
+        ).unwrap();
+        let board = Board::from_json(json.clone()).unwrap();
+        assert_eq!(board.to_json(), json);
+    }
+
+    #[test]
+    fn Board__layout() {
+        let mut boards = vec![Board::default(); 3];
+        boards[0].width = 30;
+        boards[1].width = 30;
+        boards[2].position = Some((40, 0));
+        let area = Rect { x: 2, y: 1, width: 120, height: 60 };
+        let rects = Board::layout(&boards, area);
+        assert_eq!((rects[0].x, rects[0].y), (2, 1));
+        assert_eq!((rects[2].x, rects[2].y), (42, 1));
+        // Does not fit next to the positioned board, so wraps into next row
+        assert_eq!((rects[1].x, rects[1].y), (2, 1 + 24 + 1));
+        boards[1].width = 10;
+        let rects = Board::layout(&boards, area);
+        assert_eq!((rects[1].x, rects[1].y), (2 + 40 + 64 + 1, 1));
+        assert_eq!(rects[1].width, 10);
+    }
+
+    #[test]
+    fn Board__to_json__unknown_fields() {
+        let json = json!({
+            "type": "simpi/board",
+            "name": "Test",
+            "author": "someone",
+            "hardware": [
+                { "type": "simpi/led", "pin": 4, "note": "status" },
+                { "type": "simpi/buzzer", "pin": 5 },
+            ],
+        });
+        let out = Board::from_json(json).unwrap().to_json();
+        assert_eq!(out["author"], "someone");
+        assert_eq!(out["hardware"][0]["note"], "status");
+        assert_eq!(out["hardware"][1]["type"], "simpi/buzzer");
+    }
+
+    #[test]
+    fn Board__from_file__include() {
+        let dir = std::env::temp_dir().join(format!("simpi-board-include-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("leds.json"), json!({
+            "type": "simpi/board",
+            "hardware": [ { "type": "simpi/led", "pin": 5 }, { "type": "simpi/buzzer", "pin": 6 } ],
+        }).to_string()).unwrap();
+        let file_name = dir.join("board.json").to_str().unwrap().to_owned();
+        fs::write(&file_name, json!({
+            "type": "simpi/board",
+            "include": [ { "file": "leds.json" } ],
+        }).to_string()).unwrap();
+        assert_eq!(Board::from_file(&file_name).unwrap().hardware.len(), 1);
+        fs::write(dir.join("leds.json"), json!({
+            "type": "simpi/board",
+            "hardware": [ { "type": "simpi/led", "pin": "5" } ],
+        }).to_string()).unwrap();
+        let err = Board::from_file(&file_name).err().unwrap().to_string();
+        assert!(err.starts_with("Cannot include '"));
+        assert!(err.contains("invalid simpi/led part"));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn Board__reload_if_changed() {
+        let dir = std::env::temp_dir().join(format!("simpi-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file_name = dir.join("board.json").to_str().unwrap().to_owned();
+        let write = |pin: u8| fs::write(&file_name, json!({
+            "type": "simpi/board",
+            "hardware": [ { "type": "simpi/button", "name": "A", "pin": pin, "hotkey": "a" } ],
+        }).to_string()).unwrap();
+        let pin = |board: &Board| match &board.hardware[0] {
+            Part::Button(button) => (button.pin, button.get()),
+            _ => unreachable!(),
+        };
+        write(4);
+        let mut board = Board::from_file(&file_name).unwrap();
+        assert!(board.reload_if_changed().is_none());
+        if let Part::Button(button) = &mut board.hardware[0] {
+            button.set(true);
+        }
+        // Modification times may be too coarse to see the change
+        write(5);
+        board.source_mtime = None;
+        assert!(board.reload_if_changed().unwrap().is_ok());
+        assert_eq!(pin(&board), (5, true));
+        // Unsaved changes of the editor are kept
+        board.is_modified = true;
+        write(6);
+        board.source_mtime = None;
+        assert!(board.reload_if_changed().unwrap().is_err());
+        assert_eq!(pin(&board), (5, true));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn Board__to_file__defaults() {
+        let dir = std::env::temp_dir().join(format!("simpi-defaults-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let file_name = dir.join("board.json").to_str().unwrap().to_owned();
+        fs::write(&file_name, json!({
+            "type": "simpi/board",
+            "defaults": { "simpi/led": { "colorOn": "green" } },
+            "hardware": [
+                { "type": "simpi/buzzer", "pin": 3 },
+                { "type": "simpi/led", "pin": 4 },
+                { "type": "simpi/led", "pin": 5, "colorOn": "red" },
+                { "type": "simpi/led", "pin": 6, "colorOn": "green" },
+                { "type": "simpi/led", "pin": 7 },
+            ],
+        }).to_string()).unwrap();
+        let mut board = Board::from_file(&file_name).unwrap();
+        if let Part::Led(led) = &mut board.hardware[3] {
+            led.color_on = Color::Yellow;
+        }
+        board.to_file(&file_name).unwrap();
+        // Fields set in the file itself are kept, even if they equal the defaults
+        let mut json: SerdeValue = serde_json::from_str(&fs::read_to_string(&file_name).unwrap()).unwrap();
+        assert_eq!(json["hardware"][0]["colorOn"], SerdeValue::Null);
+        assert_eq!(json["hardware"][2]["colorOn"], "green");
+        // Changing the defaults in the saved file changes the parts using them
+        json["defaults"]["simpi/led"]["colorOn"] = json!("blue");
+        fs::write(&file_name, json.to_string()).unwrap();
+        let board = Board::from_file(&file_name).unwrap();
+        let color_on = |part: &Part| match part {
+            Part::Led(led) => led.color_on,
+            _ => unreachable!(),
+        };
+        assert_eq!(color_on(&board.hardware[0]), Color::Blue);
+        assert_eq!(color_on(&board.hardware[1]), Color::Red);
+        assert_eq!(color_on(&board.hardware[2]), Color::Green);
+        assert_eq!(color_on(&board.hardware[3]), Color::Yellow);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,30 +1,52 @@
 /*!button.rs
- * Hardware | Button definition.
+ * Hardware | Button definition. Contact bounce is simulated by scheduling
+ * the changes of a bounce in the INPUT log.
  * 
  * Author: Patrick Goldinger
  * License: GPL 3.0 (see LICENSE file for details)
  */
 
 use super::board::Board;
-use serde_json::{Value as SerdeValue};
-use std::io::{Error, ErrorKind};
+use super::model::{ButtonModel, PositionModel};
+use serde_json::{Map, Value as SerdeValue};
 use tui::backend::CrosstermBackend;
 use tui::layout::{Rect};
 use tui::style::{Color, Style};
 use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
 use tui::Frame;
 use utils::gpioregs::RegMemory;
+use utils::inlog::INPUT_EVENT_LEAD_US;
+use utils::outlog::unix_time_us;
 
 #[derive(Clone)]
 pub struct Button {
     pub pin: u8,
     pub name: String,
     pub hotkey: String,
+    // Momentary buttons are released after the hold time (or when the mouse
+    // button is released), others toggle on each press.
+    pub momentary: bool,
+    pub hold_time_ms: u32,
+    // Duration of the contact bounce after each change, 0 for none
+    pub bounce_ms: u32,
     pub color_off: Color,
     pub color_on: Color,
     pub pos_x: u16,
     pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
     state: bool,
+    // Time a momentary press by hotkey ends at
+    release_at_us: Option<u64>,
+    held_by_mouse: bool,
+    // State last written to the INPUT register, and the time the bounce of
+    // the last change ends at
+    synced_state: Option<bool>,
+    bounce_until_us: u64,
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
 }
 
 impl Default for Button {
@@ -33,91 +55,77 @@ impl Default for Button {
             pin: 0,
             name: String::from("BTN $n"),
             hotkey: String::from(""),
+            momentary: false,
+            hold_time_ms: 200,
+            bounce_ms: 0,
             color_off: Color::Black,
             color_on: Color::LightYellow,
             pos_x: 0,
             pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
             state: false,
+            release_at_us: None,
+            held_by_mouse: false,
+            synced_state: None,
+            bounce_until_us: 0,
+            extra: Map::new(),
         }
     }
 }
 
 impl Button {
-    pub fn from_json(json: SerdeValue) -> Result<Self, Error> {
-        match json {
-            SerdeValue::Object(map) => {
-                let mut button = Self::default();
-                let mut is_valid = false;
-                for (k, v) in map.iter() {
-                    match k.as_ref() {
-                        "type" => {
-                            if v.is_string() {
-                                if v.as_str().unwrap() == "simpi/button" {
-                                    is_valid = true;
-                                }
-                            }
-                        },
-                        "name" => {
-                            if v.is_string() {
-                                button.name = v.as_str().unwrap().to_owned();
-                            }
-                        },
-                        "pin" => {
-                            if v.is_u64() {
-                                button.pin = v.as_u64().unwrap() as u8;
-                            }
-                        },
-                        "hotkey" => {
-                            if v.is_string() {
-                                button.hotkey = v.as_str().unwrap().to_owned();
-                            }
-                        },
-                        "colorOff" => {
-                            if v.is_string() {
-                                let c = super::helper_str_to_color(v.as_str().unwrap().to_owned());
-                                if c.is_ok() {
-                                    button.color_off = c.unwrap();
-                                }
-                            }
-                        },
-                        "colorOn" => {
-                            if v.is_string() {
-                                let c = super::helper_str_to_color(v.as_str().unwrap().to_owned());
-                                if c.is_ok() {
-                                    button.color_on = c.unwrap();
-                                }
-                            }
-                        },
-                        "position" => {
-                            if v.is_object() {
-                                let s = v.as_object().unwrap();
-                                for (k, v) in s.iter() {
-                                    match k.as_ref() {
-                                        "x" => {
-                                            if v.is_u64() {
-                                                button.pos_x = v.as_u64().unwrap() as u16;
-                                            }
-                                        },
-                                        "y" => {
-                                            if v.is_u64() {
-                                                button.pos_y = v.as_u64().unwrap() as u16;
-                                            }
-                                        },
-                                        _ => {}
-                                    }
-                                }
-                            }
-                        },
-                        _ => {}
-                    }
-                }
-                if is_valid {
-                    Ok(button)
-                } else {
-                    Err(Error::new(ErrorKind::InvalidData, "Input data is invalid!"))
-                }
-            },
-            _ => Err(Error::new(ErrorKind::InvalidInput, "Input must be map!"))
+    pub const WIDTH: u16 = 13;
+    pub const HEIGHT: u16 = 2;
+    pub const MIN_HOLD_TIME_MS: u32 = 10;
+    pub const MAX_HOLD_TIME_MS: u32 = 10000;
+    pub const MAX_BOUNCE_MS: u32 = 100;
+
+    pub fn from_model(model: ButtonModel) -> Self {
+        let mut button = Self::default();
+        // Pins out of range are ignored
+        if model.pin < 32 {
+            button.pin = model.pin;
+        }
+        if let Some(name) = model.name {
+            button.name = name;
+        }
+        if let Some(hotkey) = model.hotkey {
+            button.hotkey = hotkey;
+        }
+        button.momentary = model.mode.as_deref() == Some("momentary");
+        if let Some(hold_time) = model.hold_time {
+            button.hold_time_ms = hold_time.clamp(Self::MIN_HOLD_TIME_MS, Self::MAX_HOLD_TIME_MS);
+        }
+        if let Some(bounce) = model.bounce {
+            button.bounce_ms = bounce.min(Self::MAX_BOUNCE_MS);
+        }
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            button.color_off = c;
+        }
+        if let Some(c) = model.color_on.and_then(|c| super::helper_str_to_color(c).ok()) {
+            button.color_on = c;
+        }
+        if let Some(position) = model.position {
+            button.pos_x = position.x;
+            button.pos_y = position.y;
+        }
+        button.extra = model.extra;
+        button
+    }
+    pub fn to_model(&self) -> ButtonModel {
+        ButtonModel {
+            pin: self.pin,
+            name: Some(self.name.clone()),
+            hotkey: Some(self.hotkey.clone()),
+            // Written only if set, so boards stay unchanged
+            mode: if self.momentary { Some(String::from("momentary")) } else { None },
+            hold_time: if self.hold_time_ms != Self::default().hold_time_ms { Some(self.hold_time_ms) } else { None },
+            bounce: if self.bounce_ms > 0 { Some(self.bounce_ms) } else { None },
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            color_on: Some(super::helper_color_to_str(self.color_on)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
         }
     }
     pub fn get(&self) -> bool {
@@ -126,8 +134,54 @@ impl Button {
     pub fn set(&mut self, state: bool) {
         self.state = state
     }
+    // Press by hotkey. Terminals do not report key releases, so momentary
+    // buttons are released after the hold time. Key repeats extend it.
+    pub fn press(&mut self) {
+        if self.momentary {
+            self.state = true;
+            self.release_at_us = Some(unix_time_us() + self.hold_time_ms as u64 * 1000);
+        } else {
+            self.state = !self.state;
+        }
+    }
+    pub fn mouse_down(&mut self) {
+        if self.momentary {
+            self.state = true;
+            self.release_at_us = None;
+            self.held_by_mouse = true;
+        } else {
+            self.state = !self.state;
+        }
+    }
+    pub fn mouse_up(&mut self) {
+        if self.held_by_mouse {
+            self.state = false;
+            self.held_by_mouse = false;
+        }
+    }
     pub fn sync(&mut self, reg_memory: &mut RegMemory) -> &mut Self {
-        reg_memory.input.write_pin(self.pin, self.state as u8);
+        let now_us = unix_time_us();
+        if self.release_at_us.map_or(false, |t| now_us >= t) {
+            self.state = false;
+            self.release_at_us = None;
+        }
+        if self.synced_state != Some(self.state) {
+            if self.bounce_ms > 0 && self.synced_state.is_some() {
+                // Scheduled ahead so the client does not miss the timing
+                let start_us = now_us + INPUT_EVENT_LEAD_US;
+                let duration_us = self.bounce_ms as u64 * 1000;
+                for (time_us, value) in bounce_events(self.state, start_us, duration_us, now_us) {
+                    reg_memory.input_log.push(time_us, self.pin, value as u8);
+                }
+                self.bounce_until_us = start_us + duration_us;
+            }
+            self.synced_state = Some(self.state);
+        }
+        // The client applies the changes of the bounce, the final state is
+        // written afterwards in case no client is running
+        if now_us >= self.bounce_until_us {
+            reg_memory.input.write_pin(self.pin, self.state as u8);
+        }
         self
     }
     pub fn render(
@@ -137,8 +191,8 @@ impl Button {
         let button_area = Rect {
             x: area.x + self.pos_x + 1,
             y: area.y + self.pos_y + 1,
-            width: 13,
-            height: 2,
+            width: Self::WIDTH,
+            height: Self::HEIGHT,
         };
         if !super::helper_is_rect_in_range(area, button_area) {
             return;
@@ -164,3 +218,46 @@ impl Button {
             .render(f, button_area);
     }
 }
+
+// Changes of a contact bounce to the given value, starting at the given time.
+// The level flips back and forth a few times at random intervals before it
+// settles at the given value at the end of the bounce.
+fn bounce_events(value: bool, start_us: u64, duration_us: u64, seed: u64) -> Vec<(u64, bool)> {
+    let mut rng = seed | 0x1;
+    let mut next = || {
+        // xorshift64
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        rng
+    };
+    let flips = 1 + 2 * (next() % 3);
+    let step = duration_us / (flips + 1);
+    let mut events = vec![(start_us, value)];
+    for i in 1..=flips {
+        let jitter = if step > 1 { next() % (step / 2) } else { 0 };
+        events.push((start_us + i * step - jitter, (i % 2 == 0) == value));
+    }
+    events.push((start_us + duration_us, value));
+    events
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn Button__bounce_events() {
+        for seed in 0..20 {
+            let events = bounce_events(true, 1000, 5000, seed);
+            assert_eq!(events.first(), Some(&(1000, true)));
+            assert_eq!(events.last(), Some(&(6000, true)));
+            assert!(events.len() % 2 == 1);
+            for pair in events.windows(2) {
+                assert!(pair[0].0 < pair[1].0);
+                assert!(pair[0].1 != pair[1].1);
+            }
+        }
+    }
+}
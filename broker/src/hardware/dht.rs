@@ -0,0 +1,284 @@
+/*!dht.rs
+ * Hardware | DHT11/DHT22 temperature and humidity sensor definition. The
+ * response to the start signal of the program is timed by the client, the
+ * sensor only hands the measured values to the responder slot of its pin.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::board::Board;
+use super::model::{DhtModel, PositionModel};
+use serde_json::{Map, Value as SerdeValue};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Rect};
+use tui::style::{Color, Style};
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
+use tui::Frame;
+use utils::gpioregs::RegMemory;
+use utils::outlog::unix_time_us;
+use utils::responder::{DHT_DATA_LEN, RESPONDER_DHT11, RESPONDER_DHT22};
+
+// Time (in us) the activity mark is shown after a response
+const ACTIVITY_US: u64 = 500_000;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum DhtSensor {
+    Dht11,
+    Dht22,
+}
+
+impl DhtSensor {
+    pub fn name(&self) -> &str {
+        match self {
+            DhtSensor::Dht11 => "dht11",
+            DhtSensor::Dht22 => "dht22",
+        }
+    }
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dht11" => Some(DhtSensor::Dht11),
+            "dht22" => Some(DhtSensor::Dht22),
+            _ => None,
+        }
+    }
+    // Measuring range of the temperature and the humidity (in tenths)
+    pub fn temperature_range(&self) -> (i32, i32) {
+        match self {
+            DhtSensor::Dht11 => (0, 500),
+            DhtSensor::Dht22 => (-400, 800),
+        }
+    }
+    pub fn humidity_range(&self) -> (i32, i32) {
+        match self {
+            DhtSensor::Dht11 => (200, 900),
+            DhtSensor::Dht22 => (0, 1000),
+        }
+    }
+    // Resolution of both values (in tenths)
+    pub fn step(&self) -> i32 {
+        match self {
+            DhtSensor::Dht11 => 10,
+            DhtSensor::Dht22 => 1,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Dht {
+    pub pin: u8,
+    pub name: String,
+    pub sensor: DhtSensor,
+    // Values sent to the program, in tenths of °C and %
+    pub temperature: i32,
+    pub humidity: i32,
+    pub color_off: Color,
+    pub color_on: Color,
+    pub pos_x: u16,
+    pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
+    // Number of responses the client has sent and the time the last new one
+    // has been seen until
+    responses: u32,
+    active_until_us: u64,
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
+}
+
+impl Default for Dht {
+    fn default() -> Self {
+        Self {
+            pin: 4,
+            name: String::from("DHT $n"),
+            sensor: DhtSensor::Dht22,
+            temperature: 215,
+            humidity: 450,
+            color_off: Color::DarkGray,
+            color_on: Color::LightGreen,
+            pos_x: 0,
+            pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
+            responses: 0,
+            active_until_us: 0,
+            extra: Map::new(),
+        }
+    }
+}
+
+impl Dht {
+    pub const WIDTH: u16 = 16;
+    pub const HEIGHT: u16 = 3;
+    // Step (in tenths) of the arrows
+    pub const CHANGE_STEP: i32 = 5;
+
+    pub fn from_model(model: DhtModel) -> Self {
+        let mut dht = Self::default();
+        // Pins out of range are ignored
+        if model.pin < 32 {
+            dht.pin = model.pin;
+        }
+        if let Some(name) = model.name {
+            dht.name = name;
+        }
+        if let Some(sensor) = model.sensor.as_deref().and_then(DhtSensor::from_name) {
+            dht.sensor = sensor;
+        }
+        if let Some(temperature) = model.temperature {
+            dht.temperature = (temperature * 10.0).round() as i32;
+        }
+        if let Some(humidity) = model.humidity {
+            dht.humidity = (humidity * 10.0).round() as i32;
+        }
+        dht.fit_values();
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            dht.color_off = c;
+        }
+        if let Some(c) = model.color_on.and_then(|c| super::helper_str_to_color(c).ok()) {
+            dht.color_on = c;
+        }
+        if let Some(position) = model.position {
+            dht.pos_x = position.x;
+            dht.pos_y = position.y;
+        }
+        dht.extra = model.extra;
+        dht
+    }
+    pub fn to_model(&self) -> DhtModel {
+        DhtModel {
+            pin: self.pin,
+            name: Some(self.name.clone()),
+            sensor: Some(self.sensor.name().to_owned()),
+            temperature: Some(self.temperature as f64 / 10.0),
+            humidity: Some(self.humidity as f64 / 10.0),
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            color_on: Some(super::helper_color_to_str(self.color_on)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
+        }
+    }
+    // Rounds the values to the resolution of the sensor and keeps them in
+    // its measuring range.
+    pub fn fit_values(&mut self) {
+        let fit = |value: i32, (min, max): (i32, i32), step: i32| {
+            ((value as f64 / step as f64).round() as i32 * step).max(min).min(max)
+        };
+        self.temperature = fit(self.temperature, self.sensor.temperature_range(), self.sensor.step());
+        self.humidity = fit(self.humidity, self.sensor.humidity_range(), self.sensor.step());
+    }
+    pub fn change_temperature(&mut self, up: bool) {
+        let step = Self::CHANGE_STEP.max(self.sensor.step());
+        self.temperature += if up { step } else { -step };
+        self.fit_values();
+    }
+    pub fn change_humidity(&mut self, up: bool) {
+        let step = Self::CHANGE_STEP.max(self.sensor.step());
+        self.humidity += if up { step } else { -step };
+        self.fit_values();
+    }
+    // Changes the values with the arrows, x and y are relative to the part.
+    pub fn mouse_down(&mut self, x: u16, y: u16) {
+        let up = match x {
+            0..=2 => false,
+            10..=12 => true,
+            _ => return,
+        };
+        match y {
+            1 => self.change_temperature(up),
+            2 => self.change_humidity(up),
+            _ => {},
+        }
+    }
+    // Bytes of the response: humidity, temperature and checksum. The DHT11
+    // sends integral and decimal part of each value, the DHT22 tenths with
+    // the sign of the temperature in the highest bit.
+    pub fn data(&self) -> [u8; DHT_DATA_LEN] {
+        let (humidity, temperature) = match self.sensor {
+            DhtSensor::Dht11 => (
+                [(self.humidity / 10) as u8, (self.humidity % 10) as u8],
+                [(self.temperature / 10) as u8, (self.temperature % 10) as u8],
+            ),
+            DhtSensor::Dht22 => {
+                let magnitude = self.temperature.unsigned_abs() as u16 | if self.temperature < 0 { 0x8000 } else { 0 };
+                ((self.humidity as u16).to_be_bytes(), magnitude.to_be_bytes())
+            },
+        };
+        let mut data = [humidity[0], humidity[1], temperature[0], temperature[1], 0];
+        data[4] = data[0..4].iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        data
+    }
+    pub fn sync(&mut self, reg_memory: &mut RegMemory) -> &mut Self {
+        let kind = match self.sensor {
+            DhtSensor::Dht11 => RESPONDER_DHT11,
+            DhtSensor::Dht22 => RESPONDER_DHT22,
+        };
+        reg_memory.responders.configure(self.pin, kind, self.data());
+        // The pull-up resistor of the module keeps the released line high
+        reg_memory.input.write_pin(self.pin, 1);
+        let responses = reg_memory.responders.responses(self.pin);
+        if responses != self.responses {
+            self.responses = responses;
+            self.active_until_us = unix_time_us() + ACTIVITY_US;
+        }
+        self
+    }
+    pub fn render(
+        &self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>,
+        area: Rect, board: &Board
+    ) {
+        let dht_area = Rect {
+            x: area.x + self.pos_x + 1,
+            y: area.y + self.pos_y + 1,
+            width: Self::WIDTH,
+            height: Self::HEIGHT,
+        };
+        if !super::helper_is_rect_in_range(area, dht_area) {
+            return;
+        }
+        let style = Style::default().fg(board.foreground_color).bg(board.background_color);
+        let arrow_style = Style::default().fg(Color::Black).bg(self.color_off);
+        let is_active = unix_time_us() < self.active_until_us;
+        let dht_content = vec![
+            Text::styled(format!("{}\n", self.name), style),
+            Text::styled(" < ", arrow_style),
+            Text::styled(format!("{:^7}", format!("{:.1}°C", self.temperature as f64 / 10.0)), style),
+            Text::styled(" > ", arrow_style),
+            Text::raw(" "),
+            // Lights up after the program has read the sensor
+            Text::styled("  ", Style::default().bg(if is_active { self.color_on } else { self.color_off })),
+            Text::raw("\n"),
+            Text::styled(" < ", arrow_style),
+            Text::styled(format!("{:^7}", format!("{:.1}%", self.humidity as f64 / 10.0)), style),
+            Text::styled(" > ", arrow_style),
+        ];
+        Paragraph::new(dht_content.iter())
+            .block(Block::default()
+                .borders(Borders::NONE)
+            )
+            .style(Style::default().bg(board.background_color))
+            .render(f, dht_area);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn Dht__data() {
+        let mut dht = Dht::default();
+        dht.temperature = -101;
+        dht.humidity = 652;
+        assert_eq!(dht.data(), [0x02, 0x8C, 0x80, 0x65, 0x73]);
+        dht.sensor = DhtSensor::Dht11;
+        dht.fit_values();
+        assert_eq!((dht.temperature, dht.humidity), (0, 650));
+        assert_eq!(dht.data(), [65, 0, 0, 0, 65]);
+        dht.change_temperature(true);
+        assert_eq!(dht.temperature, 10);
+    }
+}
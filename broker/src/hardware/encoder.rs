@@ -0,0 +1,293 @@
+/*!encoder.rs
+ * Hardware | Rotary encoder definition. Each step is scheduled as a full
+ * quadrature cycle of the A and B pins in the INPUT log, so the client
+ * applies the edges with accurate timing (including interrupts).
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::board::Board;
+use super::model::{EncoderModel, PositionModel};
+use serde_json::{Map, Value as SerdeValue};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Rect};
+use tui::style::{Color, Style};
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
+use tui::Frame;
+use utils::gpioregs::RegMemory;
+use utils::inlog::INPUT_EVENT_LEAD_US;
+use utils::outlog::unix_time_us;
+
+#[derive(Clone)]
+pub struct Encoder {
+    pub pin_a: u8,
+    pub pin_b: u8,
+    // Pin of the push switch, None for encoders without one
+    pub pin_sw: Option<u8>,
+    pub name: String,
+    pub hotkey_cw: String,
+    pub hotkey_ccw: String,
+    pub hotkey_sw: String,
+    // Duration of the quadrature cycle of one step
+    pub step_time_ms: u32,
+    pub hold_time_ms: u32,
+    pub color_off: Color,
+    pub color_on: Color,
+    pub pos_x: u16,
+    pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
+    // Steps since the start, clockwise steps count up
+    position: i32,
+    // Directions of the steps which have not been scheduled yet, true for
+    // clockwise
+    pending: Vec<bool>,
+    // Time the last scheduled step ends at
+    busy_until_us: u64,
+    pressed: bool,
+    // Time a press of the switch by hotkey ends at
+    release_at_us: Option<u64>,
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self {
+            pin_a: 5,
+            pin_b: 6,
+            pin_sw: None,
+            name: String::from("ENC $n"),
+            hotkey_cw: String::from(""),
+            hotkey_ccw: String::from(""),
+            hotkey_sw: String::from(""),
+            step_time_ms: 4,
+            hold_time_ms: 200,
+            color_off: Color::DarkGray,
+            color_on: Color::LightYellow,
+            pos_x: 0,
+            pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
+            position: 0,
+            pending: vec![],
+            busy_until_us: 0,
+            pressed: false,
+            release_at_us: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+impl Encoder {
+    pub const WIDTH: u16 = 16;
+    pub const HEIGHT: u16 = 2;
+    pub const MIN_STEP_TIME_MS: u32 = 1;
+    pub const MAX_STEP_TIME_MS: u32 = 100;
+    // Steps which may wait for being scheduled, further ones are dropped
+    pub const MAX_PENDING: usize = 16;
+
+    pub fn from_model(model: EncoderModel) -> Self {
+        let mut encoder = Self::default();
+        // Pins out of range are ignored
+        if model.pin_a < 32 {
+            encoder.pin_a = model.pin_a;
+        }
+        if model.pin_b < 32 {
+            encoder.pin_b = model.pin_b;
+        }
+        encoder.pin_sw = model.pin_sw.filter(|&p| p < 32);
+        if let Some(name) = model.name {
+            encoder.name = name;
+        }
+        if let Some(hotkey) = model.hotkey_cw {
+            encoder.hotkey_cw = hotkey;
+        }
+        if let Some(hotkey) = model.hotkey_ccw {
+            encoder.hotkey_ccw = hotkey;
+        }
+        if let Some(hotkey) = model.hotkey_sw {
+            encoder.hotkey_sw = hotkey;
+        }
+        if let Some(step_time) = model.step_time {
+            encoder.step_time_ms = step_time.clamp(Self::MIN_STEP_TIME_MS, Self::MAX_STEP_TIME_MS);
+        }
+        if let Some(hold_time) = model.hold_time {
+            encoder.hold_time_ms = hold_time.clamp(10, 10000);
+        }
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            encoder.color_off = c;
+        }
+        if let Some(c) = model.color_on.and_then(|c| super::helper_str_to_color(c).ok()) {
+            encoder.color_on = c;
+        }
+        if let Some(position) = model.position {
+            encoder.pos_x = position.x;
+            encoder.pos_y = position.y;
+        }
+        encoder.extra = model.extra;
+        encoder
+    }
+    pub fn to_model(&self) -> EncoderModel {
+        EncoderModel {
+            name: Some(self.name.clone()),
+            pin_a: self.pin_a,
+            pin_b: self.pin_b,
+            pin_sw: self.pin_sw,
+            hotkey_cw: Some(self.hotkey_cw.clone()),
+            hotkey_ccw: Some(self.hotkey_ccw.clone()),
+            hotkey_sw: if self.hotkey_sw.is_empty() { None } else { Some(self.hotkey_sw.clone()) },
+            step_time: Some(self.step_time_ms),
+            hold_time: if self.hold_time_ms != Self::default().hold_time_ms { Some(self.hold_time_ms) } else { None },
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            color_on: Some(super::helper_color_to_str(self.color_on)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
+        }
+    }
+    pub fn turn(&mut self, cw: bool) {
+        if self.pending.len() < Self::MAX_PENDING {
+            self.pending.push(cw);
+        }
+    }
+    // Presses the switch for the hold time, as terminals do not report key
+    // releases.
+    pub fn press(&mut self) {
+        self.pressed = true;
+        self.release_at_us = Some(unix_time_us() + self.hold_time_ms as u64 * 1000);
+    }
+    // Turns the encoder with the arrows or holds the switch until the mouse
+    // button is released, x and y are relative to the part.
+    pub fn mouse_down(&mut self, x: u16, y: u16) {
+        if y != 1 {
+            return;
+        }
+        match x {
+            0..=2 => self.turn(false),
+            10..=12 => self.turn(true),
+            14..=15 => {
+                self.pressed = true;
+                self.release_at_us = None;
+            },
+            _ => {},
+        }
+    }
+    pub fn mouse_up(&mut self) {
+        if self.pressed && self.release_at_us.is_none() {
+            self.pressed = false;
+        }
+    }
+    pub fn sync(&mut self, reg_memory: &mut RegMemory) -> &mut Self {
+        let now_us = unix_time_us();
+        if self.release_at_us.map_or(false, |t| now_us >= t) {
+            self.pressed = false;
+            self.release_at_us = None;
+        }
+        if !self.pending.is_empty() {
+            // Scheduled ahead so the client does not miss the timing
+            let mut start_us = std::cmp::max(self.busy_until_us, now_us + INPUT_EVENT_LEAD_US);
+            let step_us = self.step_time_ms as u64 * 1000;
+            for cw in self.pending.drain(..) {
+                for (time_us, pin, value) in quadrature_events(self.pin_a, self.pin_b, cw, start_us, step_us) {
+                    reg_memory.input_log.push(time_us, pin, value as u8);
+                }
+                start_us += step_us;
+                self.position += if cw { 1 } else { -1 };
+            }
+            self.busy_until_us = start_us;
+        }
+        // The client applies the edges of the steps, the idle level is
+        // written afterwards in case no client is running
+        if now_us >= self.busy_until_us {
+            reg_memory.input.write_pin(self.pin_a, 1);
+            reg_memory.input.write_pin(self.pin_b, 1);
+        }
+        if let Some(pin_sw) = self.pin_sw {
+            reg_memory.input.write_pin(pin_sw, !self.pressed as u8);
+        }
+        self
+    }
+    pub fn render(
+        &self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>,
+        area: Rect, board: &Board
+    ) {
+        let encoder_area = Rect {
+            x: area.x + self.pos_x + 1,
+            y: area.y + self.pos_y + 1,
+            width: Self::WIDTH,
+            height: Self::HEIGHT,
+        };
+        if !super::helper_is_rect_in_range(area, encoder_area) {
+            return;
+        }
+        let style = Style::default().fg(board.foreground_color).bg(board.background_color);
+        let arrow_style = Style::default().fg(Color::Black).bg(self.color_off);
+        let mut encoder_content = vec![
+            Text::styled(format!("{} [{}{}{}]\n", self.name, self.hotkey_ccw, self.hotkey_cw, self.hotkey_sw), style),
+            Text::styled(" < ", arrow_style),
+            Text::styled(format!("{:^7}", self.position), style),
+            Text::styled(" > ", arrow_style),
+        ];
+        if self.pin_sw.is_some() {
+            encoder_content.push(Text::raw(" "));
+            encoder_content.push(Text::styled("  ", Style::default().bg(
+                if self.pressed { self.color_on } else { self.color_off }
+            )));
+        }
+        Paragraph::new(encoder_content.iter())
+            .block(Block::default()
+                .borders(Borders::NONE)
+            )
+            .style(Style::default().bg(board.background_color))
+            .render(f, encoder_area);
+    }
+}
+
+// Changes of the A and B pins during one step, starting at the given time.
+// Both pins are high at rest; A falls first on a clockwise step, B on a
+// counter-clockwise one, and the step ends at rest again.
+fn quadrature_events(pin_a: u8, pin_b: u8, cw: bool, start_us: u64, step_us: u64) -> Vec<(u64, u8, bool)> {
+    let (first, second) = if cw { (pin_a, pin_b) } else { (pin_b, pin_a) };
+    let quarter_us = step_us / 4;
+    vec![
+        (start_us, first, false),
+        (start_us + quarter_us, second, false),
+        (start_us + 2 * quarter_us, first, true),
+        (start_us + 3 * quarter_us, second, true),
+    ]
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn Encoder__sync() {
+        let mut encoder = Encoder::default();
+        encoder.turn(true);
+        encoder.turn(true);
+        encoder.turn(false);
+        let mut reg_memory = RegMemory::new();
+        encoder.sync(&mut reg_memory);
+        assert_eq!(encoder.position, 1);
+        let events = reg_memory.input_log.pop_due(encoder.busy_until_us);
+        assert_eq!(events.len(), 12);
+        // The level of A and B (Gray code) after each edge
+        let mut a = 1;
+        let mut b = 1;
+        let mut states = vec![];
+        for event in events.iter() {
+            if event.pin == encoder.pin_a { a = event.value; } else { b = event.value; }
+            states.push((a, b));
+        }
+        let cw = [(0, 1), (0, 0), (1, 0), (1, 1)];
+        let ccw = [(1, 0), (0, 0), (0, 1), (1, 1)];
+        assert_eq!(states[0..4], cw);
+        assert_eq!(states[4..8], cw);
+        assert_eq!(states[8..12], ccw);
+    }
+}
@@ -0,0 +1,148 @@
+/*!erc.rs
+ * Hardware | Electrical rules check of the loaded boards against the
+ * current pin configuration of the program.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::board::Board;
+use std::fmt;
+use utils::gpioregs::RegMemory;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub pin: u8,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} GPIO{:02}: {}",
+            match self.severity {
+                Severity::Warning => "WARNING",
+                Severity::Error => "ERROR  ",
+            },
+            self.pin, self.message
+        )
+    }
+}
+
+fn describe_parts(parts: &Vec<(String, String)>) -> String {
+    parts.iter()
+        .map(|(board, part)| format!("'{}' on '{}'", part, board))
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+pub fn check(boards: &Vec<Board>, reg_memory: &RegMemory) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    for pin in 0..32u8 {
+        let mut drivers = vec![];
+        let mut is_pulled = false;
+        for board in boards.iter() {
+            for part in board.hardware.iter() {
+                if part.driven_pins().contains(&pin) {
+                    drivers.push((board.name.clone(), part.name().to_owned()));
+                }
+                is_pulled |= part.pulled_pins().contains(&pin);
+            }
+        }
+        let is_used = reg_memory.used.read_pin(pin) == 1;
+        let is_input = reg_memory.config.read_pin(pin) == 1;
+        let has_pull = reg_memory.pull.read_pin(pin) == 1 || is_pulled;
+        if drivers.len() > 1 {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                pin,
+                message: format!("Multiple parts drive the same net: {}", describe_parts(&drivers)),
+            });
+        }
+        if !is_input && !drivers.is_empty() {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                pin,
+                message: format!("Pin is an output, but is also driven by {}", describe_parts(&drivers)),
+            });
+        }
+        if is_used && is_input && !has_pull && drivers.is_empty() {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Warning,
+                pin,
+                message: String::from("Input is floating, as no part drives it and no pull-up/down is enabled"),
+            });
+        }
+    }
+    diagnostics
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hardware::{dht::Dht, Button, Part};
+
+    fn boards(parts: Vec<Part>) -> Vec<Board> {
+        let mut board = Board::default();
+        board.name = String::from("Test");
+        board.hardware = parts;
+        vec![board]
+    }
+
+    fn button(name: &str, pin: u8) -> Part {
+        let mut button = Button::default();
+        button.name = String::from(name);
+        button.pin = pin;
+        Part::Button(button)
+    }
+
+    #[test]
+    fn check__multiple_drivers() {
+        let reg_memory = RegMemory::new();
+        let diagnostics = check(&boards(vec![button("A", 4), button("B", 4)]), &reg_memory);
+        assert_eq!(diagnostics, vec![Diagnostic {
+            severity: Severity::Error,
+            pin: 4,
+            message: String::from("Multiple parts drive the same net: 'A' on 'Test', 'B' on 'Test'"),
+        }]);
+        assert!(check(&boards(vec![button("A", 4), button("B", 5)]), &reg_memory).is_empty());
+    }
+
+    #[test]
+    fn check__driven_output() {
+        let mut reg_memory = RegMemory::new();
+        reg_memory.used.write_pin(4, 1);
+        let parts = boards(vec![button("A", 4)]);
+        assert!(check(&parts, &reg_memory).is_empty());
+        reg_memory.config.write_pin(4, 0);
+        let diagnostics = check(&parts, &reg_memory);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((&diagnostics[0].severity, diagnostics[0].pin), (&Severity::Error, 4));
+        assert!(diagnostics[0].message.starts_with("Pin is an output"));
+    }
+
+    #[test]
+    fn check__floating_input() {
+        let mut reg_memory = RegMemory::new();
+        // Unused inputs are not reported
+        assert!(check(&vec![], &reg_memory).is_empty());
+        reg_memory.used.write_pin(6, 1);
+        let diagnostics = check(&vec![], &reg_memory);
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!((&diagnostics[0].severity, diagnostics[0].pin), (&Severity::Warning, 6));
+        // Pulled by the program or by a part
+        reg_memory.pull.write_pin(6, 1);
+        assert!(check(&vec![], &reg_memory).is_empty());
+        reg_memory.pull.write_pin(6, 0);
+        let mut dht = Dht::default();
+        dht.pin = 6;
+        assert!(check(&boards(vec![Part::Dht(dht)]), &reg_memory).is_empty());
+    }
+}
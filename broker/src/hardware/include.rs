@@ -0,0 +1,176 @@
+/*!include.rs
+ * Hardware | Resolves the includes and part defaults of board files.
+ *
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::model::{self, IncludeModel};
+use serde_json::{Map, Value as SerdeValue};
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::{Path, PathBuf};
+
+pub struct Resolved {
+    // The board with the defaults applied to its own parts
+    pub board: SerdeValue,
+    // Fields filled in from the defaults for each of its own parts
+    pub defaulted: Vec<Vec<String>>,
+    // Parts of all included files as (file name, part), moved by the offset
+    // of the include and with the defaults of the including files applied
+    pub included: Vec<(String, SerdeValue)>,
+    // All included files, including nested includes
+    pub files: Vec<String>,
+}
+
+// Fills in the defaults for the type of the given part, fields set in the
+// part itself take precedence. Returns the fields filled in.
+fn apply_defaults(part: &mut SerdeValue, defaults: &SerdeValue) -> Vec<String> {
+    let defaults = match part.get("type").and_then(|t| t.as_str()).and_then(|t| defaults.get(t)) {
+        Some(SerdeValue::Object(defaults)) => defaults.clone(),
+        _ => return vec![],
+    };
+    let mut keys = vec![];
+    if let Some(map) = part.as_object_mut() {
+        for (k, v) in defaults.into_iter() {
+            if !map.contains_key(&k) {
+                map.insert(k.clone(), v);
+                keys.push(k);
+            }
+        }
+    }
+    return keys;
+}
+
+// Removes the given fields filled in from the defaults if they still equal the
+// defaults for the type of the part, so the part keeps following the defaults
+// when the board is written back.
+pub fn strip_defaults(part: &mut SerdeValue, defaults: &Map<String, SerdeValue>, keys: &[String]) {
+    let defaults = match part.get("type").and_then(|t| t.as_str()).and_then(|t| defaults.get(t)) {
+        Some(SerdeValue::Object(defaults)) => defaults.clone(),
+        _ => return,
+    };
+    if let Some(map) = part.as_object_mut() {
+        for (k, v) in defaults.into_iter() {
+            if keys.contains(&k) && map.get(&k) == Some(&v) {
+                map.remove(&k);
+            }
+        }
+    }
+}
+
+fn apply_offset(part: &mut SerdeValue, offset: (u16, u16)) {
+    let map = match part.as_object_mut() {
+        Some(map) => map,
+        None => return,
+    };
+    let position = map.entry("position").or_insert_with(|| serde_json::json!({}));
+    if let Some(position) = position.as_object_mut() {
+        for (key, off) in [("x", offset.0), ("y", offset.1)].iter() {
+            let v = position.get(*key).and_then(|v| v.as_u64()).unwrap_or(0);
+            position.insert(key.to_string(), SerdeValue::from(v + *off as u64));
+        }
+    }
+}
+
+fn resolve(
+    file_name: &str, stack: &mut Vec<PathBuf>, included: &mut Vec<(String, SerdeValue)>,
+    files: &mut Vec<String>, defaulted: &mut Vec<Vec<String>>
+) -> Result<SerdeValue, Error> {
+    let canonical = fs::canonicalize(file_name)?;
+    if stack.contains(&canonical) {
+        return Err(Error::new(ErrorKind::InvalidData, format!("'{}' includes itself", file_name)));
+    }
+    let mut json: SerdeValue = model::read_file(file_name)?;
+    let defaults = json.get("defaults").cloned().unwrap_or(SerdeValue::Null);
+    if let Some(parts) = json.get_mut("hardware").and_then(|h| h.as_array_mut()) {
+        for part in parts.iter_mut() {
+            defaulted.push(apply_defaults(part, &defaults));
+        }
+    }
+    let includes: Vec<IncludeModel> = match json.get("include") {
+        Some(include) => serde_json::from_value(include.clone()).map_err(|err| Error::new(
+            ErrorKind::InvalidData, format!("Invalid include in '{}': {}", file_name, err)
+        ))?,
+        None => vec![],
+    };
+    stack.push(canonical);
+    for include in includes.iter() {
+        // Paths are relative to the including file
+        let path = Path::new(file_name).parent().unwrap_or(Path::new(""))
+            .join(&include.file)
+            .to_string_lossy()
+            .into_owned();
+        let mut nested = vec![];
+        let fragment = resolve(&path, stack, &mut nested, files, &mut vec![]).map_err(|err| Error::new(
+            err.kind(), format!("Cannot include '{}': {}", path, err)
+        ))?;
+        files.push(path.clone());
+        let parts = fragment.get("hardware").and_then(|h| h.as_array()).cloned().unwrap_or_default();
+        for (origin, mut part) in parts.into_iter().map(|p| (path.clone(), p)).chain(nested) {
+            apply_defaults(&mut part, &defaults);
+            apply_offset(&mut part, (include.offset.x, include.offset.y));
+            included.push((origin, part));
+        }
+    }
+    stack.pop();
+    return Ok(json);
+}
+
+// Reads a board file and resolves its includes recursively. Included files
+// are board files themselves, only their parts are used.
+pub fn resolve_file(file_name: &str) -> Result<Resolved, Error> {
+    let mut included = vec![];
+    let mut files = vec![];
+    let mut defaulted = vec![];
+    let board = resolve(file_name, &mut vec![], &mut included, &mut files, &mut defaulted)?;
+    return Ok(Resolved { board, defaulted, included, files });
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn resolve_file__include() {
+        let dir = std::env::temp_dir().join(format!("simpi-include-{}", std::process::id()));
+        fs::create_dir_all(dir.join("modules")).unwrap();
+        fs::write(dir.join("modules/leds.toml"), r#"
+            type = "simpi/board"
+            [defaults."simpi/led"]
+            colorOn = "lightblue"
+            [[hardware]]
+            type = "simpi/led"
+            pin = 5
+            position = { x = 1, y = 2 }
+            [[hardware]]
+            type = "simpi/led"
+            pin = 6
+            colorOn = "red"
+        "#).unwrap();
+        fs::write(dir.join("board.json"), json!({
+            "type": "simpi/board",
+            "defaults": { "simpi/led": { "colorOff": "gray", "colorOn": "green" } },
+            "include": [ { "file": "modules/leds.toml", "offset": { "x": 10, "y": 4 } } ],
+            "hardware": [ { "type": "simpi/led", "pin": 4 } ],
+        }).to_string()).unwrap();
+        let resolved = resolve_file(dir.join("board.json").to_str().unwrap()).unwrap();
+        assert_eq!(resolved.board["hardware"][0]["colorOn"], "green");
+        assert_eq!(resolved.included.len(), 2);
+        let (origin, led) = &resolved.included[0];
+        assert!(origin.ends_with("modules/leds.toml"));
+        assert_eq!(led["colorOn"], "lightblue");
+        assert_eq!(led["colorOff"], "gray");
+        assert_eq!(led["position"], json!({ "x": 11, "y": 6 }));
+        assert_eq!(resolved.included[1].1["colorOn"], "red");
+        assert_eq!(resolved.included[1].1["position"], json!({ "x": 10, "y": 4 }));
+        fs::write(dir.join("modules/leds.toml"), r#"
+            type = "simpi/board"
+            include = [ { file = "../board.json" } ]
+        "#).unwrap();
+        assert!(resolve_file(dir.join("board.json").to_str().unwrap()).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,287 @@
+/*!keypad.rs
+ * Hardware | Matrix keypad definition. A pressed key links its row and
+ * column pin, the level of an input linked to an output is resolved by the
+ * client on each read, so programs can scan the matrix at any speed.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::board::Board;
+use super::model::{KeypadModel, PositionModel};
+use serde_json::{Map, Value as SerdeValue};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Rect};
+use tui::style::{Color, Style};
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
+use tui::Frame;
+use utils::gpioregs::RegMemory;
+use utils::outlog::unix_time_us;
+
+// Labels of the common 4x4 keypad, smaller keypads use the top left part
+const DEFAULT_KEYS: [&str; 4] = ["123A", "456B", "789C", "*0#D"];
+
+#[derive(Clone)]
+pub struct Keypad {
+    pub rows: Vec<u8>,
+    pub cols: Vec<u8>,
+    // Label of each key, one string per row
+    pub keys: Vec<String>,
+    // Hotkey of each key, one string per row. None uses the labels.
+    pub hotkeys: Option<Vec<String>>,
+    // Level the pins read while no key is pressed, set by the pull resistors
+    pub pull_up: bool,
+    pub hold_time_ms: u32,
+    pub name: String,
+    pub color_off: Color,
+    pub color_on: Color,
+    pub pos_x: u16,
+    pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
+    // Release time of each pressed key (row by row), u64::MAX while it is
+    // held with the mouse
+    pressed: Vec<Option<u64>>,
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
+}
+
+impl Default for Keypad {
+    fn default() -> Self {
+        Self {
+            rows: vec![18, 23, 24, 25],
+            cols: vec![4, 17, 27, 22],
+            keys: DEFAULT_KEYS.iter().map(|&k| k.to_owned()).collect(),
+            hotkeys: None,
+            pull_up: true,
+            hold_time_ms: 200,
+            name: String::from("KEYPAD $n"),
+            color_off: Color::DarkGray,
+            color_on: Color::LightYellow,
+            pos_x: 0,
+            pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
+            pressed: vec![],
+            extra: Map::new(),
+        }
+    }
+}
+
+impl Keypad {
+    pub const MAX_ROWS: usize = 8;
+    pub const MAX_COLS: usize = 8;
+
+    pub fn default_keys(rows: usize, cols: usize) -> Vec<String> {
+        (0..rows).map(|r| {
+            (0..cols).map(|c| DEFAULT_KEYS.get(r).and_then(|k| k.chars().nth(c)).unwrap_or(' ')).collect()
+        }).collect()
+    }
+    // Keeps the keys of the layout which exist in the defaults, filling
+    // missing ones from the defaults.
+    fn fit_layout(layout: &[String], defaults: &[String]) -> Vec<String> {
+        defaults.iter().enumerate().map(|(r, row)| row.chars().enumerate().map(|(c, default)| {
+            layout.get(r).and_then(|k| k.chars().nth(c)).unwrap_or(default)
+        }).collect()).collect()
+    }
+    // Resizes the labels and hotkeys to the number of rows and columns,
+    // filling new keys with the default labels.
+    pub fn fit_keys(&mut self) {
+        let defaults = Self::default_keys(self.rows.len(), self.cols.len());
+        self.keys = Self::fit_layout(&self.keys, &defaults);
+        if let Some(hotkeys) = &self.hotkeys {
+            let defaults: Vec<String> = defaults.iter().map(|k| k.to_lowercase()).collect();
+            self.hotkeys = Some(Self::fit_layout(hotkeys, &defaults));
+        }
+        self.pressed.clear();
+    }
+    pub fn from_model(model: KeypadModel) -> Self {
+        let mut keypad = Self::default();
+        // Pins out of range are ignored
+        keypad.rows = model.rows.into_iter().filter(|&p| p < 32).take(Self::MAX_ROWS).collect();
+        keypad.cols = model.cols.into_iter().filter(|&p| p < 32).take(Self::MAX_COLS).collect();
+        keypad.keys = model.keys
+            .unwrap_or_else(|| Self::default_keys(keypad.rows.len(), keypad.cols.len()));
+        keypad.hotkeys = model.hotkeys;
+        keypad.pull_up = model.pull.as_deref() != Some("down");
+        if let Some(hold_time) = model.hold_time {
+            keypad.hold_time_ms = hold_time.clamp(10, 10000);
+        }
+        if let Some(name) = model.name {
+            keypad.name = name;
+        }
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            keypad.color_off = c;
+        }
+        if let Some(c) = model.color_on.and_then(|c| super::helper_str_to_color(c).ok()) {
+            keypad.color_on = c;
+        }
+        if let Some(position) = model.position {
+            keypad.pos_x = position.x;
+            keypad.pos_y = position.y;
+        }
+        keypad.extra = model.extra;
+        keypad
+    }
+    pub fn to_model(&self) -> KeypadModel {
+        KeypadModel {
+            name: Some(self.name.clone()),
+            rows: self.rows.clone(),
+            cols: self.cols.clone(),
+            keys: Some(self.keys.clone()),
+            hotkeys: self.hotkeys.clone(),
+            pull: Some(String::from(if self.pull_up { "up" } else { "down" })),
+            hold_time: if self.hold_time_ms != Self::default().hold_time_ms { Some(self.hold_time_ms) } else { None },
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            color_on: Some(super::helper_color_to_str(self.color_on)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
+        }
+    }
+    pub fn width(&self) -> u16 {
+        (self.cols.len() as u16 * 4).saturating_sub(1).max(1)
+    }
+    pub fn height(&self) -> u16 {
+        self.rows.len() as u16 + 1
+    }
+    pub fn label(&self, row: usize, col: usize) -> char {
+        self.keys.get(row).and_then(|k| k.chars().nth(col)).unwrap_or(' ')
+    }
+    pub fn hotkey(&self, row: usize, col: usize) -> char {
+        match &self.hotkeys {
+            Some(hotkeys) => hotkeys.get(row).and_then(|k| k.chars().nth(col)).unwrap_or(' '),
+            None => self.label(row, col).to_ascii_lowercase(),
+        }
+    }
+    fn set_pressed(&mut self, row: usize, col: usize, release_at_us: Option<u64>) {
+        self.pressed.resize(self.rows.len() * self.cols.len(), None);
+        if let Some(pressed) = self.pressed.get_mut(row * self.cols.len() + col) {
+            *pressed = release_at_us;
+        }
+    }
+    fn is_pressed(&self, row: usize, col: usize) -> bool {
+        self.pressed.get(row * self.cols.len() + col).map_or(false, |p| p.is_some())
+    }
+    // Presses all keys with the hotkey for the hold time, as terminals do
+    // not report key releases. Returns true if a key has been pressed.
+    pub fn press(&mut self, c: char) -> bool {
+        let release_at_us = unix_time_us() + self.hold_time_ms as u64 * 1000;
+        let mut is_pressed = false;
+        for row in 0..self.rows.len() {
+            for col in 0..self.cols.len() {
+                if c != ' ' && self.hotkey(row, col) == c {
+                    self.set_pressed(row, col, Some(release_at_us));
+                    is_pressed = true;
+                }
+            }
+        }
+        is_pressed
+    }
+    // Holds the key at the given position (relative to the part) until the
+    // mouse button is released.
+    pub fn mouse_down(&mut self, x: u16, y: u16) {
+        let (row, col) = (y as usize, x as usize / 4);
+        if row >= 1 && row <= self.rows.len() && col < self.cols.len() && x % 4 != 3 {
+            self.set_pressed(row - 1, col, Some(u64::MAX));
+        }
+    }
+    pub fn mouse_up(&mut self) {
+        for pressed in self.pressed.iter_mut() {
+            if *pressed == Some(u64::MAX) {
+                *pressed = None;
+            }
+        }
+    }
+    pub fn sync(&mut self, reg_memory: &mut RegMemory) -> &mut Self {
+        let now_us = unix_time_us();
+        for pressed in self.pressed.iter_mut() {
+            if pressed.map_or(false, |t| now_us >= t) {
+                *pressed = None;
+            }
+        }
+        let mask = self.rows.iter().chain(self.cols.iter()).fold(0u32, |m, &p| m | (0x1 << p));
+        reg_memory.links.unlink(mask);
+        for (r, &row) in self.rows.iter().enumerate() {
+            for (c, &col) in self.cols.iter().enumerate() {
+                if self.is_pressed(r, c) {
+                    reg_memory.links.link(row, col);
+                }
+            }
+        }
+        // Pins which are not linked to an output read the pull level
+        for &pin in self.rows.iter().chain(self.cols.iter()) {
+            reg_memory.input.write_pin(pin, self.pull_up as u8);
+        }
+        self
+    }
+    pub fn render(
+        &self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>,
+        area: Rect, board: &Board
+    ) {
+        let keypad_area = Rect {
+            x: area.x + self.pos_x + 1,
+            y: area.y + self.pos_y + 1,
+            width: self.width(),
+            height: self.height(),
+        };
+        if !super::helper_is_rect_in_range(area, keypad_area) {
+            return;
+        }
+        let style = Style::default().fg(board.foreground_color).bg(board.background_color);
+        let mut keypad_content = vec![
+            Text::styled(format!("{}\n", self.name.chars().take(self.width() as usize).collect::<String>()), style),
+        ];
+        for row in 0..self.rows.len() {
+            for col in 0..self.cols.len() {
+                if col > 0 {
+                    keypad_content.push(Text::styled(" ", style));
+                }
+                let color = if self.is_pressed(row, col) { self.color_on } else { self.color_off };
+                keypad_content.push(Text::styled(
+                    format!(" {} ", self.label(row, col)),
+                    Style::default().fg(Color::Black).bg(color)
+                ));
+            }
+            keypad_content.push(Text::raw("\n"));
+        }
+        Paragraph::new(keypad_content.iter())
+            .block(Block::default()
+                .borders(Borders::NONE)
+            )
+            .style(Style::default().bg(board.background_color))
+            .render(f, keypad_area);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn Keypad__sync() {
+        let mut keypad = Keypad::default();
+        keypad.rows = vec![5, 6, 13, 19];
+        keypad.cols = vec![12, 16, 20];
+        keypad.keys = Keypad::default_keys(4, 3);
+        assert_eq!(keypad.keys[3], "*0#");
+        let mut reg_memory = RegMemory::new();
+        for &row in keypad.rows.iter() {
+            reg_memory.config.write_pin(row, 0);
+            reg_memory.output.write_pin(row, 1);
+        }
+        assert!(keypad.press('8'));
+        keypad.sync(&mut reg_memory);
+        assert_eq!(reg_memory.read_input(16), 1);
+        // Scanning the third row finds the key
+        reg_memory.output.write_pin(13, 0);
+        assert_eq!(reg_memory.read_input(12), 1);
+        assert_eq!(reg_memory.read_input(16), 0);
+        keypad.mouse_up();
+        keypad.pressed.clear();
+        keypad.sync(&mut reg_memory);
+        assert_eq!(reg_memory.read_input(16), 1);
+    }
+}
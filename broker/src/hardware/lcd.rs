@@ -0,0 +1,447 @@
+/*!lcd.rs
+ * Hardware | HD44780 character LCD definition. The data is latched from the
+ * OUTPUT log on each falling edge of the E pin, like the controller does.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::board::Board;
+use super::model::{LcdModel, PositionModel};
+use serde_json::{Map, Value as SerdeValue};
+use tui::backend::CrosstermBackend;
+use tui::layout::Rect;
+use tui::style::{Color, Modifier, Style};
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
+use tui::Frame;
+use utils::gpioregs::RegMemory;
+use utils::outlog::unix_time_us;
+
+// Length of a display line in DDRAM, in 2-line mode line 2 starts at 0x40
+const LINE_LEN_1: u8 = 80;
+const LINE_LEN_2: u8 = 40;
+
+// Character generator ROM (A00), characters which differ from ASCII or are
+// above it. Unknown characters are shown as '?'.
+fn rom_char(code: u8) -> char {
+    match code {
+        0x5C => '¥',
+        0x7E => '→',
+        0x7F => '←',
+        0x20..=0x7D => code as char,
+        0xA5 => '·',
+        0xDF => '°',
+        0xE0 => 'α',
+        0xE2 => 'β',
+        0xE3 => 'ε',
+        0xE4 => 'µ',
+        0xE5 => 'σ',
+        0xE6 => 'ρ',
+        0xF2 => 'θ',
+        0xF3 => '∞',
+        0xF4 => 'Ω',
+        0xF6 => 'Σ',
+        0xF7 => 'π',
+        0xFF => '█',
+        _ => '?',
+    }
+}
+
+// State of the controller. Only the parts needed to show the display are
+// emulated, reading (RW pin) and the busy flag are not supported.
+#[derive(Clone)]
+pub struct Hd44780 {
+    // Indexed by DDRAM address
+    ddram: [u8; 128],
+    // 8 characters of 8 rows, 5 pixels each
+    cgram: [u8; 64],
+    address: u8,
+    cgram_selected: bool,
+    increment: bool,
+    shift_on_write: bool,
+    display_on: bool,
+    cursor_on: bool,
+    blink_on: bool,
+    // Display shift to the left, in characters
+    shift: u8,
+    eight_bit: bool,
+    two_lines: bool,
+    // High nibble of a transfer in 4-bit mode
+    nibble: Option<u8>,
+}
+
+impl Default for Hd44780 {
+    fn default() -> Self {
+        // The state after the internal reset on power on
+        Self {
+            ddram: [0x20; 128],
+            cgram: [0; 64],
+            address: 0,
+            cgram_selected: false,
+            increment: true,
+            shift_on_write: false,
+            display_on: false,
+            cursor_on: false,
+            blink_on: false,
+            shift: 0,
+            eight_bit: true,
+            two_lines: false,
+            nibble: None,
+        }
+    }
+}
+
+impl Hd44780 {
+    fn line_len(&self) -> u8 {
+        if self.two_lines { LINE_LEN_2 } else { LINE_LEN_1 }
+    }
+    // Handles a falling edge of E. `value` are the levels of the connected
+    // data pins, D0..D7 on an 8-bit bus or D4..D7 on a 4-bit bus.
+    pub fn write(&mut self, rs: bool, value: u8, wide_bus: bool) {
+        if self.eight_bit {
+            // Unconnected pins D0..D3 read as low
+            self.execute(rs, if wide_bus { value } else { value << 4 });
+        } else {
+            let nibble = (if wide_bus { value >> 4 } else { value }) & 0xF;
+            match self.nibble.take() {
+                Some(high) => self.execute(rs, (high << 4) | nibble),
+                None => self.nibble = Some(nibble),
+            }
+        }
+    }
+    fn execute(&mut self, rs: bool, byte: u8) {
+        if rs {
+            if self.cgram_selected {
+                self.cgram[self.address as usize & 0x3F] = byte & 0x1F;
+            } else {
+                self.ddram[self.address as usize & 0x7F] = byte;
+                if self.shift_on_write {
+                    self.shift_display(self.increment);
+                }
+            }
+            self.step_address(self.increment);
+        } else if byte & 0x80 != 0 {
+            self.address = byte & 0x7F;
+            self.cgram_selected = false;
+        } else if byte & 0x40 != 0 {
+            self.address = byte & 0x3F;
+            self.cgram_selected = true;
+        } else if byte & 0x20 != 0 {
+            self.eight_bit = byte & 0x10 != 0;
+            self.two_lines = byte & 0x08 != 0;
+            self.nibble = None;
+        } else if byte & 0x10 != 0 {
+            if byte & 0x08 != 0 {
+                self.shift_display(byte & 0x04 == 0);
+            } else {
+                self.step_address(byte & 0x04 != 0);
+            }
+        } else if byte & 0x08 != 0 {
+            self.display_on = byte & 0x04 != 0;
+            self.cursor_on = byte & 0x02 != 0;
+            self.blink_on = byte & 0x01 != 0;
+        } else if byte & 0x04 != 0 {
+            self.increment = byte & 0x02 != 0;
+            self.shift_on_write = byte & 0x01 != 0;
+        } else if byte & 0x02 != 0 {
+            self.address = 0;
+            self.cgram_selected = false;
+            self.shift = 0;
+        } else if byte & 0x01 != 0 {
+            self.ddram = [0x20; 128];
+            self.address = 0;
+            self.cgram_selected = false;
+            self.shift = 0;
+            self.increment = true;
+        }
+    }
+    fn step_address(&mut self, forward: bool) {
+        if self.cgram_selected {
+            let address = if forward { self.address + 1 } else { self.address.wrapping_sub(1) };
+            self.address = address & 0x3F;
+        } else if self.two_lines {
+            // The end of line 1 continues with line 2 and vice versa
+            let (line, col) = (self.address & 0x40, self.address & 0x3F);
+            self.address = match (forward, col) {
+                (true, c) if c + 1 >= LINE_LEN_2 => line ^ 0x40,
+                (true, c) => line | (c + 1),
+                (false, 0) => (line ^ 0x40) | (LINE_LEN_2 - 1),
+                (false, c) => line | (c - 1),
+            };
+        } else {
+            let len = LINE_LEN_1;
+            self.address = if forward { (self.address + 1) % len } else { (self.address + len - 1) % len };
+        }
+    }
+    fn shift_display(&mut self, left: bool) {
+        // 80 is a multiple of both line lengths
+        self.shift = if left {
+            (self.shift + 1) % LINE_LEN_1
+        } else {
+            (self.shift + LINE_LEN_1 - 1) % LINE_LEN_1
+        };
+    }
+    // DDRAM address shown at the given position of a display with the given
+    // number of columns. Rows 3 and 4 continue rows 1 and 2.
+    fn address_at(&self, row: u8, col: u8, cols: u8) -> u8 {
+        let len = self.line_len() as u16;
+        let (line, offset) = if self.two_lines {
+            (row % 2, (row / 2) as u16 * cols as u16)
+        } else {
+            (0, row as u16 * cols as u16)
+        };
+        let col = ((offset + col as u16 + self.shift as u16) % len) as u8;
+        return line * 0x40 + col;
+    }
+    // Character shown at the given position, characters of the CGRAM are
+    // approximated by their share of lit pixels.
+    pub fn char_at(&self, row: u8, col: u8, cols: u8) -> char {
+        if !self.display_on {
+            return ' ';
+        }
+        let code = self.ddram[self.address_at(row, col, cols) as usize];
+        if code < 0x10 {
+            let c = (code & 0x7) as usize * 8;
+            let lit: u32 = self.cgram[c..(c + 8)].iter().map(|r| r.count_ones()).sum();
+            return match lit {
+                0 => ' ',
+                1..=10 => '░',
+                11..=20 => '▒',
+                21..=30 => '▓',
+                _ => '█',
+            };
+        }
+        return rom_char(code);
+    }
+    pub fn is_cursor_at(&self, row: u8, col: u8, cols: u8) -> bool {
+        return self.display_on && !self.cgram_selected && self.address_at(row, col, cols) == self.address;
+    }
+}
+
+#[derive(Clone)]
+pub struct Lcd {
+    pub rs: u8,
+    pub e: u8,
+    // Data pins, D0..D7 for 8-bit mode or D4..D7 for 4-bit mode
+    pub data: Vec<u8>,
+    pub rows: u8,
+    pub cols: u8,
+    pub name: String,
+    // Background of the screen and color of the characters
+    pub color_off: Color,
+    pub color_on: Color,
+    pub pos_x: u16,
+    pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
+    controller: Hd44780,
+    // Read position in the OUTPUT log and the OUTPUT register value of the
+    // last event read
+    log_seq: u64,
+    last_output: Option<u32>,
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
+}
+
+impl Default for Lcd {
+    fn default() -> Self {
+        Self {
+            rs: 2,
+            e: 3,
+            data: vec![4, 5, 6, 7],
+            rows: 2,
+            cols: 16,
+            name: String::from("LCD $n"),
+            color_off: Color::LightGreen,
+            color_on: Color::Black,
+            pos_x: 0,
+            pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
+            controller: Hd44780::default(),
+            log_seq: 0,
+            last_output: None,
+            extra: Map::new(),
+        }
+    }
+}
+
+impl Lcd {
+    pub const MAX_ROWS: u8 = 4;
+    pub const MAX_COLS: u8 = 40;
+
+    pub fn from_model(model: LcdModel) -> Self {
+        let mut lcd = Self::default();
+        // Pins out of range are ignored
+        if model.rs < 32 {
+            lcd.rs = model.rs;
+        }
+        if model.e < 32 {
+            lcd.e = model.e;
+        }
+        let data: Vec<u8> = model.data.into_iter().filter(|&pin| pin < 32).collect();
+        if data.len() == 4 || data.len() == 8 {
+            lcd.data = data;
+        }
+        if let Some(rows) = model.rows {
+            lcd.rows = rows.clamp(1, Self::MAX_ROWS);
+        }
+        if let Some(cols) = model.cols {
+            lcd.cols = cols.clamp(1, Self::MAX_COLS);
+        }
+        if let Some(name) = model.name {
+            lcd.name = name;
+        }
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            lcd.color_off = c;
+        }
+        if let Some(c) = model.color_on.and_then(|c| super::helper_str_to_color(c).ok()) {
+            lcd.color_on = c;
+        }
+        if let Some(position) = model.position {
+            lcd.pos_x = position.x;
+            lcd.pos_y = position.y;
+        }
+        lcd.extra = model.extra;
+        lcd
+    }
+    pub fn to_model(&self) -> LcdModel {
+        LcdModel {
+            name: Some(self.name.clone()),
+            rs: self.rs,
+            e: self.e,
+            data: self.data.clone(),
+            rows: Some(self.rows),
+            cols: Some(self.cols),
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            color_on: Some(super::helper_color_to_str(self.color_on)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
+        }
+    }
+    pub fn width(&self) -> u16 {
+        self.cols as u16 + 2
+    }
+    pub fn height(&self) -> u16 {
+        self.rows as u16 + 2
+    }
+    // Passes a new OUTPUT register value to the controller if E falls
+    fn latch(&mut self, output: u32) {
+        let level = |pin: u8| (output >> pin) & 0x1 != 0;
+        if let Some(last) = self.last_output {
+            if (last >> self.e) & 0x1 != 0 && !level(self.e) {
+                let value = self.data.iter().enumerate()
+                    .fold(0u8, |v, (i, &pin)| v | ((level(pin) as u8) << i));
+                self.controller.write(level(self.rs), value, self.data.len() == 8);
+            }
+        }
+        self.last_output = Some(output);
+    }
+    pub fn sync(&mut self, reg_memory: &RegMemory) -> &mut Self {
+        // The first sync replays what is left in the log, so text written
+        // before the broker has been started is shown as well
+        for event in reg_memory.output_log.read_since(&mut self.log_seq) {
+            self.latch(event.value);
+        }
+        self
+    }
+    pub fn render(
+        &self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>,
+        area: Rect, board: &Board
+    ) {
+        let lcd_area = Rect {
+            x: area.x + self.pos_x + 1,
+            y: area.y + self.pos_y + 1,
+            width: self.width(),
+            height: self.height(),
+        };
+        if !super::helper_is_rect_in_range(area, lcd_area) {
+            return;
+        }
+        let style = Style::default().fg(self.color_on).bg(self.color_off);
+        // The blinking block alternates with the character twice a second
+        let blink = self.controller.blink_on && (unix_time_us() / 500_000) % 2 == 0;
+        let mut content = vec![];
+        for row in 0..self.rows {
+            for col in 0..self.cols {
+                let c = self.controller.char_at(row, col, self.cols);
+                content.push(if self.controller.is_cursor_at(row, col, self.cols) {
+                    let mut cursor_style = style;
+                    if self.controller.cursor_on {
+                        cursor_style = cursor_style.modifier(Modifier::UNDERLINED);
+                    }
+                    if blink {
+                        cursor_style = cursor_style.fg(self.color_off).bg(self.color_on);
+                    }
+                    Text::styled(c.to_string(), cursor_style)
+                } else {
+                    Text::styled(c.to_string(), style)
+                });
+            }
+            content.push(Text::raw("\n"));
+        }
+        Paragraph::new(content.iter())
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(board.foreground_color).bg(board.background_color))
+                .title(&self.name)
+                .title_style(Style::default().fg(board.foreground_color).bg(board.background_color))
+            )
+            .style(style)
+            .render(f, lcd_area);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn Lcd__latch() {
+        let mut lcd = Lcd::default();
+        lcd.cols = 16;
+        // Sends a nibble on D4..D7 (pins 4..7) with RS on pin 2 and E on pin 3
+        let send = |lcd: &mut Lcd, rs: bool, nibble: u8| {
+            let output = ((rs as u32) << 2) | ((nibble as u32 & 0xF) << 4);
+            lcd.latch(output);
+            lcd.latch(output | (0x1 << 3));
+            lcd.latch(output);
+        };
+        let send_byte = |lcd: &mut Lcd, rs: bool, byte: u8| {
+            send(lcd, rs, byte >> 4);
+            send(lcd, rs, byte & 0xF);
+        };
+        // Initialization as done by wiringPi's lcdInit()
+        for &nibble in [0x3, 0x3, 0x3, 0x2].iter() {
+            send(&mut lcd, false, nibble);
+        }
+        for &cmd in [0x28, 0x0C, 0x06, 0x01].iter() {
+            send_byte(&mut lcd, false, cmd);
+        }
+        for &c in b"Hi".iter() {
+            send_byte(&mut lcd, true, c);
+        }
+        send_byte(&mut lcd, false, 0xC0);
+        send_byte(&mut lcd, true, b'x');
+        let c = &lcd.controller;
+        assert_eq!((c.char_at(0, 0, 16), c.char_at(0, 1, 16), c.char_at(0, 2, 16)), ('H', 'i', ' '));
+        assert_eq!(c.char_at(1, 0, 16), 'x');
+        assert!(c.is_cursor_at(1, 1, 16));
+        // Custom character 0 with all pixels lit
+        send_byte(&mut lcd, false, 0x40);
+        for _ in 0..8 {
+            send_byte(&mut lcd, true, 0x1F);
+        }
+        send_byte(&mut lcd, false, 0x80);
+        send_byte(&mut lcd, true, 0x00);
+        assert_eq!(lcd.controller.char_at(0, 0, 16), '█');
+        // Shifting the display to the left moves the text with it
+        send_byte(&mut lcd, false, 0x18);
+        assert_eq!(lcd.controller.char_at(0, 0, 16), 'i');
+        send_byte(&mut lcd, false, 0x01);
+        assert_eq!(lcd.controller.char_at(0, 0, 16), ' ');
+    }
+}
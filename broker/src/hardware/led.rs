@@ -6,8 +6,8 @@
  */
 
 use super::board::Board;
-use serde_json::{Value as SerdeValue};
-use std::io::{Error, ErrorKind};
+use super::model::{LedModel, PositionModel};
+use serde_json::{Map, Value as SerdeValue};
 use tui::backend::CrosstermBackend;
 use tui::layout::{Rect};
 use tui::style::{Color, Style};
@@ -23,7 +23,13 @@ pub struct Led {
     pub color_on: Color,
     pub pos_x: u16,
     pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
     state: bool,
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
 }
 
 impl Default for Led {
@@ -35,82 +41,48 @@ impl Default for Led {
             color_on: Color::LightRed,
             pos_x: 0,
             pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
             state: false,
+            extra: Map::new(),
         }
     }
 }
 
 impl Led {
-    pub fn from_json(json: SerdeValue) -> Result<Self, Error> {
-        match json {
-            SerdeValue::Object(map) => {
-                let mut led = Self::default();
-                let mut is_valid = false;
-                for (k, v) in map.iter() {
-                    match k.as_ref() {
-                        "type" => {
-                            if v.is_string() {
-                                if v.as_str().unwrap() == "simpi/led" {
-                                    is_valid = true;
-                                }
-                            }
-                        },
-                        "name" => {
-                            if v.is_string() {
-                                led.name = v.as_str().unwrap().to_owned();
-                            }
-                        },
-                        "pin" => {
-                            if v.is_u64() {
-                                led.pin = v.as_u64().unwrap() as u8;
-                            }
-                        },
-                        "colorOff" => {
-                            if v.is_string() {
-                                let c = super::helper_str_to_color(v.as_str().unwrap().to_owned());
-                                if c.is_ok() {
-                                    led.color_off = c.unwrap();
-                                }
-                            }
-                        },
-                        "colorOn" => {
-                            if v.is_string() {
-                                let c = super::helper_str_to_color(v.as_str().unwrap().to_owned());
-                                if c.is_ok() {
-                                    led.color_on = c.unwrap();
-                                }
-                            }
-                        },
-                        "position" => {
-                            if v.is_object() {
-                                let s = v.as_object().unwrap();
-                                for (k, v) in s.iter() {
-                                    match k.as_ref() {
-                                        "x" => {
-                                            if v.is_u64() {
-                                                led.pos_x = v.as_u64().unwrap() as u16;
-                                            }
-                                        },
-                                        "y" => {
-                                            if v.is_u64() {
-                                                led.pos_y = v.as_u64().unwrap() as u16;
-                                            }
-                                        },
-                                        _ => {}
-                                    }
-                                }
-                            }
-                        },
-                        _ => {}
-                    }
-                }
-                if is_valid {
-                    Ok(led)
-                } else {
-                    Err(Error::new(ErrorKind::InvalidData, "Input data is invalid!"))
-                }
-            },
-            _ => Err(Error::new(ErrorKind::InvalidInput, "Input must be map!"))
+    pub const WIDTH: u16 = 9;
+    pub const HEIGHT: u16 = 2;
+
+    pub fn from_model(model: LedModel) -> Self {
+        let mut led = Self::default();
+        // Pins out of range are ignored
+        if model.pin < 32 {
+            led.pin = model.pin;
+        }
+        if let Some(name) = model.name {
+            led.name = name;
+        }
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            led.color_off = c;
+        }
+        if let Some(c) = model.color_on.and_then(|c| super::helper_str_to_color(c).ok()) {
+            led.color_on = c;
+        }
+        if let Some(position) = model.position {
+            led.pos_x = position.x;
+            led.pos_y = position.y;
+        }
+        led.extra = model.extra;
+        led
+    }
+    pub fn to_model(&self) -> LedModel {
+        LedModel {
+            pin: self.pin,
+            name: Some(self.name.clone()),
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            color_on: Some(super::helper_color_to_str(self.color_on)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
         }
     }
     pub fn get(&self) -> bool {
@@ -130,8 +102,8 @@ impl Led {
         let led_area = Rect {
             x: area.x + self.pos_x + 1,
             y: area.y + self.pos_y + 1,
-            width: 9,
-            height: 2,
+            width: Self::WIDTH,
+            height: Self::HEIGHT,
         };
         if !super::helper_is_rect_in_range(area, led_area) {
             return;
@@ -0,0 +1,164 @@
+/*!ledbar.rs
+ * Hardware | LED bar graph definition, a row of LEDs with one pin each.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::board::Board;
+use super::model::{LedBarModel, PositionModel};
+use serde_json::{Map, Value as SerdeValue};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Rect};
+use tui::style::{Color, Style};
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
+use tui::Frame;
+use utils::gpioregs::RegMemory;
+
+#[derive(Clone)]
+pub struct LedBar {
+    // Pins of the LEDs, from left to right
+    pub pins: Vec<u8>,
+    pub name: String,
+    pub color_off: Color,
+    pub color_on: Color,
+    pub pos_x: u16,
+    pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
+    // Lit LEDs, bit 0 = leftmost LED
+    state: u32,
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
+}
+
+impl Default for LedBar {
+    fn default() -> Self {
+        Self {
+            pins: (2..12).collect(),
+            name: String::from("BAR $n"),
+            color_off: Color::Black,
+            color_on: Color::LightRed,
+            pos_x: 0,
+            pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
+            state: 0,
+            extra: Map::new(),
+        }
+    }
+}
+
+impl LedBar {
+    pub const HEIGHT: u16 = 2;
+    pub const MAX_PINS: usize = 16;
+
+    pub fn from_model(model: LedBarModel) -> Self {
+        let mut ledbar = Self::default();
+        // Pins out of range are ignored
+        if !model.pins.is_empty() {
+            ledbar.pins = model.pins.into_iter().filter(|&pin| pin < 32).take(Self::MAX_PINS).collect();
+        }
+        if let Some(name) = model.name {
+            ledbar.name = name;
+        }
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            ledbar.color_off = c;
+        }
+        if let Some(c) = model.color_on.and_then(|c| super::helper_str_to_color(c).ok()) {
+            ledbar.color_on = c;
+        }
+        if let Some(position) = model.position {
+            ledbar.pos_x = position.x;
+            ledbar.pos_y = position.y;
+        }
+        ledbar.extra = model.extra;
+        ledbar
+    }
+    pub fn to_model(&self) -> LedBarModel {
+        LedBarModel {
+            name: Some(self.name.clone()),
+            pins: self.pins.clone(),
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            color_on: Some(super::helper_color_to_str(self.color_on)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
+        }
+    }
+    // Each LED takes one column, followed by a gap
+    pub fn width(&self) -> u16 {
+        let leds_width = (self.pins.len() as u16 * 2).saturating_sub(1);
+        std::cmp::max(leds_width, self.name.chars().count() as u16)
+    }
+    pub fn get(&self, led: usize) -> bool {
+        (self.state >> led) & 0x1 != 0
+    }
+    pub fn sync(&mut self, reg_memory: &RegMemory) -> &mut Self {
+        self.state = self.pins.iter().enumerate().fold(0, |state, (i, &pin)| {
+            state | ((reg_memory.output.read_pin(pin) as u32 & 0x1) << i)
+        });
+        self
+    }
+    pub fn render(
+        &self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>,
+        area: Rect, board: &Board
+    ) {
+        let ledbar_area = Rect {
+            x: area.x + self.pos_x + 1,
+            y: area.y + self.pos_y + 1,
+            width: self.width(),
+            height: Self::HEIGHT,
+        };
+        if !super::helper_is_rect_in_range(area, ledbar_area) {
+            return;
+        }
+        let mut content = vec![];
+        for i in 0..self.pins.len() {
+            if i > 0 {
+                content.push(Text::raw(" "));
+            }
+            content.push(Text::styled("█", Style::default()
+                .fg(if self.get(i) { self.color_on } else { self.color_off })
+                .bg(board.background_color)
+            ));
+        }
+        content.push(Text::raw("\n"));
+        content.push(Text::styled(self.name.clone(), Style::default()
+            .fg(board.foreground_color)
+            .bg(board.background_color)
+        ));
+        Paragraph::new(content.iter())
+            .block(Block::default()
+                .borders(Borders::NONE)
+            )
+            .style(Style::default().bg(board.background_color))
+            .render(f, ledbar_area);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn LedBar__sync() {
+        // Pins out of range are dropped, at most MAX_PINS are kept
+        let mut model = LedBarModel::default();
+        model.pins = vec![20, 40, 3, 17];
+        model.pins.extend(0..20);
+        let mut ledbar = LedBar::from_model(model);
+        assert_eq!(ledbar.pins.len(), LedBar::MAX_PINS);
+        assert_eq!(&ledbar.pins[..4], &[20, 3, 17, 0]);
+        // The leftmost LED is bit 0
+        let mut reg_memory = RegMemory::new();
+        reg_memory.output.write_pin(20, 1);
+        reg_memory.output.write_pin(17, 1);
+        ledbar.sync(&reg_memory);
+        assert_eq!(ledbar.state, 0b101);
+        assert!(ledbar.get(0) && !ledbar.get(1) && ledbar.get(2) && !ledbar.get(3));
+        assert_eq!(LedBar::from_model(LedBarModel::default()).pins, LedBar::default().pins);
+    }
+}
@@ -0,0 +1,229 @@
+/*!ledmatrix.rs
+ * Hardware | LED matrix definition. The LEDs are driven by row and column
+ * pins and decoded from the OUTPUT log, so scanned matrices are shown like
+ * the human eye would see them.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::board::Board;
+use super::model::{LedMatrixModel, PositionModel};
+use serde_json::{Map, Value as SerdeValue};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Rect};
+use tui::style::{Color, Style};
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
+use tui::Frame;
+use utils::gpioregs::RegMemory;
+use utils::outlog::OutputWindow;
+
+#[derive(Clone)]
+pub struct LedMatrix {
+    // Pins of the rows from top to bottom and of the columns from left to
+    // right
+    pub rows: Vec<u8>,
+    pub cols: Vec<u8>,
+    // The rows connect the cathodes (common cathode) or the anodes (common
+    // anode) of their LEDs. A LED lights up if its row is low and its column
+    // high, or the other way round for common anode.
+    pub common_anode: bool,
+    pub name: String,
+    pub color_off: Color,
+    pub color_on: Color,
+    pub pos_x: u16,
+    pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
+    // Lit LEDs of each row, bit 0 = leftmost column
+    state: Vec<u32>,
+    // Time window of the OUTPUT log since the last sync
+    window: OutputWindow,
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
+}
+
+impl Default for LedMatrix {
+    fn default() -> Self {
+        Self {
+            rows: (2..10).collect(),
+            cols: (10..18).collect(),
+            common_anode: false,
+            name: String::from("MATRIX $n"),
+            color_off: Color::DarkGray,
+            color_on: Color::LightRed,
+            pos_x: 0,
+            pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
+            state: vec![],
+            window: OutputWindow::new(),
+            extra: Map::new(),
+        }
+    }
+}
+
+impl LedMatrix {
+    pub const MAX_ROWS: usize = 16;
+    pub const MAX_COLS: usize = 16;
+
+    pub fn from_model(model: LedMatrixModel) -> Self {
+        let mut ledmatrix = Self::default();
+        // Pins out of range are ignored
+        if !model.rows.is_empty() {
+            ledmatrix.rows = model.rows.into_iter().filter(|&pin| pin < 32).take(Self::MAX_ROWS).collect();
+        }
+        if !model.cols.is_empty() {
+            ledmatrix.cols = model.cols.into_iter().filter(|&pin| pin < 32).take(Self::MAX_COLS).collect();
+        }
+        ledmatrix.common_anode = model.common.as_deref() == Some("anode");
+        if let Some(name) = model.name {
+            ledmatrix.name = name;
+        }
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            ledmatrix.color_off = c;
+        }
+        if let Some(c) = model.color_on.and_then(|c| super::helper_str_to_color(c).ok()) {
+            ledmatrix.color_on = c;
+        }
+        if let Some(position) = model.position {
+            ledmatrix.pos_x = position.x;
+            ledmatrix.pos_y = position.y;
+        }
+        ledmatrix.extra = model.extra;
+        ledmatrix
+    }
+    pub fn to_model(&self) -> LedMatrixModel {
+        LedMatrixModel {
+            name: Some(self.name.clone()),
+            rows: self.rows.clone(),
+            cols: self.cols.clone(),
+            common: Some(String::from(if self.common_anode { "anode" } else { "cathode" })),
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            color_on: Some(super::helper_color_to_str(self.color_on)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
+        }
+    }
+    // Each LED is half a character high, so two rows share a line
+    pub fn width(&self) -> u16 {
+        std::cmp::max(self.cols.len() as u16, self.name.chars().count() as u16)
+    }
+    pub fn height(&self) -> u16 {
+        (self.rows.len() as u16 + 1) / 2 + 1
+    }
+    pub fn get(&self, row: usize, col: usize) -> bool {
+        self.state.get(row).map_or(false, |&mask| (mask >> col) & 0x1 != 0)
+    }
+    // Lit columns of each row for the given OUTPUT register value
+    fn lit_mask(&self, output: u32) -> Vec<u32> {
+        let is_high = |pin: u8| (output >> pin) & 0x1 != 0;
+        let mut cols = 0;
+        for (i, &pin) in self.cols.iter().enumerate() {
+            if is_high(pin) != self.common_anode {
+                cols |= 0x1 << i;
+            }
+        }
+        self.rows.iter().map(|&pin| if is_high(pin) == self.common_anode { cols } else { 0 }).collect()
+    }
+    // Computes the lit LEDs from the OUTPUT register values of a time window.
+    // A LED is lit if it has been on for at least half of the time of the
+    // brightest LED, which works for scanning the rows as well as the columns
+    // and hides the ghosting while a program switches to the next row.
+    fn decode(&self, segments: &[(u32, u64)]) -> Vec<u32> {
+        let n_cols = self.cols.len();
+        let mut lit_us = vec![vec![0u64; n_cols]; self.rows.len()];
+        for &(output, duration) in segments.iter() {
+            for (r, mask) in self.lit_mask(output).into_iter().enumerate() {
+                for c in 0..n_cols {
+                    if (mask >> c) & 0x1 != 0 {
+                        lit_us[r][c] += duration;
+                    }
+                }
+            }
+        }
+        let max_us = lit_us.iter().flatten().cloned().max().unwrap_or(0);
+        lit_us.iter().map(|row| {
+            let mut mask = 0;
+            for (c, &us) in row.iter().enumerate() {
+                if max_us > 0 && us * 2 >= max_us {
+                    mask |= 0x1 << c;
+                }
+            }
+            mask
+        }).collect()
+    }
+    pub fn sync(&mut self, reg_memory: &RegMemory) -> &mut Self {
+        let segments = self.window.read(&reg_memory.output_log, reg_memory.output.read());
+        self.state = self.decode(&segments);
+        self
+    }
+    pub fn render(
+        &self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>,
+        area: Rect, board: &Board
+    ) {
+        let ledmatrix_area = Rect {
+            x: area.x + self.pos_x + 1,
+            y: area.y + self.pos_y + 1,
+            width: self.width(),
+            height: self.height(),
+        };
+        if !super::helper_is_rect_in_range(area, ledmatrix_area) {
+            return;
+        }
+        let color = |lit: bool| if lit { self.color_on } else { self.color_off };
+        let mut content = vec![];
+        // The upper half block shows the even row, its background the odd one
+        for r in (0..self.rows.len()).step_by(2) {
+            for c in 0..self.cols.len() {
+                let lower = if r + 1 < self.rows.len() {
+                    color(self.get(r + 1, c))
+                } else {
+                    board.background_color
+                };
+                content.push(Text::styled("▀", Style::default()
+                    .fg(color(self.get(r, c)))
+                    .bg(lower)
+                ));
+            }
+            content.push(Text::raw("\n"));
+        }
+        content.push(Text::styled(self.name.clone(), Style::default()
+            .fg(board.foreground_color)
+            .bg(board.background_color)
+        ));
+        Paragraph::new(content.iter())
+            .block(Block::default()
+                .borders(Borders::NONE)
+            )
+            .style(Style::default().bg(board.background_color))
+            .render(f, ledmatrix_area);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn LedMatrix__decode() {
+        let mut ledmatrix = LedMatrix::default();
+        ledmatrix.rows = vec![0, 1, 2];
+        ledmatrix.cols = vec![3, 4];
+        // No row selected
+        assert_eq!(ledmatrix.decode(&[(0b00_111, 1)]), vec![0, 0, 0]);
+        // Static, common anode: row 1 selected (high), both columns on (low)
+        ledmatrix.common_anode = true;
+        assert_eq!(ledmatrix.decode(&[(!0b11_101, 1)]), vec![0b00, 0b11, 0b00]);
+        // Columns scanned instead of rows, the rows of the previous column are
+        // still set for a moment after each switch
+        ledmatrix.common_anode = false;
+        let col_0 = 0b01_010;
+        let col_1 = 0b10_101;
+        let segments = [(col_0, 1000), (0b10_010, 10), (col_1, 1000), (0b01_101, 10)].repeat(3);
+        assert_eq!(ledmatrix.decode(&segments), vec![0b01, 0b10, 0b01]);
+    }
+}
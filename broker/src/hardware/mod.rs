@@ -7,18 +7,50 @@
 
 pub mod board;
 pub mod button;
+pub mod dht;
+pub mod encoder;
+pub mod erc;
+pub mod include;
+pub mod keypad;
+pub mod lcd;
 pub mod led;
+pub mod ledbar;
+pub mod ledmatrix;
+pub mod model;
 pub mod part;
+pub mod potentiometer;
+pub mod rgbled;
+pub mod schema;
+pub mod servo;
+pub mod sevenseg;
+pub mod stepper;
+pub mod validate;
 
 pub use board::Board;
 pub use button::Button;
-pub use led::Led;
+pub use encoder::Encoder;
+pub use keypad::Keypad;
+pub use lcd::Lcd;
+pub use ledbar::LedBar;
+pub use ledmatrix::LedMatrix;
 pub use part::Part;
+pub use potentiometer::Potentiometer;
+pub use rgbled::RgbLed;
+pub use servo::Servo;
+pub use sevenseg::SevenSeg;
+pub use stepper::Stepper;
 
 use tui::layout::Rect;
 use tui::style::Color;
 
-fn helper_str_to_color(c: String) -> Result<Color, ()> {
+// Names of all colors which can be used in board files.
+pub const COLOR_NAMES: [&str; 17] = [
+    "reset", "black", "red", "green", "yellow", "blue", "magenta", "cyan", "gray",
+    "darkgray", "lightred", "lightgreen", "lightyellow", "lightblue", "lightmagenta",
+    "lightcyan", "white",
+];
+
+pub fn helper_str_to_color(c: String) -> Result<Color, ()> {
     Ok(match c.to_lowercase().as_ref() {
         "reset"         => Color::Reset,
         "black"         => Color::Black,
@@ -41,6 +73,64 @@ fn helper_str_to_color(c: String) -> Result<Color, ()> {
     })
 }
 
+pub fn helper_color_to_str(c: Color) -> String {
+    String::from(match c {
+        Color::Reset        => "reset",
+        Color::Black        => "black",
+        Color::Red          => "red",
+        Color::Green        => "green",
+        Color::Yellow       => "yellow",
+        Color::Blue         => "blue",
+        Color::Magenta      => "magenta",
+        Color::Cyan         => "cyan",
+        Color::Gray         => "gray",
+        Color::DarkGray     => "darkgray",
+        Color::LightRed     => "lightred",
+        Color::LightGreen   => "lightgreen",
+        Color::LightYellow  => "lightyellow",
+        Color::LightBlue    => "lightblue",
+        Color::LightMagenta => "lightmagenta",
+        Color::LightCyan    => "lightcyan",
+        Color::White        => "white",
+        _ => "reset",
+    })
+}
+
+// Returns the next (or previous) color of COLOR_NAMES.
+pub fn helper_cycle_color(c: Color, forward: bool) -> Color {
+    let i = COLOR_NAMES.iter().position(|&n| n == helper_color_to_str(c))
+        .unwrap_or(0);
+    let i = if forward {
+        (i + 1) % COLOR_NAMES.len()
+    } else {
+        (i + COLOR_NAMES.len() - 1) % COLOR_NAMES.len()
+    };
+    helper_str_to_color(COLOR_NAMES[i].to_owned()).unwrap_or(c)
+}
+
+// Whether the terminal supports 24-bit colors, as announced by COLORTERM.
+pub fn helper_supports_truecolor() -> bool {
+    match std::env::var("COLORTERM") {
+        Ok(v) => v == "truecolor" || v == "24bit",
+        Err(_) => false,
+    }
+}
+
+// Returns the given RGB color as true color if the terminal supports it and
+// as the closest color of the 6x6x6 cube of the 256 color palette otherwise.
+pub fn helper_rgb_to_color(r: u8, g: u8, b: u8) -> Color {
+    if helper_supports_truecolor() {
+        return Color::Rgb(r, g, b);
+    }
+    // The levels of the cube are 0, 95, 135, 175, 215 and 255
+    let level = |v: u8| match v {
+        0..=47 => 0,
+        48..=114 => 1,
+        _ => (v - 35) / 40,
+    };
+    Color::Indexed(16 + 36 * level(r) + 6 * level(g) + level(b))
+}
+
 fn helper_is_rect_in_range(parent: Rect, child: Rect) -> bool {
     let p_x_min = parent.x;
     let p_x_max = parent.x + parent.width;
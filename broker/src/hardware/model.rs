@@ -0,0 +1,511 @@
+/*!model.rs
+ * Hardware | Serde model of board files and the file formats they can be
+ * stored in (JSON, TOML, YAML).
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use serde::{de::{self, DeserializeOwned}, Deserialize, Deserializer, Serialize};
+use serde_json::{Map, Value as SerdeValue};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    // Selects the format by the extension of the given file, JSON is used for
+    // unknown extensions.
+    pub fn from_file_name(file_name: &str) -> Self {
+        let ext = Path::new(file_name).extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        match ext.as_str() {
+            "toml" => Format::Toml,
+            "yaml" | "yml" => Format::Yaml,
+            _ => Format::Json,
+        }
+    }
+    pub fn parse<T: DeserializeOwned>(self, data: &str) -> Result<T, Error> {
+        let result = match self {
+            Format::Json => serde_json::from_str(data).map_err(|e| e.to_string()),
+            Format::Toml => toml::from_str(data).map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::from_str(data).map_err(|e| e.to_string()),
+        };
+        result.map_err(|err| Error::new(
+            ErrorKind::InvalidData,
+            format!("Error while deserializing given board: {}", err)
+        ))
+    }
+    pub fn write<T: Serialize>(self, value: &T) -> Result<String, Error> {
+        let result = match self {
+            Format::Json => {
+                let mut data = vec![];
+                let formatter = serde_json::ser::PrettyFormatter::with_indent(b"    ");
+                let mut ser = serde_json::Serializer::with_formatter(&mut data, formatter);
+                value.serialize(&mut ser)
+                    .map(|_| String::from_utf8_lossy(&data).into_owned() + "\n")
+                    .map_err(|e| e.to_string())
+            },
+            // Going through toml::Value makes sure plain values are written
+            // before tables, regardless of the field order
+            Format::Toml => toml::Value::try_from(value)
+                .and_then(|v| toml::to_string(&v))
+                .map_err(|e| e.to_string()),
+            Format::Yaml => serde_yaml::to_string(value)
+                .map(|s| s + "\n")
+                .map_err(|e| e.to_string()),
+        };
+        result.map_err(|err| Error::new(
+            ErrorKind::InvalidData,
+            format!("Error while serializing given board: {}", err)
+        ))
+    }
+}
+
+pub fn read_file<T: DeserializeOwned>(file_name: &str) -> Result<T, Error> {
+    let mut data = String::new();
+    File::open(file_name)?.read_to_string(&mut data)?;
+    Format::from_file_name(file_name).parse(&data)
+}
+
+pub fn write_file<T: Serialize>(file_name: &str, value: &T) -> Result<(), Error> {
+    let data = Format::from_file_name(file_name).write(value)?;
+    File::create(file_name)?.write_all(data.as_bytes())?;
+    return Ok(());
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PositionModel {
+    #[serde(default)]
+    pub x: u16,
+    #[serde(default)]
+    pub y: u16,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SizeModel {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u16>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u16>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BoardModel {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub background_color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub foreground_color: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub size: Option<SizeModel>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub defaults: Map<String, SerdeValue>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<IncludeModel>,
+    #[serde(default)]
+    pub hardware: Vec<PartModel>,
+    // Fields unknown to this version
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+// Parts of another board file, placed at the given offset
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct IncludeModel {
+    pub file: String,
+    #[serde(default)]
+    pub offset: PositionModel,
+}
+
+pub const PART_TYPES: [&str; 13] = [
+    "simpi/led", "simpi/button", "simpi/sevenseg", "simpi/lcd_hd44780", "simpi/rgbled",
+    "simpi/potentiometer", "simpi/keypad", "simpi/encoder", "simpi/servo", "simpi/stepper",
+    "simpi/dht", "simpi/ledbar", "simpi/ledmatrix",
+];
+
+// Parts of unknown types (e.g. parts of newer versions) are kept as they are.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum PartModel {
+    Known(KnownPartModel),
+    Unknown(SerdeValue),
+}
+
+// Parts of known types must be valid, their errors are reported instead of
+// keeping them as unknown parts.
+impl<'de> Deserialize<'de> for PartModel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = SerdeValue::deserialize(deserializer)?;
+        match value.get("type").and_then(|t| t.as_str()) {
+            Some(t) if PART_TYPES.contains(&t) => {
+                let t = t.to_owned();
+                serde_json::from_value(value)
+                    .map(PartModel::Known)
+                    .map_err(|err| de::Error::custom(format!("invalid {} part: {}", t, err)))
+            },
+            _ => Ok(PartModel::Unknown(value)),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum KnownPartModel {
+    #[serde(rename = "simpi/led")]
+    Led(LedModel),
+    #[serde(rename = "simpi/button")]
+    Button(ButtonModel),
+    #[serde(rename = "simpi/sevenseg")]
+    SevenSeg(SevenSegModel),
+    #[serde(rename = "simpi/lcd_hd44780")]
+    Lcd(LcdModel),
+    #[serde(rename = "simpi/rgbled")]
+    RgbLed(RgbLedModel),
+    #[serde(rename = "simpi/potentiometer")]
+    Potentiometer(PotentiometerModel),
+    #[serde(rename = "simpi/keypad")]
+    Keypad(KeypadModel),
+    #[serde(rename = "simpi/encoder")]
+    Encoder(EncoderModel),
+    #[serde(rename = "simpi/servo")]
+    Servo(ServoModel),
+    #[serde(rename = "simpi/stepper")]
+    Stepper(StepperModel),
+    #[serde(rename = "simpi/dht")]
+    Dht(DhtModel),
+    #[serde(rename = "simpi/ledbar")]
+    LedBar(LedBarModel),
+    #[serde(rename = "simpi/ledmatrix")]
+    LedMatrix(LedMatrixModel),
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedModel {
+    pub pin: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ButtonModel {
+    pub pin: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hotkey: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mode: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hold_time: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bounce: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SevenSegModel {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub segments: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub digits: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub common: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LcdModel {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub rs: u8,
+    pub e: u8,
+    #[serde(default)]
+    pub data: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cols: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RgbLedModel {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub common: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PotentiometerModel {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub channel: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hotkey_up: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hotkey_down: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeypadModel {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub rows: Vec<u8>,
+    #[serde(default)]
+    pub cols: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keys: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hotkeys: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pull: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hold_time: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncoderModel {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub pin_a: u8,
+    pub pin_b: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin_sw: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hotkey_cw: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hotkey_ccw: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hotkey_sw: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub step_time: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hold_time: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServoModel {
+    pub pin: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_pulse: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_pulse: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_angle: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StepperModel {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub pins: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DhtModel {
+    pub pin: u8,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sensor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub humidity: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedBarModel {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub pins: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedMatrixModel {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub rows: Vec<u8>,
+    #[serde(default)]
+    pub cols: Vec<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub common: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_off: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color_on: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<PositionModel>,
+    #[serde(flatten)]
+    pub extra: Map<String, SerdeValue>,
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn Format__from_file_name() {
+        assert_eq!(Format::from_file_name("board.json"), Format::Json);
+        assert_eq!(Format::from_file_name("dir.v2/board.TOML"), Format::Toml);
+        assert_eq!(Format::from_file_name("board.yml"), Format::Yaml);
+        assert_eq!(Format::from_file_name("board"), Format::Json);
+    }
+
+    #[test]
+    fn Format__write__roundtrip() {
+        let model: BoardModel = Format::Json.parse(
+            include_str!("../../config/gpiotest-board.json")
+        ).unwrap();
+        for format in [Format::Json, Format::Toml, Format::Yaml].iter() {
+            let data = format.write(&model).unwrap();
+            assert_eq!(format.parse::<BoardModel>(&data).unwrap(), model);
+        }
+    }
+
+    #[test]
+    fn PartModel__deserialize() {
+        let part: PartModel = serde_json::from_str(r#"{ "type": "simpi/buzzer" }"#).unwrap();
+        assert!(matches!(part, PartModel::Unknown(_)));
+        let err = serde_json::from_str::<PartModel>(r#"{ "type": "simpi/led" }"#).unwrap_err();
+        assert_eq!(err.to_string(), "invalid simpi/led part: missing field `pin`");
+        let err = serde_json::from_str::<PartModel>(r#"{ "type": "simpi/led", "pin": "4" }"#).unwrap_err();
+        assert!(err.to_string().starts_with("invalid simpi/led part: invalid type: string \"4\""));
+    }
+}
@@ -6,10 +6,351 @@
  */
 
 use super::button::Button;
+use super::dht::Dht;
+use super::encoder::Encoder;
+use super::keypad::Keypad;
+use super::servo::Servo;
+use super::stepper::Stepper;
+use super::lcd::Lcd;
 use super::led::Led;
+use super::ledbar::LedBar;
+use super::ledmatrix::LedMatrix;
+use super::model::KnownPartModel;
+use super::potentiometer::Potentiometer;
+use super::rgbled::RgbLed;
+use super::sevenseg::SevenSeg;
+use tui::style::Color;
 
 #[derive(Clone)]
 pub enum Part {
     Button(Button),
     Led(Led),
+    SevenSeg(SevenSeg),
+    Lcd(Lcd),
+    RgbLed(RgbLed),
+    Potentiometer(Potentiometer),
+    Keypad(Keypad),
+    Encoder(Encoder),
+    Servo(Servo),
+    Stepper(Stepper),
+    Dht(Dht),
+    LedBar(LedBar),
+    LedMatrix(LedMatrix),
+}
+
+impl Part {
+    // Creates a part of the given type (without "simpi/") with default
+    // values, returns None for unknown types.
+    pub fn default_for(type_name: &str, name: String) -> Option<Self> {
+        let mut part = match type_name {
+            "button" => Part::Button(Button::default()),
+            "led" => Part::Led(Led::default()),
+            "sevenseg" => Part::SevenSeg(SevenSeg::default()),
+            "lcd_hd44780" => Part::Lcd(Lcd::default()),
+            "rgbled" => Part::RgbLed(RgbLed::default()),
+            "potentiometer" => Part::Potentiometer(Potentiometer::default()),
+            "keypad" => Part::Keypad(Keypad::default()),
+            "encoder" => Part::Encoder(Encoder::default()),
+            "servo" => Part::Servo(Servo::default()),
+            "stepper" => Part::Stepper(Stepper::default()),
+            "dht" => Part::Dht(Dht::default()),
+            "ledbar" => Part::LedBar(LedBar::default()),
+            "ledmatrix" => Part::LedMatrix(LedMatrix::default()),
+            _ => return None,
+        };
+        *part.name_mut() = name;
+        Some(part)
+    }
+    pub fn from_model(model: KnownPartModel) -> Self {
+        match model {
+            KnownPartModel::Button(button) => Part::Button(Button::from_model(button)),
+            KnownPartModel::Led(led) => Part::Led(Led::from_model(led)),
+            KnownPartModel::SevenSeg(sevenseg) => Part::SevenSeg(SevenSeg::from_model(sevenseg)),
+            KnownPartModel::Lcd(lcd) => Part::Lcd(Lcd::from_model(lcd)),
+            KnownPartModel::RgbLed(rgbled) => Part::RgbLed(RgbLed::from_model(rgbled)),
+            KnownPartModel::Potentiometer(pot) => Part::Potentiometer(Potentiometer::from_model(pot)),
+            KnownPartModel::Keypad(keypad) => Part::Keypad(Keypad::from_model(keypad)),
+            KnownPartModel::Encoder(encoder) => Part::Encoder(Encoder::from_model(encoder)),
+            KnownPartModel::Servo(servo) => Part::Servo(Servo::from_model(servo)),
+            KnownPartModel::Stepper(stepper) => Part::Stepper(Stepper::from_model(stepper)),
+            KnownPartModel::Dht(dht) => Part::Dht(Dht::from_model(dht)),
+            KnownPartModel::LedBar(ledbar) => Part::LedBar(LedBar::from_model(ledbar)),
+            KnownPartModel::LedMatrix(ledmatrix) => Part::LedMatrix(LedMatrix::from_model(ledmatrix)),
+        }
+    }
+    pub fn to_model(&self) -> KnownPartModel {
+        match self {
+            Part::Button(button) => KnownPartModel::Button(button.to_model()),
+            Part::Led(led) => KnownPartModel::Led(led.to_model()),
+            Part::SevenSeg(sevenseg) => KnownPartModel::SevenSeg(sevenseg.to_model()),
+            Part::Lcd(lcd) => KnownPartModel::Lcd(lcd.to_model()),
+            Part::RgbLed(rgbled) => KnownPartModel::RgbLed(rgbled.to_model()),
+            Part::Potentiometer(pot) => KnownPartModel::Potentiometer(pot.to_model()),
+            Part::Keypad(keypad) => KnownPartModel::Keypad(keypad.to_model()),
+            Part::Encoder(encoder) => KnownPartModel::Encoder(encoder.to_model()),
+            Part::Servo(servo) => KnownPartModel::Servo(servo.to_model()),
+            Part::Stepper(stepper) => KnownPartModel::Stepper(stepper.to_model()),
+            Part::Dht(dht) => KnownPartModel::Dht(dht.to_model()),
+            Part::LedBar(ledbar) => KnownPartModel::LedBar(ledbar.to_model()),
+            Part::LedMatrix(ledmatrix) => KnownPartModel::LedMatrix(ledmatrix.to_model()),
+        }
+    }
+    // Name of the part type as used in board files, without "simpi/"
+    pub fn type_name(&self) -> &str {
+        match self {
+            Part::Button(_) => "button",
+            Part::Led(_) => "led",
+            Part::SevenSeg(_) => "sevenseg",
+            Part::Lcd(_) => "lcd_hd44780",
+            Part::RgbLed(_) => "rgbled",
+            Part::Potentiometer(_) => "potentiometer",
+            Part::Keypad(_) => "keypad",
+            Part::Encoder(_) => "encoder",
+            Part::Servo(_) => "servo",
+            Part::Stepper(_) => "stepper",
+            Part::Dht(_) => "dht",
+            Part::LedBar(_) => "ledbar",
+            Part::LedMatrix(_) => "ledmatrix",
+        }
+    }
+    pub fn name(&self) -> &str {
+        match self {
+            Part::Button(button) => button.name.as_str(),
+            Part::Led(led) => led.name.as_str(),
+            Part::SevenSeg(sevenseg) => sevenseg.name.as_str(),
+            Part::Lcd(lcd) => lcd.name.as_str(),
+            Part::RgbLed(rgbled) => rgbled.name.as_str(),
+            Part::Potentiometer(pot) => pot.name.as_str(),
+            Part::Keypad(keypad) => keypad.name.as_str(),
+            Part::Encoder(encoder) => encoder.name.as_str(),
+            Part::Servo(servo) => servo.name.as_str(),
+            Part::Stepper(stepper) => stepper.name.as_str(),
+            Part::Dht(dht) => dht.name.as_str(),
+            Part::LedBar(ledbar) => ledbar.name.as_str(),
+            Part::LedMatrix(ledmatrix) => ledmatrix.name.as_str(),
+        }
+    }
+    pub fn name_mut(&mut self) -> &mut String {
+        match self {
+            Part::Button(button) => &mut button.name,
+            Part::Led(led) => &mut led.name,
+            Part::SevenSeg(sevenseg) => &mut sevenseg.name,
+            Part::Lcd(lcd) => &mut lcd.name,
+            Part::RgbLed(rgbled) => &mut rgbled.name,
+            Part::Potentiometer(pot) => &mut pot.name,
+            Part::Keypad(keypad) => &mut keypad.name,
+            Part::Encoder(encoder) => &mut encoder.name,
+            Part::Servo(servo) => &mut servo.name,
+            Part::Stepper(stepper) => &mut stepper.name,
+            Part::Dht(dht) => &mut dht.name,
+            Part::LedBar(ledbar) => &mut ledbar.name,
+            Part::LedMatrix(ledmatrix) => &mut ledmatrix.name,
+        }
+    }
+    // Colors (off, on) of the part, parts which mix their color when on
+    // have no on color
+    pub fn colors(&self) -> (Color, Option<Color>) {
+        match self {
+            Part::Button(button) => (button.color_off, Some(button.color_on)),
+            Part::Led(led) => (led.color_off, Some(led.color_on)),
+            Part::SevenSeg(sevenseg) => (sevenseg.color_off, Some(sevenseg.color_on)),
+            Part::Lcd(lcd) => (lcd.color_off, Some(lcd.color_on)),
+            Part::RgbLed(rgbled) => (rgbled.color_off, None),
+            Part::Potentiometer(pot) => (pot.color_off, Some(pot.color_on)),
+            Part::Keypad(keypad) => (keypad.color_off, Some(keypad.color_on)),
+            Part::Encoder(encoder) => (encoder.color_off, Some(encoder.color_on)),
+            Part::Servo(servo) => (servo.color_off, Some(servo.color_on)),
+            Part::Stepper(stepper) => (stepper.color_off, Some(stepper.color_on)),
+            Part::Dht(dht) => (dht.color_off, Some(dht.color_on)),
+            Part::LedBar(ledbar) => (ledbar.color_off, Some(ledbar.color_on)),
+            Part::LedMatrix(ledmatrix) => (ledmatrix.color_off, Some(ledmatrix.color_on)),
+        }
+    }
+    pub fn colors_mut(&mut self) -> (&mut Color, Option<&mut Color>) {
+        match self {
+            Part::Button(button) => (&mut button.color_off, Some(&mut button.color_on)),
+            Part::Led(led) => (&mut led.color_off, Some(&mut led.color_on)),
+            Part::SevenSeg(sevenseg) => (&mut sevenseg.color_off, Some(&mut sevenseg.color_on)),
+            Part::Lcd(lcd) => (&mut lcd.color_off, Some(&mut lcd.color_on)),
+            Part::RgbLed(rgbled) => (&mut rgbled.color_off, None),
+            Part::Potentiometer(pot) => (&mut pot.color_off, Some(&mut pot.color_on)),
+            Part::Keypad(keypad) => (&mut keypad.color_off, Some(&mut keypad.color_on)),
+            Part::Encoder(encoder) => (&mut encoder.color_off, Some(&mut encoder.color_on)),
+            Part::Servo(servo) => (&mut servo.color_off, Some(&mut servo.color_on)),
+            Part::Stepper(stepper) => (&mut stepper.color_off, Some(&mut stepper.color_on)),
+            Part::Dht(dht) => (&mut dht.color_off, Some(&mut dht.color_on)),
+            Part::LedBar(ledbar) => (&mut ledbar.color_off, Some(&mut ledbar.color_on)),
+            Part::LedMatrix(ledmatrix) => (&mut ledmatrix.color_off, Some(&mut ledmatrix.color_on)),
+        }
+    }
+    pub fn included_from(&self) -> Option<&str> {
+        match self {
+            Part::Button(button) => button.included_from.as_deref(),
+            Part::Led(led) => led.included_from.as_deref(),
+            Part::SevenSeg(sevenseg) => sevenseg.included_from.as_deref(),
+            Part::Lcd(lcd) => lcd.included_from.as_deref(),
+            Part::RgbLed(rgbled) => rgbled.included_from.as_deref(),
+            Part::Potentiometer(pot) => pot.included_from.as_deref(),
+            Part::Keypad(keypad) => keypad.included_from.as_deref(),
+            Part::Encoder(encoder) => encoder.included_from.as_deref(),
+            Part::Servo(servo) => servo.included_from.as_deref(),
+            Part::Stepper(stepper) => stepper.included_from.as_deref(),
+            Part::Dht(dht) => dht.included_from.as_deref(),
+            Part::LedBar(ledbar) => ledbar.included_from.as_deref(),
+            Part::LedMatrix(ledmatrix) => ledmatrix.included_from.as_deref(),
+        }
+    }
+    pub fn set_included_from(&mut self, file_name: Option<String>) {
+        match self {
+            Part::Button(button) => { button.included_from = file_name; },
+            Part::Led(led) => { led.included_from = file_name; },
+            Part::SevenSeg(sevenseg) => { sevenseg.included_from = file_name; },
+            Part::Lcd(lcd) => { lcd.included_from = file_name; },
+            Part::RgbLed(rgbled) => { rgbled.included_from = file_name; },
+            Part::Potentiometer(pot) => { pot.included_from = file_name; },
+            Part::Keypad(keypad) => { keypad.included_from = file_name; },
+            Part::Encoder(encoder) => { encoder.included_from = file_name; },
+            Part::Servo(servo) => { servo.included_from = file_name; },
+            Part::Stepper(stepper) => { stepper.included_from = file_name; },
+            Part::Dht(dht) => { dht.included_from = file_name; },
+            Part::LedBar(ledbar) => { ledbar.included_from = file_name; },
+            Part::LedMatrix(ledmatrix) => { ledmatrix.included_from = file_name; },
+        }
+    }
+    // Fields filled in from the defaults of the board
+    pub fn defaulted(&self) -> &[String] {
+        match self {
+            Part::Button(button) => &button.defaulted,
+            Part::Led(led) => &led.defaulted,
+            Part::SevenSeg(sevenseg) => &sevenseg.defaulted,
+            Part::Lcd(lcd) => &lcd.defaulted,
+            Part::RgbLed(rgbled) => &rgbled.defaulted,
+            Part::Potentiometer(pot) => &pot.defaulted,
+            Part::Keypad(keypad) => &keypad.defaulted,
+            Part::Encoder(encoder) => &encoder.defaulted,
+            Part::Servo(servo) => &servo.defaulted,
+            Part::Stepper(stepper) => &stepper.defaulted,
+            Part::Dht(dht) => &dht.defaulted,
+            Part::LedBar(ledbar) => &ledbar.defaulted,
+            Part::LedMatrix(ledmatrix) => &ledmatrix.defaulted,
+        }
+    }
+    pub fn set_defaulted(&mut self, keys: Vec<String>) {
+        match self {
+            Part::Button(button) => { button.defaulted = keys; },
+            Part::Led(led) => { led.defaulted = keys; },
+            Part::SevenSeg(sevenseg) => { sevenseg.defaulted = keys; },
+            Part::Lcd(lcd) => { lcd.defaulted = keys; },
+            Part::RgbLed(rgbled) => { rgbled.defaulted = keys; },
+            Part::Potentiometer(pot) => { pot.defaulted = keys; },
+            Part::Keypad(keypad) => { keypad.defaulted = keys; },
+            Part::Encoder(encoder) => { encoder.defaulted = keys; },
+            Part::Servo(servo) => { servo.defaulted = keys; },
+            Part::Stepper(stepper) => { stepper.defaulted = keys; },
+            Part::Dht(dht) => { dht.defaulted = keys; },
+            Part::LedBar(ledbar) => { ledbar.defaulted = keys; },
+            Part::LedMatrix(ledmatrix) => { ledmatrix.defaulted = keys; },
+        }
+    }
+    pub fn pos(&self) -> (u16, u16) {
+        match self {
+            Part::Button(button) => (button.pos_x, button.pos_y),
+            Part::Led(led) => (led.pos_x, led.pos_y),
+            Part::SevenSeg(sevenseg) => (sevenseg.pos_x, sevenseg.pos_y),
+            Part::Lcd(lcd) => (lcd.pos_x, lcd.pos_y),
+            Part::RgbLed(rgbled) => (rgbled.pos_x, rgbled.pos_y),
+            Part::Potentiometer(pot) => (pot.pos_x, pot.pos_y),
+            Part::Keypad(keypad) => (keypad.pos_x, keypad.pos_y),
+            Part::Encoder(encoder) => (encoder.pos_x, encoder.pos_y),
+            Part::Servo(servo) => (servo.pos_x, servo.pos_y),
+            Part::Stepper(stepper) => (stepper.pos_x, stepper.pos_y),
+            Part::Dht(dht) => (dht.pos_x, dht.pos_y),
+            Part::LedBar(ledbar) => (ledbar.pos_x, ledbar.pos_y),
+            Part::LedMatrix(ledmatrix) => (ledmatrix.pos_x, ledmatrix.pos_y),
+        }
+    }
+    pub fn set_pos(&mut self, x: u16, y: u16) {
+        match self {
+            Part::Button(button) => { button.pos_x = x; button.pos_y = y; },
+            Part::Led(led) => { led.pos_x = x; led.pos_y = y; },
+            Part::SevenSeg(sevenseg) => { sevenseg.pos_x = x; sevenseg.pos_y = y; },
+            Part::Lcd(lcd) => { lcd.pos_x = x; lcd.pos_y = y; },
+            Part::RgbLed(rgbled) => { rgbled.pos_x = x; rgbled.pos_y = y; },
+            Part::Potentiometer(pot) => { pot.pos_x = x; pot.pos_y = y; },
+            Part::Keypad(keypad) => { keypad.pos_x = x; keypad.pos_y = y; },
+            Part::Encoder(encoder) => { encoder.pos_x = x; encoder.pos_y = y; },
+            Part::Servo(servo) => { servo.pos_x = x; servo.pos_y = y; },
+            Part::Stepper(stepper) => { stepper.pos_x = x; stepper.pos_y = y; },
+            Part::Dht(dht) => { dht.pos_x = x; dht.pos_y = y; },
+            Part::LedBar(ledbar) => { ledbar.pos_x = x; ledbar.pos_y = y; },
+            Part::LedMatrix(ledmatrix) => { ledmatrix.pos_x = x; ledmatrix.pos_y = y; },
+        }
+    }
+    // Size of the part on the board (width, height)
+    pub fn size(&self) -> (u16, u16) {
+        match self {
+            Part::Button(_) => (Button::WIDTH, Button::HEIGHT),
+            Part::Led(_) => (Led::WIDTH, Led::HEIGHT),
+            Part::SevenSeg(sevenseg) => (sevenseg.width(), SevenSeg::HEIGHT),
+            Part::Lcd(lcd) => (lcd.width(), lcd.height()),
+            Part::RgbLed(_) => (RgbLed::WIDTH, RgbLed::HEIGHT),
+            Part::Potentiometer(_) => (Potentiometer::WIDTH, Potentiometer::HEIGHT),
+            Part::Keypad(keypad) => (keypad.width(), keypad.height()),
+            Part::Encoder(_) => (Encoder::WIDTH, Encoder::HEIGHT),
+            Part::Servo(_) => (Servo::WIDTH, Servo::HEIGHT),
+            Part::Stepper(_) => (Stepper::WIDTH, Stepper::HEIGHT),
+            Part::Dht(_) => (Dht::WIDTH, Dht::HEIGHT),
+            Part::LedBar(ledbar) => (ledbar.width(), LedBar::HEIGHT),
+            Part::LedMatrix(ledmatrix) => (ledmatrix.width(), ledmatrix.height()),
+        }
+    }
+    // Pins whose INPUT register bit is written by this part
+    pub fn driven_pins(&self) -> Vec<u8> {
+        match self {
+            Part::Button(button) => vec![button.pin],
+            Part::Led(_) => vec![],
+            Part::SevenSeg(_) => vec![],
+            Part::Lcd(_) => vec![],
+            Part::RgbLed(_) => vec![],
+            Part::Potentiometer(_) => vec![],
+            // Keys only link pins, their level is set by the pull resistors
+            Part::Keypad(_) => vec![],
+            Part::Encoder(encoder) => [encoder.pin_a, encoder.pin_b].iter().chain(encoder.pin_sw.iter()).cloned().collect(),
+            Part::Servo(_) => vec![],
+            Part::Stepper(_) => vec![],
+            // The sensor only pulls the line low while it responds, see pulled_pins()
+            Part::Dht(_) => vec![],
+            Part::LedBar(_) => vec![],
+            Part::LedMatrix(_) => vec![],
+        }
+    }
+    // Pins whose OUTPUT register bit is read by this part
+    pub fn sensed_pins(&self) -> Vec<u8> {
+        match self {
+            Part::Button(_) => vec![],
+            Part::Led(led) => vec![led.pin],
+            Part::SevenSeg(sevenseg) => {
+                sevenseg.segments.iter().chain(sevenseg.digits.iter()).cloned().collect()
+            },
+            Part::Lcd(lcd) => {
+                [lcd.rs, lcd.e].iter().chain(lcd.data.iter()).cloned().collect()
+            },
+            Part::RgbLed(rgbled) => vec![rgbled.red, rgbled.green, rgbled.blue],
+            Part::Potentiometer(_) => vec![],
+            Part::Keypad(keypad) => keypad.rows.iter().chain(keypad.cols.iter()).cloned().collect(),
+            Part::Encoder(_) => vec![],
+            Part::Servo(servo) => vec![servo.pin],
+            Part::Stepper(stepper) => stepper.pins.clone(),
+            Part::Dht(dht) => vec![dht.pin],
+            Part::LedBar(ledbar) => ledbar.pins.clone(),
+            Part::LedMatrix(ledmatrix) => ledmatrix.rows.iter().chain(ledmatrix.cols.iter()).cloned().collect(),
+        }
+    }
+    // Pins which are pulled up by a resistor of this part
+    pub fn pulled_pins(&self) -> Vec<u8> {
+        match self {
+            Part::Dht(dht) => vec![dht.pin],
+            _ => vec![],
+        }
+    }
 }
@@ -0,0 +1,184 @@
+/*!potentiometer.rs
+ * Hardware | Potentiometer definition. Its value is written to a channel of
+ * the simulated ADC, which programs read with analogRead().
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::board::Board;
+use super::model::{PositionModel, PotentiometerModel};
+use serde_json::{Map, Value as SerdeValue};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Rect};
+use tui::style::{Color, Style};
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
+use tui::Frame;
+use utils::adc::ADC_CHANNELS;
+use utils::gpioregs::RegMemory;
+
+#[derive(Clone)]
+pub struct Potentiometer {
+    pub channel: u8,
+    pub name: String,
+    pub hotkey_up: String,
+    pub hotkey_down: String,
+    // Position of the wiper in percent
+    pub value: u8,
+    pub step: u8,
+    pub color_off: Color,
+    pub color_on: Color,
+    pub pos_x: u16,
+    pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
+}
+
+impl Default for Potentiometer {
+    fn default() -> Self {
+        Self {
+            channel: 0,
+            name: String::from("POT $n"),
+            hotkey_up: String::from(""),
+            hotkey_down: String::from(""),
+            value: 50,
+            step: 5,
+            color_off: Color::DarkGray,
+            color_on: Color::LightBlue,
+            pos_x: 0,
+            pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
+            extra: Map::new(),
+        }
+    }
+}
+
+impl Potentiometer {
+    pub const WIDTH: u16 = 16;
+    pub const HEIGHT: u16 = 2;
+    pub const BAR_WIDTH: u16 = 10;
+    pub const MAX_CHANNEL: u8 = ADC_CHANNELS as u8 - 1;
+
+    pub fn from_model(model: PotentiometerModel) -> Self {
+        let mut pot = Self::default();
+        // Channels out of range are ignored
+        if model.channel <= Self::MAX_CHANNEL {
+            pot.channel = model.channel;
+        }
+        if let Some(name) = model.name {
+            pot.name = name;
+        }
+        if let Some(hotkey) = model.hotkey_up {
+            pot.hotkey_up = hotkey;
+        }
+        if let Some(hotkey) = model.hotkey_down {
+            pot.hotkey_down = hotkey;
+        }
+        if let Some(value) = model.value {
+            pot.value = value.min(100);
+        }
+        if let Some(step) = model.step {
+            pot.step = step.clamp(1, 100);
+        }
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            pot.color_off = c;
+        }
+        if let Some(c) = model.color_on.and_then(|c| super::helper_str_to_color(c).ok()) {
+            pot.color_on = c;
+        }
+        if let Some(position) = model.position {
+            pot.pos_x = position.x;
+            pot.pos_y = position.y;
+        }
+        pot.extra = model.extra;
+        pot
+    }
+    pub fn to_model(&self) -> PotentiometerModel {
+        PotentiometerModel {
+            name: Some(self.name.clone()),
+            channel: self.channel,
+            hotkey_up: Some(self.hotkey_up.clone()),
+            hotkey_down: Some(self.hotkey_down.clone()),
+            value: Some(self.value),
+            step: Some(self.step),
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            color_on: Some(super::helper_color_to_str(self.color_on)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
+        }
+    }
+    pub fn turn(&mut self, up: bool) {
+        self.value = if up {
+            self.value.saturating_add(self.step).min(100)
+        } else {
+            self.value.saturating_sub(self.step)
+        };
+    }
+    // Sets the value to the given cell of the bar (relative to the part)
+    pub fn click(&mut self, x: u16, y: u16) {
+        if y == 1 && x < Self::BAR_WIDTH {
+            self.value = (x * 100 / (Self::BAR_WIDTH - 1)) as u8;
+        }
+    }
+    pub fn sync(&mut self, reg_memory: &mut RegMemory) -> &mut Self {
+        reg_memory.adc.write_level(self.channel, self.value as f64 / 100.0);
+        self
+    }
+    pub fn render(
+        &self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>,
+        area: Rect, board: &Board
+    ) {
+        let pot_area = Rect {
+            x: area.x + self.pos_x + 1,
+            y: area.y + self.pos_y + 1,
+            width: Self::WIDTH,
+            height: Self::HEIGHT,
+        };
+        if !super::helper_is_rect_in_range(area, pot_area) {
+            return;
+        }
+        let style = Style::default().fg(board.foreground_color).bg(board.background_color);
+        let filled = ((self.value as u16 * Self::BAR_WIDTH + 50) / 100) as usize;
+        let pot_content = [
+            Text::styled(format!("{} [{}{}]\n", self.name, self.hotkey_down, self.hotkey_up), style),
+            Text::styled(" ".repeat(filled), Style::default().bg(self.color_on)),
+            Text::styled(" ".repeat(Self::BAR_WIDTH as usize - filled), Style::default().bg(self.color_off)),
+            Text::styled(format!(" {:>3}%", self.value), style),
+        ];
+        Paragraph::new(pot_content.iter())
+            .block(Block::default()
+                .borders(Borders::NONE)
+            )
+            .style(Style::default().bg(board.background_color))
+            .render(f, pot_area);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn Potentiometer__sync() {
+        let mut pot = Potentiometer::default();
+        pot.channel = 3;
+        pot.step = 30;
+        pot.turn(true);
+        pot.turn(true);
+        assert_eq!(pot.value, 100);
+        pot.turn(false);
+        let mut reg_memory = RegMemory::new();
+        pot.sync(&mut reg_memory);
+        assert_eq!(reg_memory.adc.read_bits(3, 10), 716);
+        pot.click(Potentiometer::BAR_WIDTH - 1, 1);
+        assert_eq!(pot.value, 100);
+        pot.click(0, 1);
+        assert_eq!(pot.value, 0);
+    }
+}
@@ -0,0 +1,170 @@
+/*!rgbled.rs
+ * Hardware | RGB led definition. The brightness of each channel follows the
+ * PWM duty cycle of its pin, or the OUTPUT register for pins without PWM.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::board::Board;
+use super::led::Led;
+use super::model::{PositionModel, RgbLedModel};
+use serde_json::{Map, Value as SerdeValue};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Rect};
+use tui::style::{Color, Style};
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
+use tui::Frame;
+use utils::gpioregs::RegMemory;
+
+#[derive(Clone)]
+pub struct RgbLed {
+    pub red: u8,
+    pub green: u8,
+    pub blue: u8,
+    // Common anode: the channels are active low.
+    // Common cathode: the channels are active high.
+    pub common_anode: bool,
+    pub name: String,
+    pub color_off: Color,
+    pub pos_x: u16,
+    pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
+    // Brightness (0.0 - 1.0) of the red, green and blue channel
+    state: [f64; 3],
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
+}
+
+impl Default for RgbLed {
+    fn default() -> Self {
+        Self {
+            red: 0,
+            green: 0,
+            blue: 0,
+            common_anode: false,
+            name: String::from("RGB $n"),
+            color_off: Color::Black,
+            pos_x: 0,
+            pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
+            state: [0.0; 3],
+            extra: Map::new(),
+        }
+    }
+}
+
+impl RgbLed {
+    pub const WIDTH: u16 = Led::WIDTH;
+    pub const HEIGHT: u16 = Led::HEIGHT;
+
+    pub fn from_model(model: RgbLedModel) -> Self {
+        let mut rgbled = Self::default();
+        // Pins out of range are ignored
+        if model.red < 32 {
+            rgbled.red = model.red;
+        }
+        if model.green < 32 {
+            rgbled.green = model.green;
+        }
+        if model.blue < 32 {
+            rgbled.blue = model.blue;
+        }
+        rgbled.common_anode = model.common.as_deref() == Some("anode");
+        if let Some(name) = model.name {
+            rgbled.name = name;
+        }
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            rgbled.color_off = c;
+        }
+        if let Some(position) = model.position {
+            rgbled.pos_x = position.x;
+            rgbled.pos_y = position.y;
+        }
+        rgbled.extra = model.extra;
+        rgbled
+    }
+    pub fn to_model(&self) -> RgbLedModel {
+        RgbLedModel {
+            name: Some(self.name.clone()),
+            red: self.red,
+            green: self.green,
+            blue: self.blue,
+            common: Some(String::from(if self.common_anode { "anode" } else { "cathode" })),
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
+        }
+    }
+    // Mixed color of the channels, None if all channels are off
+    pub fn color(&self) -> Option<Color> {
+        if self.state.iter().all(|&level| level <= 0.0) {
+            return None;
+        }
+        let c = |level: f64| (level * 255.0).round() as u8;
+        Some(super::helper_rgb_to_color(c(self.state[0]), c(self.state[1]), c(self.state[2])))
+    }
+    pub fn sync(&mut self, reg_memory: &RegMemory) -> &mut Self {
+        for (i, &pin) in [self.red, self.green, self.blue].iter().enumerate() {
+            let level = reg_memory.pwm.duty(pin)
+                .unwrap_or(reg_memory.output.read_pin(pin) as f64);
+            self.state[i] = if self.common_anode { 1.0 - level } else { level };
+        }
+        self
+    }
+    pub fn render(
+        &self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>,
+        area: Rect, board: &Board
+    ) {
+        let rgbled_area = Rect {
+            x: area.x + self.pos_x + 1,
+            y: area.y + self.pos_y + 1,
+            width: Self::WIDTH,
+            height: Self::HEIGHT,
+        };
+        if !super::helper_is_rect_in_range(area, rgbled_area) {
+            return;
+        }
+        let rgbled_content = [
+            Text::styled("  ", Style::default().bg(self.color().unwrap_or(self.color_off))),
+            Text::raw(" "),
+            Text::styled(self.name.clone(), Style::default()
+                .fg(board.foreground_color)
+                .bg(board.background_color)
+            ),
+        ];
+        Paragraph::new(rgbled_content.iter())
+            .block(Block::default()
+                .borders(Borders::NONE)
+            )
+            .style(Style::default().bg(board.background_color))
+            .render(f, rgbled_area);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn RgbLed__sync() {
+        let mut rgbled = RgbLed::default();
+        rgbled.red = 17;
+        rgbled.green = 18;
+        rgbled.blue = 19;
+        let mut reg_memory = RegMemory::new();
+        reg_memory.output.write_pin(17, 1);
+        reg_memory.pwm.enable(18, 100);
+        reg_memory.pwm.write(18, 40);
+        rgbled.sync(&reg_memory);
+        assert_eq!(rgbled.state, [1.0, 0.4, 0.0]);
+        rgbled.common_anode = true;
+        rgbled.sync(&reg_memory);
+        assert_eq!(rgbled.state, [0.0, 0.6, 1.0]);
+    }
+}
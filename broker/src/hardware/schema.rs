@@ -0,0 +1,262 @@
+/*!schema.rs
+ * Hardware | Embedded JSON schemas of the board format and a validator for
+ * the subset of JSON Schema (draft-07) they use.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::erc::Severity;
+use super::validate::{self, Issue};
+use serde_json::{Map, Value as SerdeValue};
+use std::io::Error;
+
+// (name, content) of all embedded schemas
+pub const SCHEMAS: [(&str, &str); 14] = [
+    ("board", include_str!("../../../docs/schemas/board.json")),
+    ("led", include_str!("../../../docs/schemas/led.json")),
+    ("button", include_str!("../../../docs/schemas/button.json")),
+    ("sevenseg", include_str!("../../../docs/schemas/sevenseg.json")),
+    ("lcd_hd44780", include_str!("../../../docs/schemas/lcd_hd44780.json")),
+    ("rgbled", include_str!("../../../docs/schemas/rgbled.json")),
+    ("potentiometer", include_str!("../../../docs/schemas/potentiometer.json")),
+    ("keypad", include_str!("../../../docs/schemas/keypad.json")),
+    ("encoder", include_str!("../../../docs/schemas/encoder.json")),
+    ("servo", include_str!("../../../docs/schemas/servo.json")),
+    ("stepper", include_str!("../../../docs/schemas/stepper.json")),
+    ("dht", include_str!("../../../docs/schemas/dht.json")),
+    ("ledbar", include_str!("../../../docs/schemas/ledbar.json")),
+    ("ledmatrix", include_str!("../../../docs/schemas/ledmatrix.json")),
+];
+
+pub fn get_schema(name: &str) -> Option<&'static str> {
+    SCHEMAS.iter().find(|(n, _)| *n == name).map(|(_, s)| *s)
+}
+
+struct SchemaValidator {
+    // Parsed schemas, referenced by their $id
+    schemas: Vec<SerdeValue>,
+}
+
+impl SchemaValidator {
+    fn new() -> Self {
+        Self {
+            schemas: SCHEMAS.iter()
+                .filter_map(|(_, s)| serde_json::from_str(s).ok())
+                .collect(),
+        }
+    }
+    fn resolve(&self, reference: &str) -> Option<&SerdeValue> {
+        self.schemas.iter().find(|s| s.get("$id").and_then(|id| id.as_str()) == Some(reference))
+    }
+    fn join(path: &str, key: &str) -> String {
+        if path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    }
+    fn error(issues: &mut Vec<Issue>, path: &str, message: String) {
+        issues.push(Issue { severity: Severity::Error, path: path.to_owned(), message });
+    }
+    fn has_type(value: &SerdeValue, t: &str) -> bool {
+        match t {
+            "object" => value.is_object(),
+            "array" => value.is_array(),
+            "string" => value.is_string(),
+            "number" => value.is_number(),
+            "integer" => value.is_i64() || value.is_u64(),
+            "boolean" => value.is_boolean(),
+            "null" => value.is_null(),
+            _ => true,
+        }
+    }
+    fn validate(&self, schema: &SerdeValue, value: &SerdeValue, path: &str, issues: &mut Vec<Issue>) {
+        let schema = match schema.as_object() {
+            Some(schema) => schema,
+            None => return,
+        };
+        if let Some(reference) = schema.get("$ref").and_then(|r| r.as_str()) {
+            match self.resolve(reference) {
+                Some(s) => self.validate(s, value, path, issues),
+                None => Self::error(issues, path, format!("unknown schema reference '{}'", reference)),
+            }
+            return;
+        }
+        if let Some(t) = schema.get("type") {
+            let types: Vec<&str> = match t {
+                SerdeValue::String(t) => vec![t.as_str()],
+                SerdeValue::Array(ts) => ts.iter().filter_map(|t| t.as_str()).collect(),
+                _ => vec![],
+            };
+            if !types.is_empty() && !types.iter().any(|t| Self::has_type(value, t)) {
+                Self::error(issues, path, format!("expected {}, found {}", types.join(" or "), value));
+                return;
+            }
+        }
+        if let Some(c) = schema.get("const") {
+            if c != value {
+                Self::error(issues, path, format!("expected {}, found {}", c, value));
+            }
+        }
+        if let Some(SerdeValue::Array(options)) = schema.get("enum") {
+            if !options.contains(value) {
+                Self::error(issues, path, format!("{} is not one of the allowed values", value));
+            }
+        }
+        if let Some(n) = value.as_f64() {
+            if let Some(min) = schema.get("minimum").and_then(|m| m.as_f64()) {
+                if n < min {
+                    Self::error(issues, path, format!("{} is less than the minimum of {}", value, min));
+                }
+            }
+            if let Some(max) = schema.get("maximum").and_then(|m| m.as_f64()) {
+                if n > max {
+                    Self::error(issues, path, format!("{} is greater than the maximum of {}", value, max));
+                }
+            }
+        }
+        if let Some(s) = value.as_str() {
+            let len = s.chars().count() as u64;
+            if let Some(min) = schema.get("minLength").and_then(|m| m.as_u64()) {
+                if len < min {
+                    Self::error(issues, path, format!("must be at least {} character(s) long", min));
+                }
+            }
+            if let Some(max) = schema.get("maxLength").and_then(|m| m.as_u64()) {
+                if len > max {
+                    Self::error(issues, path, format!("must be at most {} character(s) long", max));
+                }
+            }
+        }
+        if let Some(map) = value.as_object() {
+            self.validate_object(schema, map, path, issues);
+        }
+        if let Some(values) = value.as_array() {
+            let len = values.len() as u64;
+            if let Some(min) = schema.get("minItems").and_then(|m| m.as_u64()) {
+                if len < min {
+                    Self::error(issues, path, format!("must have at least {} item(s)", min));
+                }
+            }
+            if let Some(max) = schema.get("maxItems").and_then(|m| m.as_u64()) {
+                if len > max {
+                    Self::error(issues, path, format!("must have at most {} item(s)", max));
+                }
+            }
+        }
+        if let (Some(items), Some(values)) = (schema.get("items"), value.as_array()) {
+            for (i, v) in values.iter().enumerate() {
+                self.validate(items, v, &format!("{}[{}]", path, i), issues);
+            }
+        }
+        if let Some(SerdeValue::Array(branches)) = schema.get("anyOf") {
+            // Report the issues of the branch which matches best, preferring
+            // branches whose const properties (e.g. "type") match
+            let mut best: Option<((bool, usize), Vec<Issue>)> = None;
+            for branch in branches.iter() {
+                let mut branch_issues = vec![];
+                self.validate(branch, value, path, &mut branch_issues);
+                if branch_issues.is_empty() {
+                    return;
+                }
+                let score = (self.has_const_mismatch(branch, value), branch_issues.len());
+                if best.as_ref().map_or(true, |(b, _)| score < *b) {
+                    best = Some((score, branch_issues));
+                }
+            }
+            issues.extend(best.map(|(_, i)| i).unwrap_or_default());
+        }
+    }
+    fn has_const_mismatch(&self, schema: &SerdeValue, value: &SerdeValue) -> bool {
+        let schema = match schema.get("$ref").and_then(|r| r.as_str()) {
+            Some(reference) => match self.resolve(reference) {
+                Some(s) => s,
+                None => return true,
+            },
+            None => schema,
+        };
+        match schema.get("properties").and_then(|p| p.as_object()) {
+            Some(properties) => properties.iter().any(|(key, s)| match s.get("const") {
+                Some(c) => value.get(key).map_or(false, |v| v != c),
+                None => false,
+            }),
+            None => false,
+        }
+    }
+    fn validate_object(
+        &self, schema: &Map<String, SerdeValue>, map: &Map<String, SerdeValue>, path: &str,
+        issues: &mut Vec<Issue>
+    ) {
+        if let Some(SerdeValue::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(|k| k.as_str()) {
+                if !map.contains_key(key) {
+                    Self::error(issues, &Self::join(path, key), String::from("missing required field"));
+                }
+            }
+        }
+        let properties = schema.get("properties").and_then(|p| p.as_object());
+        for (key, v) in map.iter() {
+            let p = Self::join(path, key);
+            match properties.and_then(|props| props.get(key)) {
+                Some(s) => self.validate(s, v, &p, issues),
+                None => match schema.get("additionalProperties") {
+                    Some(SerdeValue::Bool(false)) => {
+                        Self::error(issues, &p, String::from("additional field is not allowed"));
+                    },
+                    Some(s) if s.is_object() => self.validate(s, v, &p, issues),
+                    _ => {},
+                },
+            }
+        }
+    }
+}
+
+// Validates a board against the embedded board schema.
+pub fn check_board(json: &SerdeValue) -> Vec<Issue> {
+    let validator = SchemaValidator::new();
+    let mut issues = vec![];
+    if let Some(schema) = validator.resolve("https://patrickgold.dev/simpi/schemas/board.json") {
+        validator.validate(schema, json, "", &mut issues);
+    }
+    issues
+}
+
+// Reads a board file and validates it against the embedded board schema.
+pub fn check_board_file(file_name: &str) -> Result<Vec<Issue>, Error> {
+    Ok(check_board(&validate::read_board_json(file_name)?))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn check_board__valid() {
+        let json: SerdeValue = serde_json::from_str(
+            include_str!("../../config/gpiotest-board.json")
+        ).unwrap();
+        assert!(check_board(&json).is_empty());
+    }
+
+    #[test]
+    fn check_board__invalid() {
+        let json = json!({
+            "type": "simpi/broker",
+            "size": { "width": 0 },
+            "hardware": [
+                { "type": "simpi/led", "pin": 40 },
+                { "type": "simpi/button", "pin": 3 },
+            ],
+        });
+        let issues: Vec<String> = check_board(&json).iter().map(|i| i.to_string()).collect();
+        assert_eq!(issues, vec![
+            "ERROR   type: expected \"simpi/board\", found \"simpi/broker\"",
+            "ERROR   size.width: 0 is less than the minimum of 1",
+            "ERROR   hardware[0].pin: 40 is greater than the maximum of 31",
+            "ERROR   hardware[1].hotkey: missing required field",
+        ]);
+    }
+}
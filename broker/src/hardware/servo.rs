@@ -0,0 +1,209 @@
+/*!servo.rs
+ * Hardware | Servo definition. The angle is decoded from the length of the
+ * PWM pulses on its pin.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::board::Board;
+use super::model::{PositionModel, ServoModel};
+use serde_json::{Map, Value as SerdeValue};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Rect};
+use tui::style::{Color, Style};
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
+use tui::Frame;
+use utils::gpioregs::RegMemory;
+
+#[derive(Clone)]
+pub struct Servo {
+    pub pin: u8,
+    pub name: String,
+    // Pulse lengths (in us) of the two end positions
+    pub min_pulse_us: u32,
+    pub max_pulse_us: u32,
+    // Angle of the end position of the longest pulse
+    pub max_angle: u32,
+    pub color_off: Color,
+    pub color_on: Color,
+    pub pos_x: u16,
+    pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
+    // None until the first pulse, the servo keeps its angle without pulses
+    angle: Option<f64>,
+    // Whether the last pulse has been outside of the pulse range
+    out_of_range: bool,
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
+}
+
+impl Default for Servo {
+    fn default() -> Self {
+        Self {
+            pin: 18,
+            name: String::from("SERVO $n"),
+            min_pulse_us: 1000,
+            max_pulse_us: 2000,
+            max_angle: 180,
+            color_off: Color::DarkGray,
+            color_on: Color::LightCyan,
+            pos_x: 0,
+            pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
+            angle: None,
+            out_of_range: false,
+            extra: Map::new(),
+        }
+    }
+}
+
+impl Servo {
+    pub const WIDTH: u16 = 16;
+    pub const HEIGHT: u16 = 2;
+    pub const GAUGE_WIDTH: u16 = 10;
+    pub const MIN_PULSE_US: u32 = 100;
+    pub const MAX_PULSE_US: u32 = 3000;
+    pub const MAX_ANGLE: u32 = 360;
+
+    pub fn from_model(model: ServoModel) -> Self {
+        let mut servo = Self::default();
+        // Pins out of range are ignored
+        if model.pin < 32 {
+            servo.pin = model.pin;
+        }
+        if let Some(name) = model.name {
+            servo.name = name;
+        }
+        if let Some(pulse) = model.min_pulse {
+            servo.min_pulse_us = pulse.clamp(Self::MIN_PULSE_US, Self::MAX_PULSE_US);
+        }
+        if let Some(pulse) = model.max_pulse {
+            servo.max_pulse_us = pulse.clamp(Self::MIN_PULSE_US, Self::MAX_PULSE_US);
+        }
+        if let Some(angle) = model.max_angle {
+            servo.max_angle = angle.clamp(1, Self::MAX_ANGLE);
+        }
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            servo.color_off = c;
+        }
+        if let Some(c) = model.color_on.and_then(|c| super::helper_str_to_color(c).ok()) {
+            servo.color_on = c;
+        }
+        if let Some(position) = model.position {
+            servo.pos_x = position.x;
+            servo.pos_y = position.y;
+        }
+        servo.extra = model.extra;
+        servo
+    }
+    pub fn to_model(&self) -> ServoModel {
+        ServoModel {
+            pin: self.pin,
+            name: Some(self.name.clone()),
+            min_pulse: Some(self.min_pulse_us),
+            max_pulse: Some(self.max_pulse_us),
+            max_angle: Some(self.max_angle),
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            color_on: Some(super::helper_color_to_str(self.color_on)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
+        }
+    }
+    // Angle of the given pulse length, pulses outside of the pulse range
+    // move the servo to the nearest end position.
+    pub fn angle_of(&self, pulse_us: f64) -> f64 {
+        let min = self.min_pulse_us.min(self.max_pulse_us) as f64;
+        let max = self.max_pulse_us.max(self.min_pulse_us) as f64;
+        if max <= min {
+            return 0.0;
+        }
+        (pulse_us.max(min).min(max) - min) / (max - min) * self.max_angle as f64
+    }
+    pub fn sync(&mut self, reg_memory: &RegMemory) -> &mut Self {
+        match reg_memory.pwm.pulse_us(self.pin) {
+            Some(pulse_us) if pulse_us > 0.0 => {
+                self.angle = Some(self.angle_of(pulse_us));
+                self.out_of_range = pulse_us < self.min_pulse_us.min(self.max_pulse_us) as f64
+                    || pulse_us > self.max_pulse_us.max(self.min_pulse_us) as f64;
+            },
+            _ => {},
+        }
+        self
+    }
+    pub fn render(
+        &self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>,
+        area: Rect, board: &Board
+    ) {
+        let servo_area = Rect {
+            x: area.x + self.pos_x + 1,
+            y: area.y + self.pos_y + 1,
+            width: Self::WIDTH,
+            height: Self::HEIGHT,
+        };
+        if !super::helper_is_rect_in_range(area, servo_area) {
+            return;
+        }
+        let style = Style::default().fg(board.foreground_color).bg(board.background_color);
+        let mut servo_content = vec![
+            Text::styled(format!("{}\n", self.name), style),
+        ];
+        match self.angle {
+            Some(angle) => {
+                let needle = (angle / self.max_angle as f64 * (Self::GAUGE_WIDTH - 1) as f64).round() as u16;
+                for i in 0..Self::GAUGE_WIDTH {
+                    let color = if i == needle { self.color_on } else { self.color_off };
+                    servo_content.push(Text::styled(" ", Style::default().bg(color)));
+                }
+                // Pulses outside of the pulse range are flagged
+                let angle_style = if self.out_of_range { style.fg(Color::LightRed) } else { style };
+                servo_content.push(Text::styled(format!(" {:>3}°", angle.round()), angle_style));
+            },
+            None => {
+                servo_content.push(Text::styled(
+                    " ".repeat(Self::GAUGE_WIDTH as usize), Style::default().bg(self.color_off)
+                ));
+                servo_content.push(Text::styled("  --°", style));
+            },
+        }
+        Paragraph::new(servo_content.iter())
+            .block(Block::default()
+                .borders(Borders::NONE)
+            )
+            .style(Style::default().bg(board.background_color))
+            .render(f, servo_area);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn Servo__sync() {
+        let mut servo = Servo::default();
+        let mut reg_memory = RegMemory::new();
+        servo.sync(&reg_memory);
+        assert_eq!(servo.angle, None);
+        // 50 Hz with 10 us steps, as set up by pwmSetClock(192) and
+        // pwmSetRange(2000)
+        reg_memory.pwm.enable(servo.pin, 2000);
+        reg_memory.pwm.set_period_ns(servo.pin, 20_000_000);
+        reg_memory.pwm.write(servo.pin, 150);
+        servo.sync(&reg_memory);
+        assert_eq!(servo.angle, Some(90.0));
+        reg_memory.pwm.write(servo.pin, 250);
+        servo.sync(&reg_memory);
+        assert_eq!(servo.angle, Some(180.0));
+        assert!(servo.out_of_range);
+        // Without pulses the servo keeps its angle
+        reg_memory.pwm.write(servo.pin, 0);
+        servo.sync(&reg_memory);
+        assert_eq!(servo.angle, Some(180.0));
+    }
+}
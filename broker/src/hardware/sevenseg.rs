@@ -0,0 +1,250 @@
+/*!sevenseg.rs
+ * Hardware | Seven-segment display definition. Multiplexed displays are
+ * decoded from the OUTPUT log, so digits which are only lit for a fraction
+ * of the time are shown like the human eye would see them.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::board::Board;
+use super::model::{PositionModel, SevenSegModel};
+use serde_json::{Map, Value as SerdeValue};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Rect};
+use tui::style::{Color, Style};
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
+use tui::Frame;
+use utils::gpioregs::RegMemory;
+use utils::outlog::OutputWindow;
+
+// Width of a digit including the decimal point
+const DIGIT_WIDTH: u16 = 5;
+const DIGIT_GAP: u16 = 1;
+
+#[derive(Clone)]
+pub struct SevenSeg {
+    // Pins of the segments a, b, c, d, e, f, g and optionally dp
+    pub segments: Vec<u8>,
+    // Digit select pins, from left to right. Empty for a single digit
+    // which is always selected.
+    pub digits: Vec<u8>,
+    // Common anode: segments are active low, digit selects active high.
+    // Common cathode: segments are active high, digit selects active low.
+    pub common_anode: bool,
+    pub name: String,
+    pub color_off: Color,
+    pub color_on: Color,
+    pub pos_x: u16,
+    pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
+    // Lit segments of each digit, bit 0 = a ... bit 7 = dp
+    state: Vec<u8>,
+    // Time window of the OUTPUT log since the last sync
+    window: OutputWindow,
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
+}
+
+impl Default for SevenSeg {
+    fn default() -> Self {
+        Self {
+            segments: vec![2, 3, 4, 5, 6, 7, 8, 9],
+            digits: vec![],
+            common_anode: false,
+            name: String::from("7SEG $n"),
+            color_off: Color::Black,
+            color_on: Color::LightRed,
+            pos_x: 0,
+            pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
+            state: vec![],
+            window: OutputWindow::new(),
+            extra: Map::new(),
+        }
+    }
+}
+
+impl SevenSeg {
+    pub const HEIGHT: u16 = 4;
+
+    pub fn from_model(model: SevenSegModel) -> Self {
+        let mut sevenseg = Self::default();
+        // Pins out of range are ignored
+        if !model.segments.is_empty() {
+            sevenseg.segments = model.segments.into_iter().filter(|&pin| pin < 32).take(8).collect();
+        }
+        sevenseg.digits = model.digits.into_iter().filter(|&pin| pin < 32).collect();
+        sevenseg.common_anode = model.common.as_deref() == Some("anode");
+        if let Some(name) = model.name {
+            sevenseg.name = name;
+        }
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            sevenseg.color_off = c;
+        }
+        if let Some(c) = model.color_on.and_then(|c| super::helper_str_to_color(c).ok()) {
+            sevenseg.color_on = c;
+        }
+        if let Some(position) = model.position {
+            sevenseg.pos_x = position.x;
+            sevenseg.pos_y = position.y;
+        }
+        sevenseg.extra = model.extra;
+        sevenseg
+    }
+    pub fn to_model(&self) -> SevenSegModel {
+        SevenSegModel {
+            name: Some(self.name.clone()),
+            segments: self.segments.clone(),
+            digits: self.digits.clone(),
+            common: Some(String::from(if self.common_anode { "anode" } else { "cathode" })),
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            color_on: Some(super::helper_color_to_str(self.color_on)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
+        }
+    }
+    pub fn digit_count(&self) -> usize {
+        std::cmp::max(self.digits.len(), 1)
+    }
+    pub fn width(&self) -> u16 {
+        let digits_width = self.digit_count() as u16 * (DIGIT_WIDTH + DIGIT_GAP) - DIGIT_GAP;
+        std::cmp::max(digits_width, self.name.chars().count() as u16)
+    }
+    pub fn get(&self, digit: usize) -> u8 {
+        self.state.get(digit).cloned().unwrap_or(0)
+    }
+    // Active segments for the given OUTPUT register value
+    fn segment_mask(&self, output: u32) -> u8 {
+        let mut mask = 0;
+        for (i, &pin) in self.segments.iter().enumerate() {
+            if (((output >> pin) & 0x1) != 0) != self.common_anode {
+                mask |= 0x1 << i;
+            }
+        }
+        mask
+    }
+    fn is_selected(&self, output: u32, digit: usize) -> bool {
+        match self.digits.get(digit) {
+            Some(&pin) => (((output >> pin) & 0x1) != 0) == self.common_anode,
+            None => self.digits.is_empty(),
+        }
+    }
+    // Computes the lit segments of each digit from the OUTPUT register values
+    // of a time window. A segment is lit if it has been active for at least
+    // half of the time its digit has been selected.
+    fn decode(&self, segments: &[(u32, u64)]) -> Vec<u8> {
+        let n = self.digit_count();
+        let mut selected_us = vec![0u64; n];
+        let mut lit_us = vec![[0u64; 8]; n];
+        for &(output, duration) in segments.iter() {
+            let mask = self.segment_mask(output);
+            for d in 0..n {
+                if self.is_selected(output, d) {
+                    selected_us[d] += duration;
+                    for s in 0..8 {
+                        if (mask >> s) & 0x1 != 0 {
+                            lit_us[d][s] += duration;
+                        }
+                    }
+                }
+            }
+        }
+        (0..n).map(|d| {
+            let mut mask = 0;
+            for s in 0..8 {
+                if selected_us[d] > 0 && lit_us[d][s] * 2 >= selected_us[d] {
+                    mask |= 0x1 << s;
+                }
+            }
+            mask
+        }).collect()
+    }
+    pub fn sync(&mut self, reg_memory: &RegMemory) -> &mut Self {
+        let segments = self.window.read(&reg_memory.output_log, reg_memory.output.read());
+        self.state = self.decode(&segments);
+        self
+    }
+    pub fn render(
+        &self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>,
+        area: Rect, board: &Board
+    ) {
+        let sevenseg_area = Rect {
+            x: area.x + self.pos_x + 1,
+            y: area.y + self.pos_y + 1,
+            width: self.width(),
+            height: Self::HEIGHT,
+        };
+        if !super::helper_is_rect_in_range(area, sevenseg_area) {
+            return;
+        }
+        let style = |lit: bool| Style::default()
+            .fg(if lit { self.color_on } else { self.color_off })
+            .bg(board.background_color);
+        let blank = Style::default().bg(board.background_color);
+        let gap = " ".repeat(DIGIT_GAP as usize);
+        let mut content = vec![];
+        // Segments (a..dp) of each character, row by row:
+        //  ▄▄      a
+        // █▄▄█    f g b
+        // █▄▄█▄   e d c dp
+        let rows: [[(&str, Option<usize>); 4]; 3] = [
+            [(" ", None), ("▄▄", Some(0)), (" ", None), (" ", None)],
+            [("█", Some(5)), ("▄▄", Some(6)), ("█", Some(1)), (" ", None)],
+            [("█", Some(4)), ("▄▄", Some(3)), ("█", Some(2)), ("▄", Some(7))],
+        ];
+        for row in rows.iter() {
+            for d in 0..self.digit_count() {
+                if d > 0 {
+                    content.push(Text::styled(gap.clone(), blank));
+                }
+                let mask = self.get(d);
+                for (chars, segment) in row.iter() {
+                    content.push(match segment {
+                        // Without dp pin there is no dp to show
+                        Some(s) if *s < self.segments.len() => {
+                            Text::styled(*chars, style((mask >> s) & 0x1 != 0))
+                        },
+                        _ => Text::styled(" ".repeat(chars.chars().count()), blank),
+                    });
+                }
+            }
+            content.push(Text::raw("\n"));
+        }
+        content.push(Text::styled(self.name.clone(), Style::default()
+            .fg(board.foreground_color)
+            .bg(board.background_color)
+        ));
+        Paragraph::new(content.iter())
+            .block(Block::default()
+                .borders(Borders::NONE)
+            )
+            .style(Style::default().bg(board.background_color))
+            .render(f, sevenseg_area);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn SevenSeg__decode() {
+        let mut sevenseg = SevenSeg::default();
+        sevenseg.segments = vec![0, 1, 2, 3, 4, 5, 6];
+        assert_eq!(sevenseg.decode(&[(0b0000110, 1)]), vec![0b0000110]);
+        sevenseg.common_anode = true;
+        assert_eq!(sevenseg.decode(&[(!0b0000110, 1)]), vec![0b0000110]);
+        // Two digits multiplexed every 5 ms: "1" on the left, "7" on the right
+        sevenseg.common_anode = false;
+        sevenseg.digits = vec![10, 11];
+        let one = 0b0000110 | (0x1 << 11);
+        let seven = 0b0000111 | (0x1 << 10);
+        assert_eq!(sevenseg.decode(&[(one, 5000), (seven, 5000)].repeat(5)), vec![0b0000110, 0b0000111]);
+    }
+}
@@ -0,0 +1,246 @@
+/*!stepper.rs
+ * Hardware | Stepper motor definition. The phase patterns of the four coil
+ * pins are decoded from the OUTPUT log, so fast step sequences are counted
+ * completely.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::board::Board;
+use super::model::{PositionModel, StepperModel};
+use serde_json::{Map, Value as SerdeValue};
+use tui::backend::CrosstermBackend;
+use tui::layout::{Rect};
+use tui::style::{Color, Style};
+use tui::widgets::{Block, Borders, Paragraph, Text, Widget};
+use tui::Frame;
+use utils::gpioregs::RegMemory;
+use utils::outlog::unix_time_us;
+
+// Coil patterns (bit 0 = first pin) of the half-step sequence. Full steps
+// use every second pattern, with one (wave drive) or two coils energized.
+const HALF_STEPS: [u8; 8] = [0b0001, 0b0011, 0b0010, 0b0110, 0b0100, 0b1100, 0b1000, 0b1001];
+// Patterns which last shorter than this (in us) are ignored, they occur
+// while the pins of a step are written one after another
+const GLITCH_US: u64 = 100;
+
+#[derive(Clone)]
+pub struct Stepper {
+    // Pins of the coils in the order of the phase sequence (IN1-IN4)
+    pub pins: Vec<u8>,
+    pub name: String,
+    pub color_off: Color,
+    pub color_on: Color,
+    pub pos_x: u16,
+    pub pos_y: u16,
+    // File the part has been included from, None for parts of the board itself
+    pub included_from: Option<String>,
+    // Fields set by the board defaults, written back only if changed
+    pub defaulted: Vec<String>,
+    // Position in half steps, forward steps count up
+    position: i64,
+    // 1 if the last step has been forward, -1 if backward, 0 before the first
+    direction: i8,
+    // Number of patterns which are not part of the sequence or skip steps
+    invalid: u32,
+    // Applied coil pattern and its index in the half-step sequence, None
+    // after an invalid pattern
+    coils: u8,
+    phase: Option<usize>,
+    // Pattern which has not been applied yet and the time it has been seen
+    pending: Option<(u64, u8)>,
+    log_seq: u64,
+    // Fields unknown to this version, kept for writing the board back
+    extra: Map<String, SerdeValue>,
+}
+
+impl Default for Stepper {
+    fn default() -> Self {
+        Self {
+            pins: vec![17, 18, 27, 22],
+            name: String::from("STEPPER $n"),
+            color_off: Color::DarkGray,
+            color_on: Color::LightGreen,
+            pos_x: 0,
+            pos_y: 0,
+            included_from: None,
+            defaulted: vec![],
+            position: 0,
+            direction: 0,
+            invalid: 0,
+            coils: 0,
+            phase: None,
+            pending: None,
+            log_seq: 0,
+            extra: Map::new(),
+        }
+    }
+}
+
+impl Stepper {
+    pub const WIDTH: u16 = 16;
+    pub const HEIGHT: u16 = 2;
+    pub const PIN_COUNT: usize = 4;
+
+    pub fn from_model(model: StepperModel) -> Self {
+        let mut stepper = Self::default();
+        // Pins out of range are ignored
+        if model.pins.len() == Self::PIN_COUNT && model.pins.iter().all(|&p| p < 32) {
+            stepper.pins = model.pins;
+        }
+        if let Some(name) = model.name {
+            stepper.name = name;
+        }
+        if let Some(c) = model.color_off.and_then(|c| super::helper_str_to_color(c).ok()) {
+            stepper.color_off = c;
+        }
+        if let Some(c) = model.color_on.and_then(|c| super::helper_str_to_color(c).ok()) {
+            stepper.color_on = c;
+        }
+        if let Some(position) = model.position {
+            stepper.pos_x = position.x;
+            stepper.pos_y = position.y;
+        }
+        stepper.extra = model.extra;
+        stepper
+    }
+    pub fn to_model(&self) -> StepperModel {
+        StepperModel {
+            name: Some(self.name.clone()),
+            pins: self.pins.clone(),
+            color_off: Some(super::helper_color_to_str(self.color_off)),
+            color_on: Some(super::helper_color_to_str(self.color_on)),
+            position: Some(PositionModel { x: self.pos_x, y: self.pos_y }),
+            extra: self.extra.clone(),
+        }
+    }
+    // Coil pattern of the given OUTPUT register value
+    fn pattern(&self, output: u32) -> u8 {
+        self.pins.iter().enumerate()
+            .fold(0, |p, (i, &pin)| p | ((((output >> pin) & 0x1) as u8) << i))
+    }
+    fn apply(&mut self, pattern: u8) {
+        self.coils = pattern;
+        // Released coils do not move the motor
+        if pattern == 0 {
+            return;
+        }
+        let index = match HALF_STEPS.iter().position(|&p| p == pattern) {
+            Some(index) => index,
+            None => {
+                self.invalid += 1;
+                self.phase = None;
+                return;
+            },
+        };
+        if let Some(phase) = self.phase {
+            match (index + HALF_STEPS.len() - phase) % HALF_STEPS.len() {
+                0 => {},
+                delta @ 1..=2 => {
+                    self.position += delta as i64;
+                    self.direction = 1;
+                },
+                delta @ 6..=7 => {
+                    self.position -= (HALF_STEPS.len() - delta) as i64;
+                    self.direction = -1;
+                },
+                // The motor cannot follow jumps of more than a full step
+                _ => self.invalid += 1,
+            }
+        }
+        self.phase = Some(index);
+    }
+    pub fn sync(&mut self, reg_memory: &RegMemory) -> &mut Self {
+        for event in reg_memory.output_log.read_since(&mut self.log_seq) {
+            let pattern = self.pattern(event.value);
+            let last_pattern = self.pending.map_or(self.coils, |(_, p)| p);
+            if pattern == last_pattern {
+                continue;
+            }
+            if let Some((time_us, p)) = self.pending {
+                if event.time_us >= time_us + GLITCH_US {
+                    self.apply(p);
+                }
+            }
+            self.pending = if pattern == self.coils { None } else { Some((event.time_us, pattern)) };
+        }
+        if let Some((time_us, p)) = self.pending {
+            if unix_time_us() >= time_us + GLITCH_US {
+                self.apply(p);
+                self.pending = None;
+            }
+        }
+        self
+    }
+    pub fn render(
+        &self, f: &mut Frame<'_, CrosstermBackend<std::io::Stdout>>,
+        area: Rect, board: &Board
+    ) {
+        let stepper_area = Rect {
+            x: area.x + self.pos_x + 1,
+            y: area.y + self.pos_y + 1,
+            width: Self::WIDTH,
+            height: Self::HEIGHT,
+        };
+        if !super::helper_is_rect_in_range(area, stepper_area) {
+            return;
+        }
+        let style = Style::default().fg(board.foreground_color).bg(board.background_color);
+        let mut stepper_content = vec![
+            Text::styled(format!("{}\n", self.name), style),
+        ];
+        for i in 0..Self::PIN_COUNT {
+            let color = if (self.coils >> i) & 0x1 != 0 { self.color_on } else { self.color_off };
+            stepper_content.push(Text::styled(
+                (i + 1).to_string(), Style::default().fg(Color::Black).bg(color)
+            ));
+        }
+        stepper_content.push(Text::styled(format!(" {:>6} {}", self.position, match self.direction {
+            1 => ">",
+            -1 => "<",
+            _ => " ",
+        }), style));
+        // Invalid sequences are flagged until the board is reloaded
+        if self.invalid > 0 {
+            stepper_content.push(Text::styled(" !", style.fg(Color::LightRed)));
+        }
+        Paragraph::new(stepper_content.iter())
+            .block(Block::default()
+                .borders(Borders::NONE)
+            )
+            .style(Style::default().bg(board.background_color))
+            .render(f, stepper_area);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn output(pattern: u8) -> u32 {
+        (0..4).filter(|i| (pattern >> i) & 0x1 != 0).fold(0, |o, i| o | (0x1 << [17, 18, 27, 22][i]))
+    }
+
+    #[test]
+    fn Stepper__sync() {
+        let mut stepper = Stepper::default();
+        let mut reg_memory = RegMemory::new();
+        // Half steps forward, then full steps (two coils) backward
+        for (i, &pattern) in [0b0001, 0b0011, 0b0010, 0b0110, 0b0100, 0b0110, 0b0011, 0b1001].iter().enumerate() {
+            reg_memory.output_log.push(1000 + i as u64 * 2000, output(pattern));
+        }
+        stepper.sync(&reg_memory);
+        assert_eq!((stepper.position, stepper.direction, stepper.invalid), (-1, -1, 0));
+        // Patterns which only last while the pins are written are ignored
+        reg_memory.output_log.push(20000, output(0b1011));
+        reg_memory.output_log.push(20005, output(0b0011));
+        stepper.sync(&reg_memory);
+        assert_eq!((stepper.position, stepper.direction, stepper.invalid), (1, 1, 0));
+        // Jumps of more than a full step are invalid
+        reg_memory.output_log.push(22000, output(0b1100));
+        stepper.sync(&reg_memory);
+        assert_eq!((stepper.position, stepper.invalid), (1, 1));
+    }
+}
@@ -0,0 +1,824 @@
+/*!validate.rs
+ * Hardware | Strict validation of board files. Reports every problem with
+ * the JSON path it has been found at.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use super::erc::Severity;
+use super::model::PART_TYPES;
+use super::{button::Button, dht::{Dht, DhtSensor}, encoder::Encoder, keypad::Keypad, lcd::Lcd, led::Led, potentiometer::Potentiometer};
+use super::{ledbar::LedBar, ledmatrix::LedMatrix, rgbled::RgbLed, servo::Servo, sevenseg::SevenSeg, stepper::Stepper};
+use serde_json::{Map, Value as SerdeValue};
+use std::fmt;
+use std::io::Error;
+
+const BOARD_KEYS: [&str; 9] = [
+    "type", "name", "backgroundColor", "foregroundColor", "size", "position", "defaults",
+    "include", "hardware",
+];
+const LED_KEYS: [&str; 6] = [
+    "type", "pin", "name", "colorOff", "colorOn", "position",
+];
+const BUTTON_KEYS: [&str; 10] = [
+    "type", "pin", "name", "hotkey", "mode", "holdTime", "bounce", "colorOff", "colorOn",
+    "position",
+];
+const SEVENSEG_KEYS: [&str; 8] = [
+    "type", "name", "segments", "digits", "common", "colorOff", "colorOn", "position",
+];
+const RGBLED_KEYS: [&str; 8] = [
+    "type", "name", "red", "green", "blue", "common", "colorOff", "position",
+];
+const LCD_KEYS: [&str; 10] = [
+    "type", "name", "rs", "e", "data", "rows", "cols", "colorOff", "colorOn", "position",
+];
+const POTENTIOMETER_KEYS: [&str; 10] = [
+    "type", "name", "channel", "hotkeyUp", "hotkeyDown", "value", "step", "colorOff", "colorOn",
+    "position",
+];
+const KEYPAD_KEYS: [&str; 11] = [
+    "type", "name", "rows", "cols", "keys", "hotkeys", "pull", "holdTime", "colorOff", "colorOn",
+    "position",
+];
+const ENCODER_KEYS: [&str; 13] = [
+    "type", "name", "pinA", "pinB", "pinSw", "hotkeyCw", "hotkeyCcw", "hotkeySw", "stepTime",
+    "holdTime", "colorOff", "colorOn", "position",
+];
+const SERVO_KEYS: [&str; 9] = [
+    "type", "pin", "name", "minPulse", "maxPulse", "maxAngle", "colorOff", "colorOn", "position",
+];
+const STEPPER_KEYS: [&str; 6] = [
+    "type", "name", "pins", "colorOff", "colorOn", "position",
+];
+const DHT_KEYS: [&str; 9] = [
+    "type", "pin", "name", "sensor", "temperature", "humidity", "colorOff", "colorOn", "position",
+];
+const LEDBAR_KEYS: [&str; 6] = [
+    "type", "name", "pins", "colorOff", "colorOn", "position",
+];
+const LEDMATRIX_KEYS: [&str; 8] = [
+    "type", "name", "rows", "cols", "common", "colorOff", "colorOn", "position",
+];
+
+#[derive(Clone, PartialEq)]
+pub struct Issue {
+    pub severity: Severity,
+    pub path: String,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}: {}",
+            match self.severity {
+                Severity::Warning => "WARNING",
+                Severity::Error => "ERROR  ",
+            },
+            if self.path.is_empty() { "(board)" } else { self.path.as_str() },
+            self.message
+        )
+    }
+}
+
+pub fn has_errors(issues: &Vec<Issue>) -> bool {
+    issues.iter().any(|i| i.severity == Severity::Error)
+}
+
+struct Checker {
+    issues: Vec<Issue>,
+    // Included files only provide parts, so they need no name
+    is_include: bool,
+}
+
+impl Checker {
+    fn error(&mut self, path: &str, message: String) {
+        self.issues.push(Issue { severity: Severity::Error, path: path.to_owned(), message });
+    }
+    fn warning(&mut self, path: &str, message: String) {
+        self.issues.push(Issue { severity: Severity::Warning, path: path.to_owned(), message });
+    }
+    fn join(path: &str, key: &str) -> String {
+        if path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", path, key)
+        }
+    }
+    fn check_unknown_keys(&mut self, path: &str, map: &Map<String, SerdeValue>, known: &[&str]) {
+        for key in map.keys() {
+            if !known.contains(&key.as_str()) {
+                self.warning(&Self::join(path, key), String::from("unknown field is ignored"));
+            }
+        }
+    }
+    fn check_type(&mut self, path: &str, map: &Map<String, SerdeValue>, expected: &str) {
+        match map.get("type") {
+            None => self.error(&Self::join(path, "type"), String::from("missing required field")),
+            Some(SerdeValue::String(t)) if t == expected => {},
+            Some(v) => self.error(&Self::join(path, "type"),
+                format!("expected \"{}\", found {}", expected, v)
+            ),
+        }
+    }
+    fn check_string(&mut self, path: &str, v: &SerdeValue) -> Option<String> {
+        match v.as_str() {
+            Some(s) => Some(s.to_owned()),
+            None => {
+                self.error(path, format!("expected a string, found {}", v));
+                None
+            },
+        }
+    }
+    fn check_uint(&mut self, path: &str, v: &SerdeValue, min: u64, max: u64) -> Option<u64> {
+        match v.as_u64() {
+            Some(n) if n >= min && n <= max => Some(n),
+            Some(n) => {
+                self.error(path, format!("{} is out of range ({}-{})", n, min, max));
+                None
+            },
+            None => {
+                self.error(path, format!("expected a non-negative integer, found {}", v));
+                None
+            },
+        }
+    }
+    fn check_pins(&mut self, path: &str, v: &SerdeValue, min_len: usize, max_len: usize) -> Vec<u8> {
+        let pins = match v.as_array() {
+            Some(pins) => pins,
+            None => {
+                self.error(path, format!("expected an array, found {}", v));
+                return vec![];
+            },
+        };
+        if min_len == max_len && pins.len() != min_len {
+            self.error(path, format!("expected {} pins, found {}", min_len, pins.len()));
+        } else if pins.len() < min_len || pins.len() > max_len {
+            self.error(path, format!("expected {}-{} pins, found {}", min_len, max_len, pins.len()));
+        }
+        pins.iter().enumerate()
+            .filter_map(|(i, pin)| self.check_uint(&format!("{}[{}]", path, i), pin, 0, 31))
+            .map(|pin| pin as u8)
+            .collect()
+    }
+    fn check_color(&mut self, path: &str, v: &SerdeValue) {
+        if let Some(c) = self.check_string(path, v) {
+            if super::helper_str_to_color(c.clone()).is_err() {
+                self.error(path, format!("invalid color '{}' (expected one of {})",
+                    c, super::COLOR_NAMES.join(", ")
+                ));
+            }
+        }
+    }
+    fn check_xy(&mut self, path: &str, v: &SerdeValue, x_key: &str, y_key: &str) -> (u64, u64) {
+        let mut xy = (0, 0);
+        match v.as_object() {
+            Some(map) => {
+                for (k, v) in map.iter() {
+                    let p = Self::join(path, k);
+                    if k == x_key {
+                        xy.0 = self.check_uint(&p, v, 0, u16::max_value() as u64).unwrap_or(0);
+                    } else if k == y_key {
+                        xy.1 = self.check_uint(&p, v, 0, u16::max_value() as u64).unwrap_or(0);
+                    } else {
+                        self.warning(&p, String::from("unknown field is ignored"));
+                    }
+                }
+            },
+            None => self.error(path, format!("expected an object, found {}", v)),
+        }
+        xy
+    }
+    fn check_part(&mut self, path: &str, part: &SerdeValue, board_size: (u64, u64), hotkeys: &mut Vec<(String, String)>) {
+        let map = match part.as_object() {
+            Some(map) => map,
+            None => {
+                self.error(path, format!("expected an object, found {}", part));
+                return;
+            },
+        };
+        let type_path = Self::join(path, "type");
+        let (known_keys, size): (&[&str], (u64, u64)) = match map.get("type") {
+            Some(SerdeValue::String(t)) => match t.as_str() {
+                "simpi/led" => (&LED_KEYS, (Led::WIDTH as u64, Led::HEIGHT as u64)),
+                "simpi/button" => (&BUTTON_KEYS, (Button::WIDTH as u64, Button::HEIGHT as u64)),
+                "simpi/sevenseg" => {
+                    let mut sevenseg = SevenSeg::default();
+                    sevenseg.digits = map.get("digits").and_then(|d| d.as_array())
+                        .map_or(vec![], |d| vec![0; d.len()]);
+                    sevenseg.name = map.get("name").and_then(|n| n.as_str()).unwrap_or("").to_owned();
+                    (&SEVENSEG_KEYS, (sevenseg.width() as u64, SevenSeg::HEIGHT as u64))
+                },
+                "simpi/lcd_hd44780" => {
+                    let mut lcd = Lcd::default();
+                    if let Some(rows) = map.get("rows").and_then(|r| r.as_u64()) {
+                        lcd.rows = rows.min(Lcd::MAX_ROWS as u64) as u8;
+                    }
+                    if let Some(cols) = map.get("cols").and_then(|c| c.as_u64()) {
+                        lcd.cols = cols.min(Lcd::MAX_COLS as u64) as u8;
+                    }
+                    (&LCD_KEYS, (lcd.width() as u64, lcd.height() as u64))
+                },
+                "simpi/rgbled" => (&RGBLED_KEYS, (RgbLed::WIDTH as u64, RgbLed::HEIGHT as u64)),
+                "simpi/potentiometer" => (&POTENTIOMETER_KEYS,
+                    (Potentiometer::WIDTH as u64, Potentiometer::HEIGHT as u64)
+                ),
+                "simpi/keypad" => {
+                    let mut keypad = Keypad::default();
+                    keypad.rows = map.get("rows").and_then(|r| r.as_array())
+                        .map_or(vec![], |r| vec![0; r.len().min(Keypad::MAX_ROWS)]);
+                    keypad.cols = map.get("cols").and_then(|c| c.as_array())
+                        .map_or(vec![], |c| vec![0; c.len().min(Keypad::MAX_COLS)]);
+                    (&KEYPAD_KEYS, (keypad.width() as u64, keypad.height() as u64))
+                },
+                "simpi/encoder" => (&ENCODER_KEYS, (Encoder::WIDTH as u64, Encoder::HEIGHT as u64)),
+                "simpi/servo" => (&SERVO_KEYS, (Servo::WIDTH as u64, Servo::HEIGHT as u64)),
+                "simpi/stepper" => (&STEPPER_KEYS, (Stepper::WIDTH as u64, Stepper::HEIGHT as u64)),
+                "simpi/dht" => (&DHT_KEYS, (Dht::WIDTH as u64, Dht::HEIGHT as u64)),
+                "simpi/ledbar" => {
+                    let mut ledbar = LedBar::default();
+                    ledbar.pins = map.get("pins").and_then(|p| p.as_array())
+                        .map_or(vec![], |p| vec![0; p.len().min(LedBar::MAX_PINS)]);
+                    ledbar.name = map.get("name").and_then(|n| n.as_str()).unwrap_or("").to_owned();
+                    (&LEDBAR_KEYS, (ledbar.width() as u64, LedBar::HEIGHT as u64))
+                },
+                "simpi/ledmatrix" => {
+                    let mut ledmatrix = LedMatrix::default();
+                    ledmatrix.rows = map.get("rows").and_then(|r| r.as_array())
+                        .map_or(vec![], |r| vec![0; r.len().min(LedMatrix::MAX_ROWS)]);
+                    ledmatrix.cols = map.get("cols").and_then(|c| c.as_array())
+                        .map_or(vec![], |c| vec![0; c.len().min(LedMatrix::MAX_COLS)]);
+                    ledmatrix.name = map.get("name").and_then(|n| n.as_str()).unwrap_or("").to_owned();
+                    (&LEDMATRIX_KEYS, (ledmatrix.width() as u64, ledmatrix.height() as u64))
+                },
+                _ => {
+                    self.warning(&type_path, format!("unknown part type '{}' is ignored", t));
+                    return;
+                },
+            },
+            Some(v) => {
+                self.error(&type_path, format!("expected a string, found {}", v));
+                return;
+            },
+            None => {
+                self.error(&type_path, String::from("missing required field"));
+                return;
+            },
+        };
+        self.check_unknown_keys(path, map, known_keys);
+        for key in ["pin", "red", "green", "blue", "pinA", "pinB"].iter().filter(|k| known_keys.contains(k)) {
+            match map.get(*key) {
+                Some(v) => { self.check_uint(&Self::join(path, key), v, 0, 31); },
+                None => self.error(&Self::join(path, key), String::from("missing required field")),
+            }
+        }
+        if known_keys.contains(&"segments") {
+            match map.get("segments") {
+                Some(v) => { self.check_pins(&Self::join(path, "segments"), v, 7, 8); },
+                None => self.error(&Self::join(path, "segments"), String::from("missing required field")),
+            }
+            if let Some(v) = map.get("digits") {
+                self.check_pins(&Self::join(path, "digits"), v, 0, 8);
+            }
+        }
+        // Pins of a LED matrix, those of a keypad are checked with its keys
+        if map.get("type").and_then(|t| t.as_str()) == Some("simpi/ledmatrix") {
+            for (key, max_len) in [("rows", LedMatrix::MAX_ROWS), ("cols", LedMatrix::MAX_COLS)].iter() {
+                match map.get(*key) {
+                    Some(v) => { self.check_pins(&Self::join(path, key), v, 1, *max_len); },
+                    None => self.error(&Self::join(path, key), String::from("missing required field")),
+                }
+            }
+        }
+        if let (true, Some(v)) = (known_keys.contains(&"common"), map.get("common")) {
+            if let Some(common) = self.check_string(&Self::join(path, "common"), v) {
+                if common != "anode" && common != "cathode" {
+                    self.error(&Self::join(path, "common"),
+                        format!("'{}' must be 'anode' or 'cathode'", common)
+                    );
+                }
+            }
+        }
+        if known_keys.contains(&"data") {
+            for key in ["rs", "e"].iter() {
+                match map.get(*key) {
+                    Some(v) => { self.check_uint(&Self::join(path, key), v, 0, 31); },
+                    None => self.error(&Self::join(path, key), String::from("missing required field")),
+                }
+            }
+            match map.get("data") {
+                Some(v) => {
+                    let p = Self::join(path, "data");
+                    let pins = self.check_pins(&p, v, 4, 8);
+                    if pins.len() > 4 && pins.len() < 8 {
+                        self.error(&p, format!("expected 4 or 8 pins, found {}", pins.len()));
+                    }
+                },
+                None => self.error(&Self::join(path, "data"), String::from("missing required field")),
+            }
+            if let Some(v) = map.get("rows") {
+                self.check_uint(&Self::join(path, "rows"), v, 1, Lcd::MAX_ROWS as u64);
+            }
+            if let Some(v) = map.get("cols") {
+                self.check_uint(&Self::join(path, "cols"), v, 1, Lcd::MAX_COLS as u64);
+            }
+        }
+        if let Some(v) = map.get("name") {
+            self.check_string(&Self::join(path, "name"), v);
+        }
+        for key in ["colorOff", "colorOn"].iter() {
+            if let Some(v) = map.get(*key) {
+                self.check_color(&Self::join(path, key), v);
+            }
+        }
+        let hotkey_keys = ["hotkey", "hotkeyUp", "hotkeyDown", "hotkeyCw", "hotkeyCcw", "hotkeySw"];
+        for key in hotkey_keys.iter().filter(|k| known_keys.contains(k)) {
+            let p = Self::join(path, key);
+            match map.get(*key) {
+                Some(v) => {
+                    if let Some(hotkey) = self.check_string(&p, v) {
+                        if hotkey.chars().count() != 1 {
+                            self.error(&p, format!("'{}' must be a single character", hotkey));
+                        } else if let Some((other, _)) = hotkeys.iter().find(|(_, h)| *h == hotkey) {
+                            self.warning(&p, format!("'{}' is already used by {}", hotkey, other));
+                        } else {
+                            hotkeys.push((p.clone(), hotkey));
+                        }
+                    }
+                },
+                // The switch of an encoder is optional
+                None if *key == "hotkeySw" => {},
+                None => self.error(&p, String::from("missing required field")),
+            }
+        }
+        if let (true, Some(v)) = (known_keys.contains(&"pinSw"), map.get("pinSw")) {
+            self.check_uint(&Self::join(path, "pinSw"), v, 0, 31);
+        }
+        if let (true, Some(v)) = (known_keys.contains(&"stepTime"), map.get("stepTime")) {
+            self.check_uint(&Self::join(path, "stepTime"), v,
+                Encoder::MIN_STEP_TIME_MS as u64, Encoder::MAX_STEP_TIME_MS as u64
+            );
+        }
+        if known_keys.contains(&"keys") {
+            self.check_keypad(path, map, hotkeys);
+        }
+        if known_keys.contains(&"minPulse") {
+            let mut pulses = vec![];
+            for key in ["minPulse", "maxPulse"].iter() {
+                if let Some(v) = map.get(*key) {
+                    pulses.push(self.check_uint(&Self::join(path, key), v,
+                        Servo::MIN_PULSE_US as u64, Servo::MAX_PULSE_US as u64
+                    ));
+                }
+            }
+            if let [Some(min), Some(max)] = pulses[..] {
+                if min >= max {
+                    self.error(&Self::join(path, "maxPulse"),
+                        format!("{} must be greater than minPulse ({})", max, min)
+                    );
+                }
+            }
+            if let Some(v) = map.get("maxAngle") {
+                self.check_uint(&Self::join(path, "maxAngle"), v, 1, Servo::MAX_ANGLE as u64);
+            }
+        }
+        if known_keys.contains(&"sensor") {
+            self.check_dht(path, map);
+        }
+        if known_keys.contains(&"pins") {
+            // A stepper has exactly one pin per coil input
+            let (min_len, max_len) = match map.get("type").and_then(|t| t.as_str()) {
+                Some("simpi/ledbar") => (1, LedBar::MAX_PINS),
+                _ => (Stepper::PIN_COUNT, Stepper::PIN_COUNT),
+            };
+            match map.get("pins") {
+                Some(v) => { self.check_pins(&Self::join(path, "pins"), v, min_len, max_len); },
+                None => self.error(&Self::join(path, "pins"), String::from("missing required field")),
+            }
+        }
+        if known_keys.contains(&"channel") {
+            match map.get("channel") {
+                Some(v) => { self.check_uint(&Self::join(path, "channel"), v, 0, Potentiometer::MAX_CHANNEL as u64); },
+                None => self.error(&Self::join(path, "channel"), String::from("missing required field")),
+            }
+            if let Some(v) = map.get("value") {
+                self.check_uint(&Self::join(path, "value"), v, 0, 100);
+            }
+            if let Some(v) = map.get("step") {
+                self.check_uint(&Self::join(path, "step"), v, 1, 100);
+            }
+        }
+        if let (true, Some(v)) = (known_keys.contains(&"mode"), map.get("mode")) {
+            if let Some(mode) = self.check_string(&Self::join(path, "mode"), v) {
+                if mode != "toggle" && mode != "momentary" {
+                    self.error(&Self::join(path, "mode"),
+                        format!("'{}' must be 'toggle' or 'momentary'", mode)
+                    );
+                }
+            }
+        }
+        if let (true, Some(v)) = (known_keys.contains(&"holdTime"), map.get("holdTime")) {
+            self.check_uint(&Self::join(path, "holdTime"), v,
+                Button::MIN_HOLD_TIME_MS as u64, Button::MAX_HOLD_TIME_MS as u64
+            );
+        }
+        if let (true, Some(v)) = (known_keys.contains(&"bounce"), map.get("bounce")) {
+            self.check_uint(&Self::join(path, "bounce"), v, 0, Button::MAX_BOUNCE_MS as u64);
+        }
+        if let Some(v) = map.get("position") {
+            let (x, y) = self.check_xy(&Self::join(path, "position"), v, "x", "y");
+            // Parts which do not fit completely on the board are not rendered
+            if x + size.0 + 2 > board_size.0 || y + size.1 + 2 > board_size.1 {
+                self.warning(&Self::join(path, "position"), format!(
+                    "part at ({}, {}) does not fit on the {}x{} board and is not shown",
+                    x, y, board_size.0, board_size.1
+                ));
+            }
+        }
+    }
+    // Checks the sensor and its values against the measuring range of the sensor.
+    fn check_dht(&mut self, path: &str, map: &Map<String, SerdeValue>) {
+        let mut sensor = DhtSensor::Dht22;
+        if let Some(v) = map.get("sensor") {
+            if let Some(name) = self.check_string(&Self::join(path, "sensor"), v) {
+                match DhtSensor::from_name(&name) {
+                    Some(s) => sensor = s,
+                    None => self.error(&Self::join(path, "sensor"),
+                        format!("'{}' must be 'dht11' or 'dht22'", name)
+                    ),
+                }
+            }
+        }
+        // The ranges depend on the sensor
+        let ranges = [
+            ("temperature", sensor.temperature_range()),
+            ("humidity", sensor.humidity_range()),
+        ];
+        for (key, (min, max)) in ranges.iter() {
+            let p = Self::join(path, key);
+            match map.get(*key).map(|v| (v, v.as_f64())) {
+                Some((_, Some(value))) if value * 10.0 < *min as f64 || value * 10.0 > *max as f64 => {
+                    self.error(&p, format!("{} is out of range for the {} ({}-{})",
+                        value, sensor.name(), *min as f64 / 10.0, *max as f64 / 10.0
+                    ));
+                },
+                Some((v, None)) => self.error(&p, format!("expected a number, found {}", v)),
+                _ => {},
+            }
+        }
+    }
+    // Checks the pins and key layout of a keypad, adding the hotkeys of all
+    // keys to the used hotkeys.
+    fn check_keypad(&mut self, path: &str, map: &Map<String, SerdeValue>, hotkeys: &mut Vec<(String, String)>) {
+        let mut size = (0, 0);
+        for (key, max_len) in [("rows", Keypad::MAX_ROWS), ("cols", Keypad::MAX_COLS)].iter() {
+            let len = match map.get(*key) {
+                Some(v) => self.check_pins(&Self::join(path, key), v, 1, *max_len).len(),
+                None => {
+                    self.error(&Self::join(path, key), String::from("missing required field"));
+                    0
+                },
+            };
+            if *key == "rows" { size.0 = len; } else { size.1 = len; }
+        }
+        let mut layouts: Vec<(String, Vec<String>)> = vec![];
+        for key in ["keys", "hotkeys"].iter() {
+            let v = match map.get(*key) {
+                Some(v) => v,
+                None => continue,
+            };
+            let p = Self::join(path, key);
+            let rows = match v.as_array() {
+                Some(rows) => rows,
+                None => {
+                    self.error(&p, format!("expected an array, found {}", v));
+                    continue;
+                },
+            };
+            if rows.len() != size.0 {
+                self.error(&p, format!("expected {} row(s), found {}", size.0, rows.len()));
+            }
+            let rows: Vec<String> = rows.iter().enumerate().filter_map(|(i, row)| {
+                let row_path = format!("{}[{}]", p, i);
+                let row = self.check_string(&row_path, row)?;
+                if row.chars().count() != size.1 {
+                    self.error(&row_path, format!("expected {} key(s), found {}", size.1, row.chars().count()));
+                }
+                Some(row)
+            }).collect();
+            layouts.push((p, rows));
+        }
+        // Keys without hotkeys are pressed with their lowercase label
+        let (p, rows) = match layouts.pop() {
+            Some((p, rows)) if p.ends_with("hotkeys") => (p, rows),
+            Some((p, rows)) => (p, rows.iter().map(|r| r.to_lowercase()).collect()),
+            None => (Self::join(path, "keys"), Keypad::default_keys(size.0, size.1)
+                .iter().map(|r| r.to_lowercase()).collect()),
+        };
+        for (i, row) in rows.iter().enumerate() {
+            let row_path = format!("{}[{}]", p, i);
+            for hotkey in row.chars().filter(|&c| c != ' ').map(|c| c.to_string()) {
+                if let Some((other, _)) = hotkeys.iter().find(|(_, h)| *h == hotkey) {
+                    self.warning(&row_path, format!("'{}' is already used by {}", hotkey, other));
+                } else {
+                    hotkeys.push((row_path.clone(), hotkey));
+                }
+            }
+        }
+        if let Some(v) = map.get("pull") {
+            if let Some(pull) = self.check_string(&Self::join(path, "pull"), v) {
+                if pull != "up" && pull != "down" {
+                    self.error(&Self::join(path, "pull"), format!("'{}' must be 'up' or 'down'", pull));
+                }
+            }
+        }
+    }
+    fn check_defaults(&mut self, v: &SerdeValue) {
+        let map = match v.as_object() {
+            Some(map) => map,
+            None => return self.error("defaults", format!("expected an object, found {}", v)),
+        };
+        for (t, defaults) in map.iter() {
+            let p = Self::join("defaults", t);
+            if !PART_TYPES.contains(&t.as_str()) {
+                self.warning(&p, format!("unknown part type '{}' is ignored", t));
+            } else if !defaults.is_object() {
+                self.error(&p, format!("expected an object, found {}", defaults));
+            }
+        }
+    }
+    fn check_include(&mut self, v: &SerdeValue) {
+        let includes = match v.as_array() {
+            Some(includes) => includes,
+            None => return self.error("include", format!("expected an array, found {}", v)),
+        };
+        for (i, include) in includes.iter().enumerate() {
+            let path = format!("include[{}]", i);
+            let map = match include.as_object() {
+                Some(map) => map,
+                None => {
+                    self.error(&path, format!("expected an object, found {}", include));
+                    continue;
+                },
+            };
+            self.check_unknown_keys(&path, map, &["file", "offset"]);
+            match map.get("file") {
+                Some(v) => { self.check_string(&Self::join(&path, "file"), v); },
+                None => self.error(&Self::join(&path, "file"), String::from("missing required field")),
+            }
+            if let Some(v) = map.get("offset") {
+                self.check_xy(&Self::join(&path, "offset"), v, "x", "y");
+            }
+        }
+    }
+    fn check_board(&mut self, json: &SerdeValue) {
+        let map = match json.as_object() {
+            Some(map) => map,
+            None => {
+                self.error("", format!("expected an object, found {}", json));
+                return;
+            },
+        };
+        self.check_type("", map, "simpi/board");
+        self.check_unknown_keys("", map, &BOARD_KEYS);
+        match map.get("name") {
+            Some(v) => { self.check_string("name", v); },
+            None if self.is_include => {},
+            None => self.warning("name", String::from("missing, using default name")),
+        }
+        for key in ["backgroundColor", "foregroundColor"].iter() {
+            if let Some(v) = map.get(*key) {
+                self.check_color(key, v);
+            }
+        }
+        let mut board_size = (64, 24);
+        if let Some(v) = map.get("size") {
+            board_size = self.check_xy("size", v, "width", "height");
+            if let Some(size) = v.as_object() {
+                board_size.0 = size.get("width").and_then(|w| w.as_u64()).unwrap_or(64);
+                board_size.1 = size.get("height").and_then(|h| h.as_u64()).unwrap_or(24);
+                for key in ["width", "height"].iter() {
+                    if size.get(*key).and_then(|v| v.as_u64()) == Some(0) {
+                        self.error(&Self::join("size", key), String::from("must be at least 1"));
+                    }
+                }
+            }
+        }
+        if let Some(v) = map.get("position") {
+            self.check_xy("position", v, "x", "y");
+        }
+        if let Some(v) = map.get("defaults") {
+            self.check_defaults(v);
+        }
+        if let Some(v) = map.get("include") {
+            self.check_include(v);
+        }
+        match map.get("hardware") {
+            Some(SerdeValue::Array(parts)) => {
+                let mut hotkeys = vec![];
+                for (i, part) in parts.iter().enumerate() {
+                    self.check_part(&format!("hardware[{}]", i), part, board_size, &mut hotkeys);
+                }
+            },
+            Some(v) => self.error("hardware", format!("expected an array, found {}", v)),
+            None => self.warning("hardware", String::from("missing, board has no parts")),
+        }
+    }
+}
+
+// Validates a parsed board and returns all issues found.
+pub fn check_board(json: &SerdeValue) -> Vec<Issue> {
+    let mut checker = Checker { issues: vec![], is_include: false };
+    checker.check_board(json);
+    checker.issues
+}
+
+// Reads a board file of any supported format as JSON value.
+pub fn read_board_json(file_name: &str) -> Result<SerdeValue, Error> {
+    super::model::read_file(file_name)
+}
+
+// Reads and validates a board file with the defaults of its parts applied.
+// Included files are validated the same way, the paths of their issues start
+// with the file name. Fails if the file or one of its includes cannot be read
+// or is not valid JSON, TOML or YAML.
+pub fn check_board_file(file_name: &str) -> Result<Vec<Issue>, Error> {
+    let resolved = super::include::resolve_file(file_name)?;
+    let mut issues = check_board(&resolved.board);
+    let mut checked: Vec<String> = vec![];
+    for file in resolved.files.into_iter() {
+        if checked.contains(&file) {
+            continue;
+        }
+        let mut checker = Checker { issues: vec![], is_include: true };
+        checker.check_board(&super::include::resolve_file(&file)?.board);
+        issues.extend(checker.issues.into_iter().map(|mut issue| {
+            issue.path = if issue.path.is_empty() {
+                file.clone()
+            } else {
+                format!("{}: {}", file, issue.path)
+            };
+            issue
+        }));
+        checked.push(file);
+    }
+    Ok(issues)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Checks a board with the given parts and returns its issues as text.
+    fn check_parts(parts: SerdeValue) -> Vec<String> {
+        let json = json!({ "type": "simpi/board", "name": "Test", "hardware": parts });
+        check_board(&json).iter().map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn check_board__valid() {
+        let json: SerdeValue = serde_json::from_str(
+            include_str!("../../config/gpiotest-board.json")
+        ).unwrap();
+        assert!(check_board(&json).is_empty());
+    }
+
+    #[test]
+    fn check_board_file__include() {
+        let dir = std::env::temp_dir().join(format!("simpi-validate-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("modules")).unwrap();
+        std::fs::write(dir.join("modules/leds.toml"), r#"
+            type = "simpi/board"
+            [[hardware]]
+            type = "simpi/led"
+            pin = 40
+            colour = "red"
+        "#).unwrap();
+        std::fs::write(dir.join("board.json"), json!({
+            "type": "simpi/board",
+            "name": "Test",
+            "include": [ { "file": "modules/leds.toml" }, { "file": "modules/leds.toml" } ],
+            "hardware": [ { "type": "simpi/led", "pin": 4 } ],
+        }).to_string()).unwrap();
+        let issues: Vec<String> = check_board_file(dir.join("board.json").to_str().unwrap()).unwrap()
+            .iter().map(|i| i.to_string()).collect();
+        let file = dir.join("modules/leds.toml").to_string_lossy().into_owned();
+        assert_eq!(issues, vec![
+            format!("WARNING {}: hardware[0].colour: unknown field is ignored", file),
+            format!("ERROR   {}: hardware[0].pin: 40 is out of range (0-31)", file),
+        ]);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn check_board__paths() {
+        let json = json!({
+            "type": "simpi/board",
+            "name": "Test",
+            "backgroundColor": "pink",
+            "hardware": [
+                { "type": "simpi/led", "pin": 4 },
+                { "type": "simpi/button", "pin": 40, "hotkey": "ab" },
+                { "type": "simpi/led", "pin": "5", "colour": "red" },
+            ],
+        });
+        let issues: Vec<String> = check_board(&json).iter().map(|i| i.to_string()).collect();
+        assert!(issues.iter().any(|i| i.starts_with("ERROR   backgroundColor: invalid color 'pink'")));
+        assert!(issues.contains(&String::from("ERROR   hardware[1].pin: 40 is out of range (0-31)")));
+        assert!(issues.contains(&String::from("ERROR   hardware[1].hotkey: 'ab' must be a single character")));
+        assert!(issues.contains(&String::from("ERROR   hardware[2].pin: expected a non-negative integer, found \"5\"")));
+        assert!(issues.contains(&String::from("WARNING hardware[2].colour: unknown field is ignored")));
+        assert_eq!(issues.len(), 5);
+    }
+
+    #[test]
+    fn check_board__defaults_include() {
+        let json = json!({
+            "type": "simpi/board",
+            "name": "Test",
+            "defaults": { "simpi/led": "red", "simpi/lamp": {} },
+            "include": [ { "offset": { "x": 2 } }, { "file": "leds.json", "offset": { "z": 1 } } ],
+            "hardware": [],
+        });
+        let issues: Vec<String> = check_board(&json).iter().map(|i| i.to_string()).collect();
+        assert_eq!(issues, vec![
+            "ERROR   defaults.simpi/led: expected an object, found \"red\"",
+            "WARNING defaults.simpi/lamp: unknown part type 'simpi/lamp' is ignored",
+            "ERROR   include[0].file: missing required field",
+            "WARNING include[1].offset.z: unknown field is ignored",
+        ]);
+    }
+
+    #[test]
+    fn check_board__keypad() {
+        assert_eq!(check_parts(json!([
+            { "type": "simpi/button", "pin": 4, "hotkey": "5" },
+            { "type": "simpi/keypad", "rows": [5, 6], "cols": [12, 16, 20], "keys": ["123", "45"], "pull": "left" },
+        ])), vec![
+            "ERROR   hardware[1].keys[1]: expected 3 key(s), found 2",
+            "WARNING hardware[1].keys[1]: '5' is already used by hardware[0].hotkey",
+            "ERROR   hardware[1].pull: 'left' must be 'up' or 'down'",
+        ]);
+    }
+
+    #[test]
+    fn check_board__lcd() {
+        assert_eq!(check_parts(json!([
+            { "type": "simpi/lcd_hd44780", "rs": 1, "e": 2, "data": [3, 4, 5, 6], "rows": 0, "cols": 41 },
+        ])), vec![
+            "ERROR   hardware[0].rows: 0 is out of range (1-4)",
+            "ERROR   hardware[0].cols: 41 is out of range (1-40)",
+        ]);
+    }
+
+    #[test]
+    fn check_board__servo() {
+        assert_eq!(check_parts(json!([
+            { "type": "simpi/servo", "pin": 18, "minPulse": 2000, "maxPulse": 1000, "maxAngle": 0 },
+        ])), vec![
+            "ERROR   hardware[0].maxPulse: 1000 must be greater than minPulse (2000)",
+            "ERROR   hardware[0].maxAngle: 0 is out of range (1-360)",
+        ]);
+    }
+
+    #[test]
+    fn check_board__stepper() {
+        assert_eq!(check_parts(json!([
+            { "type": "simpi/stepper", "pins": [17, 18, 27] },
+        ])), vec![
+            "ERROR   hardware[0].pins: expected 4 pins, found 3",
+        ]);
+    }
+
+    #[test]
+    fn check_board__dht() {
+        assert_eq!(check_parts(json!([
+            { "type": "simpi/dht", "pin": 4, "sensor": "dht11", "temperature": -5, "humidity": "45" },
+        ])), vec![
+            "ERROR   hardware[0].temperature: -5 is out of range for the dht11 (0-50)",
+            "ERROR   hardware[0].humidity: expected a number, found \"45\"",
+        ]);
+    }
+
+    #[test]
+    fn check_board__ledbar() {
+        assert_eq!(check_parts(json!([
+            { "type": "simpi/ledbar", "pins": [] },
+        ])), vec![
+            "ERROR   hardware[0].pins: expected 1-16 pins, found 0",
+        ]);
+    }
+
+    #[test]
+    fn check_board__ledmatrix() {
+        assert_eq!(check_parts(json!([
+            { "type": "simpi/ledmatrix", "rows": [2, 3, 4], "common": "row" },
+        ])), vec![
+            "ERROR   hardware[0].cols: missing required field",
+            "ERROR   hardware[0].common: 'row' must be 'anode' or 'cathode'",
+        ]);
+    }
+}
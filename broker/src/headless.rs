@@ -0,0 +1,144 @@
+/*!headless.rs
+ * Runs the broker without UI and prints a test report, e.g. for CI jobs.
+ *
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use crate::hardware::erc::{self, Diagnostic, Severity};
+use crate::{update_boards, update_clients, Broker, APP_NAME, VERSION};
+use std::{sync::atomic::Ordering, thread, time::{Duration, Instant}};
+use utils::{clients::ClientSlot, ShMem};
+
+// Time to wait for a program to attach if no duration is given
+pub const CONNECT_TIMEOUT_MS: u64 = 10000;
+
+// Reasons for a headless run to end
+#[derive(Debug, PartialEq)]
+enum Exit {
+    // The given duration has elapsed
+    Duration,
+    // The attached program(s) have disconnected
+    Disconnected,
+    // No program has attached within the connect timeout, e.g. because it
+    // crashed before wiringPiSetup() or uses another session
+    ConnectTimeout,
+}
+
+fn check_exit(
+    elapsed_ms: u64, duration_ms: Option<u64>, connect_timeout_ms: u64, seen_clients: usize,
+    connected_clients: usize
+) -> Option<Exit> {
+    match duration_ms {
+        Some(duration_ms) if elapsed_ms >= duration_ms => Some(Exit::Duration),
+        Some(_) => None,
+        None if seen_clients > 0 && connected_clients == 0 => Some(Exit::Disconnected),
+        None if seen_clients == 0 && elapsed_ms >= connect_timeout_ms => Some(Exit::ConnectTimeout),
+        None => None,
+    }
+}
+
+// Runs until the attached program(s) disconnect or the given duration has
+// elapsed and returns the exit code for the broker process.
+pub fn run(broker: &mut Broker, duration_ms: Option<u64>, connect_timeout_ms: u64) -> i32 {
+    if let Err(err) = broker.reg_memory.as_ref() {
+        eprintln!("Cannot attach to shared memory: {}", err);
+        return 2;
+    }
+    let start = Instant::now();
+    let mut seen_clients: Vec<ClientSlot> = vec![];
+    let mut diagnostics: Vec<Diagnostic> = vec![];
+    let exit = loop {
+        update_clients(broker);
+        for client in broker.clients.iter() {
+            if !seen_clients.iter().any(|c| c.pid() == client.pid()) {
+                seen_clients.push(client.clone());
+            }
+        }
+        update_boards(broker);
+        {
+            let reg_memory = ShMem::rlock(&broker.reg_memory);
+            for diagnostic in erc::check(&broker.bm.boards, &reg_memory) {
+                if !diagnostics.contains(&diagnostic) {
+                    diagnostics.push(diagnostic);
+                }
+            }
+        }
+        let elapsed_ms = start.elapsed().as_millis() as u64;
+        let exit = check_exit(
+            elapsed_ms, duration_ms, connect_timeout_ms, seen_clients.len(), broker.connected_clients
+        );
+        if let Some(exit) = exit {
+            break exit;
+        }
+        thread::sleep(Duration::from_millis(broker.tick_rate.load(Ordering::Relaxed)));
+    };
+    print_report(broker, start.elapsed(), &seen_clients, &diagnostics);
+    if exit == Exit::ConnectTimeout {
+        eprintln!("No program has attached to the session within {} ms", connect_timeout_ms);
+        3
+    } else if diagnostics.iter().any(|d| d.severity == Severity::Error) {
+        1
+    } else {
+        0
+    }
+}
+
+fn print_report(
+    broker: &Broker, elapsed: Duration, clients: &Vec<ClientSlot>, diagnostics: &Vec<Diagnostic>
+) {
+    let reg_memory = ShMem::rlock(&broker.reg_memory);
+    println!("{} {} - Test Report", APP_NAME, VERSION);
+    println!("Session:     {}", if broker.session.is_empty() { "(default)" } else { broker.session.as_str() });
+    println!("Duration:    {} ms", elapsed.as_millis());
+    println!("Programs:    {}", if clients.is_empty() {
+        String::from("None")
+    } else {
+        clients.iter()
+            .map(|c| format!("{} [{}]", c.name(), c.pid()))
+            .collect::<Vec<String>>()
+            .join(", ")
+    });
+    println!("Boards:      {}", if broker.bm.boards.is_empty() {
+        String::from("None")
+    } else {
+        broker.bm.boards.iter()
+            .map(|b| b.name.clone())
+            .collect::<Vec<String>>()
+            .join(", ")
+    });
+    println!("Registers:   INPUT={} OUTPUT={} CONFIG={}",
+        reg_memory.input.read_to_str(),
+        reg_memory.output.read_to_str(),
+        reg_memory.config.read_to_str()
+    );
+    let timing = &reg_memory.timing;
+    println!("Timing:      {} delays | avg. overshoot {} us | max. overshoot {} us | accuracy {:.1}% [{}]",
+        timing.count(), timing.avg_overshoot_us(), timing.max_overshoot_us(), timing.accuracy(),
+        if timing.is_reliable() { "OK" } else { "UNRELIABLE" }
+    );
+    let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+    println!("Diagnostics: {} error(s), {} warning(s)", errors, diagnostics.len() - errors);
+    for diagnostic in diagnostics.iter() {
+        println!("  {}", diagnostic);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_exit__reasons() {
+        // Fixed duration, regardless of the programs
+        assert_eq!(check_exit(999, Some(1000), 10, 0, 0), None);
+        assert_eq!(check_exit(1000, Some(1000), 10, 1, 1), Some(Exit::Duration));
+        // Until the program disconnects
+        assert_eq!(check_exit(500, None, 1000, 0, 0), None);
+        assert_eq!(check_exit(2000, None, 1000, 1, 1), None);
+        assert_eq!(check_exit(2000, None, 1000, 1, 0), Some(Exit::Disconnected));
+        // No program attached in time
+        assert_eq!(check_exit(1000, None, 1000, 0, 0), Some(Exit::ConnectTimeout));
+    }
+}
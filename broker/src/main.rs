@@ -12,17 +12,23 @@
 extern crate clap;
 extern crate tui;
 
-use clap::{App, Arg};
+use clap::{App, Arg, SubCommand};
 use std::{
-    io::{Error, stdout, Write},
-    sync::mpsc,
+    io::{Error, ErrorKind, stdout, Write},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc,
+        Arc,
+    },
     thread,
-    time::Duration,
+    time::{Duration, Instant},
 };
 use utils::{
+    clients::{ClientSlot, unix_time_ms},
     gpioregs::{Reg, RegMemory},
-    shared_memory::*,
-    ShMem
+    timing::TimingStats,
+    ShMem,
+    ShMemError,
 };
 use tui::Terminal;
 use tui::backend::CrosstermBackend;
@@ -38,6 +44,7 @@ use crossterm::terminal::LeaveAlternateScreen;
 
 mod app;
 mod hardware;
+mod headless;
 
 const PROJECT_NAME: &str = "SimPi";
 const APP_NAME: &str = "SimPi Broker";
@@ -45,23 +52,42 @@ const VERSION: &str = crate_version!();
 
 enum BrokerEvent<I> {
     Input(I),
+    Mouse(event::MouseEvent),
     Tick,
 }
 enum BrokerPage {
     GpioRegs,
     Help,
     BoardManager,
-    Preferences
+    Preferences,
+    Sessions,
 }
 
+// Interval for checking board files for changes
+const BOARD_RELOAD_INTERVAL_MS: u64 = 500;
+// Time a success message stays in the status line
+const STATUS_TIMEOUT_MS: u64 = 3000;
+
 struct Broker {
     active_page: BrokerPage,
+    auto_reset: bool,
     bm: app::BoardManager,
+    // Areas the boards have been rendered to, for mouse events
+    board_areas: Vec<Rect>,
+    clients: Vec<ClientSlot>,
+    connected_clients: usize,
+    diagnostics: Vec<hardware::erc::Diagnostic>,
     help: app::Help,
     is_paused: bool,
-    reg_memory: Result<ShMem, SharedMemError>,
+    last_reload_check: Instant,
+    prefs: app::Preferences,
+    reg_memory: Result<ShMem, ShMemError>,
     reg_memory_snapshot: RegMemory,
-    tick_rate: u64,
+    session: String,
+    sessions: app::Sessions,
+    // Last board reload message, errors stay until the next successful reload
+    status: Option<(Result<String, String>, Instant)>,
+    tick_rate: Arc<AtomicU64>,
 }
 
 fn reg_to_styled(reg: &Reg, data: &mut Vec<Text>) {
@@ -79,6 +105,136 @@ fn reg_to_styled(reg: &Reg, data: &mut Vec<Text>) {
     data.push(Text::raw("\n"));
 }
 
+fn timing_to_styled(timing: &TimingStats, data: &mut Vec<Text>) {
+    if timing.count() == 0 {
+        data.push(Text::styled("no delays recorded yet\n", Style::default().fg(Color::Gray)));
+        return;
+    }
+    data.push(Text::raw(format!(
+        "{} delays | avg. overshoot {} us | max. overshoot {} us | accuracy {:.1}% ",
+        timing.count(), timing.avg_overshoot_us(), timing.max_overshoot_us(), timing.accuracy()
+    )));
+    if timing.is_reliable() {
+        data.push(Text::styled(" OK ", Style::default().fg(Color::Black).bg(Color::LightGreen)));
+    } else {
+        data.push(Text::styled(" UNRELIABLE ", Style::default().fg(Color::White).bg(Color::LightRed).modifier(Modifier::BOLD)));
+    }
+    data.push(Text::raw("\n"));
+}
+
+fn clients_to_styled(clients: &Vec<ClientSlot>, data: &mut Vec<Text>) {
+    let now_ms = unix_time_ms();
+    if clients.is_empty() {
+        data.push(Text::styled("No program connected", Style::default().fg(Color::Gray)));
+        return;
+    }
+    for client in clients.iter() {
+        let uptime_s = now_ms.saturating_sub(client.setup_time_ms()) / 1000;
+        if client.is_stale(now_ms) {
+            data.push(Text::styled(
+                format!("{} [{}] stale", client.name(), client.pid()),
+                Style::default().fg(Color::White).bg(Color::LightRed)
+            ));
+        } else {
+            data.push(Text::styled(
+                format!("{} [{}] {:02}:{:02}", client.name(), client.pid(), uptime_s / 60, uptime_s % 60),
+                Style::default().fg(Color::Black).bg(Color::LightGreen)
+            ));
+        }
+        data.push(Text::raw(" "));
+    }
+}
+
+fn update_clients(broker: &mut Broker) {
+    if broker.reg_memory.is_err() {
+        return;
+    }
+    let mut reg_memory = ShMem::wlock(&mut broker.reg_memory);
+    let connected_clients = reg_memory.clients.count_connected(unix_time_ms());
+    broker.clients = reg_memory.clients.slots().iter()
+        .filter(|client| client.is_in_use())
+        .cloned()
+        .collect();
+    // Reset the registers as soon as the last client has gone away
+    if broker.auto_reset && !broker.is_paused
+        && broker.connected_clients > 0 && connected_clients == 0 {
+        reg_memory.reset();
+    }
+    broker.connected_clients = connected_clients;
+}
+
+fn update_boards(broker: &mut Broker) {
+    if broker.reg_memory.is_err() || broker.is_paused {
+        return;
+    }
+    let mut reg_memory = ShMem::wlock(&mut broker.reg_memory);
+    // Slots of removed parts must not answer anymore
+    let responder_pins = broker.bm.boards.iter()
+        .fold(0, |mask, board| mask | board.responder_pins());
+    reg_memory.responders.retain(responder_pins);
+    for board in broker.bm.boards.iter_mut() {
+        board.sync(&mut reg_memory);
+    }
+}
+
+fn update_diagnostics(broker: &mut Broker) {
+    if broker.reg_memory.is_err() {
+        broker.diagnostics = vec![];
+    } else if broker.is_paused {
+        broker.diagnostics = hardware::erc::check(&broker.bm.boards, &broker.reg_memory_snapshot);
+    } else {
+        let reg_memory = ShMem::rlock(&broker.reg_memory);
+        broker.diagnostics = hardware::erc::check(&broker.bm.boards, &reg_memory);
+    }
+}
+
+fn update_board_files(broker: &mut Broker) {
+    if broker.last_reload_check.elapsed() < Duration::from_millis(BOARD_RELOAD_INTERVAL_MS) {
+        return;
+    }
+    broker.last_reload_check = Instant::now();
+    for board in broker.bm.boards.iter_mut() {
+        let file_name = board.source_file.clone().unwrap_or_default();
+        match board.reload_if_changed() {
+            Some(Ok(_)) => {
+                broker.status = Some((Ok(format!("Reloaded '{}'", file_name)), Instant::now()));
+            },
+            Some(Err(err)) => {
+                broker.status = Some((Err(format!("Cannot reload '{}': {}", file_name, err)), Instant::now()));
+            },
+            None => {},
+        }
+    }
+    if let Some((Ok(_), time)) = &broker.status {
+        if time.elapsed() > Duration::from_millis(STATUS_TIMEOUT_MS) {
+            broker.status = None;
+        }
+    }
+}
+
+fn diagnostics_to_styled(diagnostics: &Vec<hardware::erc::Diagnostic>, max_lines: usize, data: &mut Vec<Text>) {
+    for (i, diagnostic) in diagnostics.iter().enumerate() {
+        if i + 1 == max_lines && diagnostics.len() > max_lines {
+            data.push(Text::styled(
+                format!("... and {} more\n", diagnostics.len() - i),
+                Style::default().fg(Color::Gray)
+            ));
+            break;
+        }
+        let color = match diagnostic.severity {
+            hardware::erc::Severity::Error => Color::LightRed,
+            hardware::erc::Severity::Warning => Color::LightYellow,
+        };
+        data.push(Text::styled(format!("{}\n", diagnostic), Style::default().fg(color)));
+    }
+}
+
+fn apply_preferences(broker: &mut Broker) {
+    if let Some(tick_rate) = broker.prefs.get_u64("sync__update_timeout_ms") {
+        broker.tick_rate.store(tick_rate, Ordering::Relaxed);
+    }
+}
+
 fn get_body_margin(rect: Rect, size: u16) -> u16 {
     if rect.width < size {
         0
@@ -91,13 +247,68 @@ fn load_board(broker: &mut Broker, board_file: &str) -> Result<(), Error> {
     let board = hardware::Board::from_file(board_file);
     if board.is_ok() {
         broker.bm.boards.push(board.unwrap());
+        let issues = hardware::validate::check_board_file(board_file).unwrap_or_default();
+        if !issues.is_empty() {
+            broker.status = Some((Err(format!(
+                "'{}' has {} validation issue(s), run with --check-board for details",
+                board_file, issues.len()
+            )), Instant::now()));
+        }
         Ok(())
     } else {
         Err(board.err().unwrap())
     }
 }
 
+// Validates the given board files, prints all issues and returns the exit
+// code for the broker process.
+fn check_board_files(
+    board_files: &Vec<&str>, check: fn(&str) -> Result<Vec<hardware::validate::Issue>, Error>
+) -> i32 {
+    let mut exit_code = 0;
+    for board_file in board_files.iter() {
+        match check(board_file) {
+            Ok(issues) => {
+                if issues.is_empty() {
+                    println!("{}: OK", board_file);
+                }
+                for issue in issues.iter() {
+                    println!("{}: {}", board_file, issue);
+                }
+                if hardware::validate::has_errors(&issues) {
+                    exit_code = 1;
+                }
+            },
+            Err(err) => {
+                println!("{}: ERROR   {}", board_file, err);
+                exit_code = 1;
+            },
+        }
+    }
+    exit_code
+}
+
+// Converts a board file into the format of the output file (by extension)
+// and returns the exit code for the broker process.
+fn convert_board_file(input: &str, output: &str) -> i32 {
+    let result = hardware::model::read_file::<hardware::model::BoardModel>(input)
+        .and_then(|model| {
+            if model.kind != "simpi/board" {
+                return Err(Error::new(ErrorKind::InvalidData, "Input data is invalid!"));
+            }
+            hardware::model::write_file(output, &model)
+        });
+    match result {
+        Ok(_) => 0,
+        Err(err) => {
+            eprintln!("Cannot convert '{}' to '{}': {}", input, output, err);
+            1
+        },
+    }
+}
+
 pub fn main() -> Result<(), failure::Error> {
+    let schema_names: Vec<&str> = hardware::schema::SCHEMAS.iter().map(|(name, _)| *name).collect();
     let matches = App::new("SimPi Broker")
         .version(VERSION)
         .author("Patrick Goldinger <@>")
@@ -109,29 +320,147 @@ pub fn main() -> Result<(), failure::Error> {
             .help("Space-separated list of boards to load")
             .min_values(1),
         )
+        .arg(Arg::with_name("check-board")
+            .long("check-board")
+            .value_name("BOARD")
+            .help("Validate the given board files and exit")
+            .min_values(1)
+            .conflicts_with_all(&["board", "headless"]),
+        )
+        .arg(Arg::with_name("session")
+            .short("s")
+            .long("session")
+            .value_name("NAME")
+            .help("Name of the shared memory session to attach to")
+            .validator(|name| {
+                if utils::is_valid_session_name(&name) {
+                    Ok(())
+                } else {
+                    Err(String::from("Session names may only contain letters, digits, '-' and '_'"))
+                }
+            }),
+        )
+        .arg(Arg::with_name("auto-reset")
+            .long("auto-reset")
+            .help("Reset the GPIO registers when the last program disconnects"),
+        )
+        .arg(Arg::with_name("headless")
+            .long("headless")
+            .help("Run without UI and print a test report when the program exits"),
+        )
+        .arg(Arg::with_name("duration")
+            .long("duration")
+            .value_name("MS")
+            .requires("headless")
+            .help("Stop the headless run after the given time in milliseconds")
+            .validator(|ms| {
+                ms.parse::<u64>().map(|_| ()).map_err(|_| String::from("Duration must be a number"))
+            }),
+        )
+        .arg(Arg::with_name("connect-timeout")
+            .long("connect-timeout")
+            .value_name("MS")
+            .requires("headless")
+            .conflicts_with("duration")
+            .help("Fail the headless run if no program attaches within the given time in milliseconds (default: 10000)")
+            .validator(|ms| {
+                ms.parse::<u64>().map(|_| ()).map_err(|_| String::from("Timeout must be a number"))
+            }),
+        )
         .arg(Arg::with_name("debug")
             .short("d")
             .help("Turn debugging information on [NYI]"),
         )
+        .subcommand(SubCommand::with_name("schema")
+            .about("Validate board files against the JSON schemas of the board format")
+            .arg(Arg::with_name("print")
+                .long("print")
+                .value_name("NAME")
+                .help("Print the given schema instead, e.g. for editor tooling")
+                .possible_values(&schema_names),
+            )
+            .arg(Arg::with_name("file")
+                .value_name("BOARD")
+                .help("Board files to validate")
+                .multiple(true)
+                .required_unless("print"),
+            ),
+        )
+        .subcommand(SubCommand::with_name("convert")
+            .about("Convert a board file between JSON, TOML and YAML (selected by file extension)")
+            .arg(Arg::with_name("input")
+                .value_name("INPUT")
+                .help("Board file to read")
+                .required(true),
+            )
+            .arg(Arg::with_name("output")
+                .value_name("OUTPUT")
+                .help("Board file to write, e.g. board.toml")
+                .required(true),
+            ),
+        )
         .get_matches();
     
+    if let Some(matches) = matches.subcommand_matches("convert") {
+        std::process::exit(convert_board_file(
+            matches.value_of("input").unwrap(),
+            matches.value_of("output").unwrap()
+        ));
+    }
+
+    if let Some(matches) = matches.subcommand_matches("schema") {
+        if let Some(name) = matches.value_of("print") {
+            print!("{}", hardware::schema::get_schema(name).unwrap_or_default());
+            std::process::exit(0);
+        }
+        let board_files: Vec<_> = matches.values_of("file").unwrap().collect();
+        std::process::exit(check_board_files(&board_files, hardware::schema::check_board_file));
+    }
+
+    if matches.is_present("check-board") {
+        let board_files: Vec<_> = matches.values_of("check-board").unwrap().collect();
+        std::process::exit(check_board_files(&board_files, hardware::validate::check_board_file));
+    }
+
+    let session = matches.value_of("session").unwrap_or("").to_owned();
     let mut broker = Broker {
         active_page: BrokerPage::GpioRegs,
+        auto_reset: matches.is_present("auto-reset"),
         bm: app::BoardManager::default(),
+        board_areas: vec![],
+        clients: vec![],
+        connected_clients: 0,
+        diagnostics: vec![],
         help: app::Help::default(),
         is_paused: false,
-        reg_memory: utils::init_shared_memory(),
+        last_reload_check: Instant::now(),
+        prefs: app::Preferences::default(),
+        reg_memory: utils::init_shared_memory(&session),
         reg_memory_snapshot: RegMemory::new(),
-        tick_rate: 50,
+        sessions: app::Sessions::new(&session),
+        session,
+        status: None,
+        tick_rate: Arc::new(AtomicU64::new(50)),
     };
+    apply_preferences(&mut broker);
     
     if matches.is_present("board") {
         let board_files: Vec<_> = matches.values_of("board").unwrap().collect();
         for board in board_files.iter() {
-            load_board(&mut broker, board).unwrap_or_default();
+            if let Err(err) = load_board(&mut broker, board) {
+                eprintln!("Cannot load board '{}': {}", board, err);
+                std::process::exit(1);
+            }
         }
     }
 
+    if matches.is_present("headless") {
+        let duration = matches.value_of("duration").map(|ms| ms.parse::<u64>().unwrap());
+        let connect_timeout = matches.value_of("connect-timeout")
+            .map_or(headless::CONNECT_TIMEOUT_MS, |ms| ms.parse::<u64>().unwrap());
+        std::process::exit(headless::run(&mut broker, duration, connect_timeout));
+    }
+
     // #region Terminal Setup
     enable_raw_mode()?;
 
@@ -147,14 +476,20 @@ pub fn main() -> Result<(), failure::Error> {
     // Setup input handling
     let (tx, rx) = mpsc::channel();
 
-    let tick_rate = broker.tick_rate;
+    let tick_rate = Arc::clone(&broker.tick_rate);
 
     thread::spawn(move || {
         loop {
             // poll for tick rate duration, if no events, sent tick event.
-            if event::poll(Duration::from_millis(tick_rate)).unwrap() {
-                if let Event::Key(key) = event::read().unwrap() {
-                    tx.send(BrokerEvent::Input(key)).unwrap_or_default();
+            if event::poll(Duration::from_millis(tick_rate.load(Ordering::Relaxed))).unwrap() {
+                match event::read().unwrap() {
+                    Event::Key(key) => {
+                        tx.send(BrokerEvent::Input(key)).unwrap_or_default();
+                    },
+                    Event::Mouse(mouse) => {
+                        tx.send(BrokerEvent::Mouse(mouse)).unwrap_or_default();
+                    },
+                    _ => {}
                 }
             }
             tx.send(BrokerEvent::Tick).unwrap_or_default();
@@ -165,6 +500,10 @@ pub fn main() -> Result<(), failure::Error> {
     // #endregion Terminal Setup
 
     loop {
+        update_clients(&mut broker);
+        update_boards(&mut broker);
+        update_diagnostics(&mut broker);
+        update_board_files(&mut broker);
         terminal.draw(|mut f| {
             // #region Application Layout
             let root_layout = Layout::default()
@@ -175,13 +514,21 @@ pub fn main() -> Result<(), failure::Error> {
                     Constraint::Percentage(get_body_margin(f.size(), 128)),
                 ].as_ref())
                 .split(f.size());
+            let diagnostics_height = if broker.diagnostics.is_empty() {
+                0
+            } else {
+                std::cmp::min(broker.diagnostics.len(), 5) as u16 + 2
+            };
+            let status_height = if broker.status.is_some() { 1 } else { 0 };
             let body_layout = match broker.active_page {
                 BrokerPage::GpioRegs => {
                     Layout::default()
                         .direction(Direction::Vertical)
                         .constraints([
                             Constraint::Length(3),  // SimPi Header
-                            Constraint::Length(9),  // GPIO Regs
+                            Constraint::Length(10), // GPIO Regs
+                            Constraint::Length(diagnostics_height), // Diagnostics
+                            Constraint::Length(status_height), // Status line
                             Constraint::Min(1),     // Board
                         ].as_ref())
                         .split(root_layout[1])
@@ -225,6 +572,11 @@ pub fn main() -> Result<(), failure::Error> {
                     if let BrokerPage::Preferences = broker.active_page { "Close Preferences" } else { "Preferences" },
                 header_cmd_style),
                 Text::raw(" "),
+                Text::styled("F4", header_key_style),
+                Text::styled(
+                    if let BrokerPage::Sessions = broker.active_page { "Close Sessions" } else { "Sessions" },
+                header_cmd_style),
+                Text::raw(" "),
                 Text::styled("F7", header_key_style),
                 Text::styled(
                     if broker.is_paused { "Play " } else { "Pause" },
@@ -238,11 +590,30 @@ pub fn main() -> Result<(), failure::Error> {
             ];
             Paragraph::new(header_text.iter())
                 .block(Block::default()
-                    .title(format!("{}{}{}", " SimPi Broker ", VERSION, " ").as_ref())
+                    .title(if broker.session.is_empty() {
+                        format!("{}{}{}", " SimPi Broker ", VERSION, " ")
+                    } else {
+                        format!("{}{} [{}] ", " SimPi Broker ", VERSION, broker.session)
+                    }.as_ref())
                     .borders(Borders::ALL)
                 )
                 .alignment(Alignment::Right)
                 .render(&mut f, body_layout[0]);
+            let header_len: usize = header_text.iter().map(|t| match t {
+                Text::Raw(s) => s.chars().count(),
+                Text::Styled(s, _) => s.chars().count(),
+            }).sum();
+            let clients_area = Rect {
+                x: body_layout[0].x + 2,
+                y: body_layout[0].y + 1,
+                width: body_layout[0].width.saturating_sub(header_len as u16 + 5),
+                height: 1,
+            };
+            let mut clients_text = vec![];
+            clients_to_styled(&broker.clients, &mut clients_text);
+            Paragraph::new(clients_text.iter())
+                .alignment(Alignment::Left)
+                .render(&mut f, clients_area);
             // #endregion Application Header UI
             
             // #region Application Body UI
@@ -268,6 +639,7 @@ pub fn main() -> Result<(), failure::Error> {
                         Text::raw("INTEN\n"),
                         Text::raw("INT0\n"),
                         Text::raw("INT1\n"),
+                        Text::raw("TIMING\n"),
                     ];
                     Paragraph::new(gpioregs_names.iter())
                         .block(Block::default())
@@ -289,8 +661,9 @@ pub fn main() -> Result<(), failure::Error> {
                             ].iter() {
                                 reg_to_styled(&reg, &mut data);
                             }
+                            timing_to_styled(&reg_memory.timing, &mut data);
                         } else {
-                            let mut reg_memory = ShMem::wlock(&mut broker.reg_memory);
+                            let reg_memory = ShMem::rlock(&broker.reg_memory);
                             for reg in [
                                 reg_memory.input,
                                 reg_memory.output,
@@ -301,25 +674,52 @@ pub fn main() -> Result<(), failure::Error> {
                             ].iter() {
                                 reg_to_styled(&reg, &mut data);
                             }
-                            for board in broker.bm.boards.iter_mut() {
-                                board.sync(&mut reg_memory);
-                            }
+                            timing_to_styled(&reg_memory.timing, &mut data);
                         }
                         Paragraph::new(data.iter())
                             .block(Block::default())
                             .alignment(Alignment::Right)
                             .render(&mut f, gpioregs_layout[1]);
-                        for board in broker.bm.boards.iter_mut() {
-                            board.render(&mut f, body_layout[2]);
+                        if !broker.diagnostics.is_empty() {
+                            let mut diagnostics_text = vec![];
+                            diagnostics_to_styled(&broker.diagnostics, 5, &mut diagnostics_text);
+                            Paragraph::new(diagnostics_text.iter())
+                                .block(Block::default()
+                                    .title(" Diagnostics ")
+                                    .borders(Borders::ALL)
+                                )
+                                .alignment(Alignment::Left)
+                                .render(&mut f, body_layout[2]);
+                        }
+                        if let Some((status, _)) = &broker.status {
+                            Paragraph::new([
+                                match status {
+                                    Ok(msg) => Text::styled(msg.clone(), Style::default().fg(Color::LightGreen)),
+                                    Err(msg) => Text::styled(msg.clone(), Style::default().fg(Color::LightRed)),
+                                },
+                            ].iter())
+                                .alignment(Alignment::Left)
+                                .render(&mut f, body_layout[3]);
                         }
+                        let board_areas = hardware::Board::layout(&broker.bm.boards, body_layout[4]);
+                        for (board, area) in broker.bm.boards.iter().zip(board_areas.iter()) {
+                            if area.width > 0 && area.height > 0 {
+                                board.render(&mut f, *area);
+                            }
+                        }
+                        broker.board_areas = board_areas;
                     } else {
                         Paragraph::new([
+                            Text::styled("Cannot attach to shared memory!\n",
+                                Style::default().fg(Color::LightRed).modifier(Modifier::BOLD)
+                            ),
                             Text::raw(format!(
                                 "{}", broker.reg_memory.as_ref().err().unwrap()
                             ))
                         ].iter())
                             .block(Block::default().borders(Borders::TOP))
                             .alignment(Alignment::Right)
+                            .wrap(true)
                             .render(&mut f, gpioregs_layout[1]);
                     };
                 },
@@ -330,12 +730,10 @@ pub fn main() -> Result<(), failure::Error> {
                     broker.bm.render(&mut f, body_layout[1]);
                 },
                 BrokerPage::Preferences => {
-                    // Placeholder
-                    Paragraph::new([
-                        Text::raw("Preferences [NYI]")
-                    ].iter())
-                        .block(Block::default().borders(Borders::ALL))
-                        .render(&mut f, body_layout[1]);
+                    broker.prefs.render(&mut f, body_layout[1]);
+                },
+                BrokerPage::Sessions => {
+                    broker.sessions.render(&mut f, body_layout[1]);
                 },
             }
             // #endregion Application Body UI
@@ -365,6 +763,13 @@ pub fn main() -> Result<(), failure::Error> {
                             } else {
                                 broker.active_page = BrokerPage::Preferences;
                             }
+                        } else if inp == 4 {
+                            if let BrokerPage::Sessions = broker.active_page {
+                                broker.active_page = BrokerPage::GpioRegs;
+                            } else {
+                                broker.sessions.refresh();
+                                broker.active_page = BrokerPage::Sessions;
+                            }
                         } else if inp == 7 {
                             if broker.is_paused {
                                 broker.is_paused = false;
@@ -397,7 +802,14 @@ pub fn main() -> Result<(), failure::Error> {
                             BrokerPage::Help => {
                                 broker.help.event_keypress(inp);
                             },
-                            _ => {}
+                            BrokerPage::Preferences => {
+                                if broker.prefs.event_keypress(inp) {
+                                    apply_preferences(&mut broker);
+                                }
+                            },
+                            BrokerPage::Sessions => {
+                                broker.sessions.event_keypress(inp);
+                            },
                         }
                     },
                     _ => {
@@ -408,11 +820,32 @@ pub fn main() -> Result<(), failure::Error> {
                             BrokerPage::Help => {
                                 broker.help.event_keypress_special(event.code);
                             },
+                            BrokerPage::Preferences => {
+                                if broker.prefs.event_keypress_special(event.code) {
+                                    apply_preferences(&mut broker);
+                                }
+                            },
+                            BrokerPage::Sessions => {
+                                broker.sessions.event_keypress_special(event.code);
+                            },
                             _ => {}
                         }
                     }
                 }
             },
+            BrokerEvent::Mouse(event) => {
+                match broker.active_page {
+                    BrokerPage::BoardManager => {
+                        broker.bm.event_mouse(event);
+                    },
+                    BrokerPage::GpioRegs => {
+                        for (board, area) in broker.bm.boards.iter_mut().zip(broker.board_areas.iter()) {
+                            board.event_mouse(event, *area);
+                        }
+                    },
+                    _ => {}
+                }
+            },
             BrokerEvent::Tick => {},
             //_ => {}
         }
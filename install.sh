@@ -60,7 +60,7 @@ STEP_copy_files () {
     cd wpisim
     cp "./target/release/libwpisim.d" "/usr/local/lib"
     cp "./target/release/libwpisim.so" "/usr/local/lib"
-    cp "./wiringPi.h" "/usr/local/include"
+    cp ./*.h "/usr/local/include"
     cd ..
     
     cp "./LICENSE" "$prog_files_dir"
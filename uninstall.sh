@@ -29,7 +29,8 @@ simpi_uninstall () {
     # #0 - Delete folders
     echo "Remove SimPi program files..."
     sudo rm -rf "$prog_files_dir"
-    sudo rm "/usr/local/include/wiringPi.h"
+    sudo rm -f "/usr/local/include/wiringPi.h" "/usr/local/include/softPwm.h" \
+        "/usr/local/include/mcp3004.h" "/usr/local/include/pcf8591.h"
     sudo rm "/usr/local/lib/libwpisim.d"
     sudo rm "/usr/local/lib/libwpisim.so"
     echo "Remove menu entry..."
@@ -0,0 +1,71 @@
+/*!adc.rs
+ * Module File for the registers of the simulated ADC. The broker writes the
+ * analog value of each channel, the client scales it to the resolution of
+ * the ADC chip the program has set up.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+pub const ADC_CHANNELS: usize =     8;
+// Values are stored with 16 bit resolution
+pub const ADC_BITS: u8 =            16;
+pub const ADC_FULL_SCALE: u32 =     (0x1 << ADC_BITS) - 1;
+
+#[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
+pub struct AdcRegs {
+    value: [u32; ADC_CHANNELS],
+}
+impl Copy for AdcRegs {}
+impl Clone for AdcRegs {
+    fn clone(&self) -> Self {
+        AdcRegs {
+            value: self.value,
+        }
+    }
+}
+impl AdcRegs {
+    pub fn new() -> AdcRegs {
+        return AdcRegs {
+            value: [0; ADC_CHANNELS],
+        };
+    }
+    pub fn reset(&mut self) {
+        *self = AdcRegs::new();
+    }
+    pub fn read(&self, channel: u8) -> u32 {
+        return self.value.get(channel as usize).cloned().unwrap_or(0);
+    }
+    pub fn write(&mut self, channel: u8, value: u32) {
+        if let Some(v) = self.value.get_mut(channel as usize) {
+            *v = value.min(ADC_FULL_SCALE);
+        }
+    }
+    // Writes the channel as fraction (0.0 - 1.0) of the full scale.
+    pub fn write_level(&mut self, channel: u8, level: f64) {
+        self.write(channel, (level.max(0.0).min(1.0) * ADC_FULL_SCALE as f64).round() as u32);
+    }
+    // Value of the channel as read by an ADC with the given resolution.
+    pub fn read_bits(&self, channel: u8, bits: u8) -> u32 {
+        return self.read(channel) >> (ADC_BITS - bits.min(ADC_BITS));
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn AdcRegs__read_bits() {
+        let mut adc = AdcRegs::new();
+        adc.write_level(0, 1.0);
+        adc.write_level(1, 0.5);
+        adc.write(9, 100);
+        assert_eq!(adc.read_bits(0, 10), 1023);
+        assert_eq!(adc.read_bits(1, 10), 512);
+        assert_eq!(adc.read_bits(1, 8), 128);
+        assert_eq!(adc.read_bits(9, 10), 0);
+    }
+}
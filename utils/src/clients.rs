@@ -0,0 +1,161 @@
+/*!clients.rs
+ * Module File for the registry of programs attached to the shared memory.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+pub const MAX_CLIENTS: usize =              8;
+pub const CLIENT_NAME_LEN: usize =          32;
+// Clients without heartbeat for longer than this are considered stale.
+pub const CLIENT_STALE_TIMEOUT_MS: u64 =    2000;
+
+// Returns the current time in milliseconds since the UNIX epoch. The system
+// clock is used as it is shared between the broker and all clients.
+pub fn unix_time_ms() -> u64 {
+    match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        Ok(duration) => duration.as_millis() as u64,
+        Err(_) => 0,
+    }
+}
+
+#[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
+pub struct ClientSlot {
+    in_use: u32,
+    pid: u32,
+    // Program name, NUL-padded
+    name: [u8; CLIENT_NAME_LEN],
+    setup_time_ms: u64,
+    heartbeat_ms: u64,
+}
+impl Copy for ClientSlot {}
+impl Clone for ClientSlot {
+    fn clone(&self) -> Self {
+        ClientSlot {
+            in_use:         self.in_use,
+            pid:            self.pid,
+            name:           self.name,
+            setup_time_ms:  self.setup_time_ms,
+            heartbeat_ms:   self.heartbeat_ms,
+        }
+    }
+}
+impl ClientSlot {
+    pub fn new() -> ClientSlot {
+        return ClientSlot {
+            in_use:         0,
+            pid:            0,
+            name:           [0; CLIENT_NAME_LEN],
+            setup_time_ms:  0,
+            heartbeat_ms:   0,
+        };
+    }
+    pub fn is_in_use(&self) -> bool {
+        return self.in_use != 0;
+    }
+    pub fn pid(&self) -> u32 {
+        return self.pid;
+    }
+    pub fn name(&self) -> String {
+        let len = self.name.iter().position(|&b| b == 0).unwrap_or(CLIENT_NAME_LEN);
+        return String::from_utf8_lossy(&self.name[..len]).into_owned();
+    }
+    pub fn setup_time_ms(&self) -> u64 {
+        return self.setup_time_ms;
+    }
+    pub fn heartbeat_ms(&self) -> u64 {
+        return self.heartbeat_ms;
+    }
+    pub fn is_stale(&self, now_ms: u64) -> bool {
+        return now_ms.saturating_sub(self.heartbeat_ms) > CLIENT_STALE_TIMEOUT_MS;
+    }
+}
+
+#[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
+pub struct ClientTable {
+    slots: [ClientSlot; MAX_CLIENTS],
+}
+impl Copy for ClientTable {}
+impl Clone for ClientTable {
+    fn clone(&self) -> Self {
+        ClientTable {
+            slots: self.slots,
+        }
+    }
+}
+impl ClientTable {
+    pub fn new() -> ClientTable {
+        return ClientTable {
+            slots: [ClientSlot::new(); MAX_CLIENTS],
+        };
+    }
+    pub fn slots(&self) -> &[ClientSlot] {
+        return &self.slots;
+    }
+    // Registers a client and returns its slot index. Slots of stale clients
+    // are reused if the table is full.
+    pub fn register(&mut self, pid: u32, name: &str, now_ms: u64) -> Option<usize> {
+        let index = self.slots.iter().position(|s| s.is_in_use() && s.pid == pid)
+            .or_else(|| self.slots.iter().position(|s| !s.is_in_use()))
+            .or_else(|| self.slots.iter().position(|s| s.is_stale(now_ms)));
+        if let Some(index) = index {
+            let slot = &mut self.slots[index];
+            *slot = ClientSlot::new();
+            slot.in_use = 1;
+            slot.pid = pid;
+            for (i, byte) in name.bytes().take(CLIENT_NAME_LEN).enumerate() {
+                slot.name[i] = byte;
+            }
+            slot.setup_time_ms = now_ms;
+            slot.heartbeat_ms = now_ms;
+        }
+        return index;
+    }
+    pub fn unregister(&mut self, index: usize, pid: u32) {
+        if index < MAX_CLIENTS && self.slots[index].pid == pid {
+            self.slots[index] = ClientSlot::new();
+        }
+    }
+    pub fn heartbeat(&mut self, index: usize, pid: u32, now_ms: u64) -> bool {
+        if index < MAX_CLIENTS && self.slots[index].is_in_use() && self.slots[index].pid == pid {
+            self.slots[index].heartbeat_ms = now_ms;
+            return true;
+        }
+        return false;
+    }
+    pub fn count_connected(&self, now_ms: u64) -> usize {
+        return self.slots.iter().filter(|s| s.is_in_use() && !s.is_stale(now_ms)).count();
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ClientTable__register() {
+        let mut clients = ClientTable::new();
+        let index = clients.register(42, "blinky", 1000).unwrap();
+        assert_eq!(clients.slots()[index].name(), "blinky");
+        assert_eq!(clients.count_connected(1000), 1);
+        assert_eq!(clients.register(42, "blinky", 2000), Some(index));
+        clients.unregister(index, 42);
+        assert_eq!(clients.count_connected(2000), 0);
+    }
+
+    #[test]
+    fn ClientTable__stale() {
+        let mut clients = ClientTable::new();
+        for pid in 0..MAX_CLIENTS as u32 {
+            clients.register(pid + 1, "prog", 0);
+        }
+        let now = CLIENT_STALE_TIMEOUT_MS + 1;
+        assert!(clients.heartbeat(0, 1, now));
+        assert_eq!(clients.count_connected(now), 1);
+        // Table is full, so the first stale slot gets reused
+        assert_eq!(clients.register(100, "new", now), Some(1));
+    }
+}
@@ -5,7 +5,18 @@
  * License: GPL 3.0 (see LICENSE file for details)
  */
 
+use crate::adc::AdcRegs;
+use crate::clients::ClientTable;
+use crate::inlog::InputLog;
+use crate::layout::ShmHeader;
+use crate::links::PinLinks;
+use crate::outlog::OutputLog;
+use crate::pwm::PwmRegs;
+use crate::responder::Responders;
+use crate::timing::TimingStats;
+
 #[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
 pub struct Reg {
     value: u32
 }
@@ -78,7 +89,10 @@ impl Reg {
 }
 
 #[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
 pub struct RegMemory {
+    // Layout header, must always stay the first field!
+    pub header: ShmHeader,
     // INPUT register (seen from programmer's view)
     pub input: Reg,
     // OUTPUT register (seen from programmer's view)
@@ -97,30 +111,72 @@ pub struct RegMemory {
     pub int0: Reg,
     // Interrupt config bit 2^1 (ignored if pin in inten is disabled!)
     pub int1: Reg,
+    // 1=Pull-up/down resistor enabled 0=No pull resistor
+    pub pull: Reg,
+    // 1=Pin has been set up by the program (pinMode, wiringPiISR, ...)
+    pub used: Reg,
+    // Duty cycles of the pins set up for (software) PWM
+    pub pwm: PwmRegs,
+    // Scheduled changes of the INPUT register, applied by the client
+    pub input_log: InputLog,
+    // Analog values of the simulated ADC channels
+    pub adc: AdcRegs,
+    // Pins connected by the broker, e.g. by pressed keys of a key matrix
+    pub links: PinLinks,
+    // Single-wire protocols answered by the client, e.g. of DHT sensors
+    pub responders: Responders,
+    // Delay timing statistics of the connected program
+    pub timing: TimingStats,
+    // Changes of the OUTPUT register (not affected by reset)
+    pub output_log: OutputLog,
+    // Programs attached to this mapping (not affected by reset)
+    pub clients: ClientTable,
 }
 
 impl Copy for RegMemory {}
 impl Clone for RegMemory {
     fn clone(&self) -> Self {
         RegMemory {
+            header: self.header.clone(),
             input:  self.input.clone(),
             output: self.output.clone(),
             config: self.config.clone(),
             inten:  self.inten.clone(),
             int0:   self.int0.clone(),
             int1:   self.int1.clone(),
+            pull:   self.pull.clone(),
+            used:   self.used.clone(),
+            pwm:    self.pwm.clone(),
+            input_log: self.input_log.clone(),
+            adc:    self.adc.clone(),
+            links:  self.links.clone(),
+            responders: self.responders.clone(),
+            timing: self.timing.clone(),
+            output_log: self.output_log.clone(),
+            clients: self.clients.clone(),
         }
     }
 }
 impl RegMemory {
     pub fn new() -> RegMemory {
         return RegMemory {
+            header: ShmHeader::new(),
             input:  Reg::from(0x00000000),
             output: Reg::from(0x00000000),
             config: Reg::from(0xFFFFFFFF),
             inten:  Reg::from(0x00000000),
             int0:   Reg::from(0x00000000),
             int1:   Reg::from(0x00000000),
+            pull:   Reg::from(0x00000000),
+            used:   Reg::from(0x00000000),
+            pwm:    PwmRegs::new(),
+            input_log: InputLog::new(),
+            adc:    AdcRegs::new(),
+            links:  PinLinks::new(),
+            responders: Responders::new(),
+            timing: TimingStats::new(),
+            output_log: OutputLog::new(),
+            clients: ClientTable::new(),
         };
     }
     pub fn reset(&mut self) {
@@ -130,6 +186,27 @@ impl RegMemory {
         self.inten.write(0x00000000);
         self.int0.write(0x00000000);
         self.int1.write(0x00000000);
+        self.pull.write(0x00000000);
+        self.used.write(0x00000000);
+        self.pwm.reset();
+        self.input_log.clear();
+        self.adc.reset();
+        self.links.reset();
+        self.responders.reset();
+        self.timing.reset();
+    }
+    // Level of an input pin. A pin linked to output pins reads their level;
+    // if they disagree, the level which differs from the INPUT register
+    // wins (e.g. a row driven low pulls a column with pull-up low).
+    pub fn read_input(&self, pin: u8) -> u8 {
+        let idle = self.input.read_pin(pin);
+        let linked_outputs = self.links.linked(pin) & !self.config.read();
+        for other in 0..32 {
+            if (linked_outputs >> other) & 0x1 != 0 && self.output.read_pin(other) != idle {
+                return self.output.read_pin(other);
+            }
+        }
+        return idle;
     }
     pub fn get(&mut self, key: String) -> Result<&mut Reg, String> {
         let key = key.to_ascii_lowercase();
@@ -152,6 +229,12 @@ impl RegMemory {
             "int1" => {
                 return Ok(&mut self.int1);
             },
+            "pull" => {
+                return Ok(&mut self.pull);
+            },
+            "used" => {
+                return Ok(&mut self.used);
+            },
             _ => {
                 return Err("Invalid register name".to_owned());
             }
@@ -164,6 +247,23 @@ impl RegMemory {
 mod tests {
     use super::*;
 
+    #[test]
+    fn RegMemory__read_input() {
+        let mut regmem = RegMemory::new();
+        regmem.input.write_pin(17, 1);
+        regmem.config.write_pin(5, 0);
+        regmem.config.write_pin(6, 0);
+        regmem.output.write_pin(5, 1);
+        regmem.links.link(5, 17);
+        regmem.links.link(6, 17);
+        assert_eq!(regmem.read_input(17), 0);
+        regmem.output.write_pin(6, 1);
+        assert_eq!(regmem.read_input(17), 1);
+        regmem.links.unlink(0x1 << 5 | 0x1 << 6);
+        regmem.output.write_pin(6, 0);
+        assert_eq!(regmem.read_input(17), 1);
+    }
+
     #[test]
     fn RegMemory__reset() {
         let mut regmem = RegMemory::new();
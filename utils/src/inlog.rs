@@ -0,0 +1,119 @@
+/*!inlog.rs
+ * Module File for the log of scheduled INPUT register changes. Lets the
+ * broker simulate changes which happen faster than it polls the registers,
+ * e.g. bouncing button contacts. The changes are applied by the client.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+pub const INPUT_LOG_LEN: usize =    256;
+// Events which are overdue by more than this (in us) are dropped, e.g. if
+// no client has been running while they were due.
+pub const INPUT_EVENT_TIMEOUT_US: u64 = 1_000_000;
+// Clients check the log at least this often (in us), so changes scheduled at
+// least this far ahead are applied on time.
+pub const INPUT_EVENT_LEAD_US: u64 = 50_000;
+
+#[derive(shared_memory::SharedMemCast, Debug, PartialEq)]
+#[repr(C)]
+pub struct InputEvent {
+    // Time the change is due at, 0 marks a free slot
+    pub time_us: u64,
+    pub pin: u8,
+    pub value: u8,
+    _reserved: [u8; 6],
+}
+impl Copy for InputEvent {}
+impl Clone for InputEvent {
+    fn clone(&self) -> Self {
+        InputEvent {
+            time_us:    self.time_us,
+            pin:        self.pin,
+            value:      self.value,
+            _reserved:  [0; 6],
+        }
+    }
+}
+impl InputEvent {
+    pub fn new(time_us: u64, pin: u8, value: u8) -> InputEvent {
+        return InputEvent { time_us, pin, value, _reserved: [0; 6] };
+    }
+}
+
+// Unordered set of scheduled INPUT register changes.
+#[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
+pub struct InputLog {
+    events: [InputEvent; INPUT_LOG_LEN],
+}
+impl Copy for InputLog {}
+impl Clone for InputLog {
+    fn clone(&self) -> Self {
+        InputLog {
+            events: self.events,
+        }
+    }
+}
+impl InputLog {
+    pub fn new() -> InputLog {
+        return InputLog {
+            events: [InputEvent::new(0, 0, 0); INPUT_LOG_LEN],
+        };
+    }
+    pub fn clear(&mut self) {
+        *self = InputLog::new();
+    }
+    // Schedules a change, returns false if the log is full.
+    pub fn push(&mut self, time_us: u64, pin: u8, value: u8) -> bool {
+        match self.events.iter_mut().find(|e| e.time_us == 0) {
+            Some(event) => {
+                *event = InputEvent::new(std::cmp::max(time_us, 1), pin, value);
+                return true;
+            },
+            None => return false,
+        }
+    }
+    // Time of the next scheduled change
+    pub fn next_due(&self) -> Option<u64> {
+        return self.events.iter().filter(|e| e.time_us != 0).map(|e| e.time_us).min();
+    }
+    // Removes and returns all changes due at the given time, oldest first.
+    pub fn pop_due(&mut self, now_us: u64) -> Vec<InputEvent> {
+        let mut due = vec![];
+        for event in self.events.iter_mut() {
+            if event.time_us != 0 && event.time_us <= now_us {
+                if event.time_us + INPUT_EVENT_TIMEOUT_US >= now_us {
+                    due.push(*event);
+                }
+                event.time_us = 0;
+            }
+        }
+        due.sort_by_key(|e| e.time_us);
+        return due;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn InputLog__pop_due() {
+        let mut log = InputLog::new();
+        assert!(log.push(300, 17, 1));
+        assert!(log.push(100, 17, 0));
+        assert!(log.push(200, 4, 1));
+        assert_eq!(log.next_due(), Some(100));
+        assert_eq!(log.pop_due(250), vec![InputEvent::new(100, 17, 0), InputEvent::new(200, 4, 1)]);
+        assert_eq!(log.next_due(), Some(300));
+        // Overdue events are dropped
+        assert!(log.pop_due(300 + INPUT_EVENT_TIMEOUT_US + 1).is_empty());
+        assert_eq!(log.next_due(), None);
+        for i in 0..INPUT_LOG_LEN {
+            assert!(log.push(1 + i as u64, 2, 0));
+        }
+        assert!(!log.push(1000, 2, 0));
+    }
+}
@@ -0,0 +1,170 @@
+/*!layout.rs
+ * Module File for the header of the shared memory layout.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use crate::gpioregs::RegMemory;
+use std::fmt;
+
+// "SIMP" in ASCII
+pub const SHM_MAGIC: u32 =              0x53494D50;
+// Must be increased on every change of the RegMemory layout!
+pub const SHM_LAYOUT_VERSION: u32 =     11;
+
+pub const FEATURE_TIMING_STATS: u32 =   0x1 << 0;
+pub const FEATURE_CLIENTS: u32 =        0x1 << 1;
+pub const FEATURE_OUTPUT_LOG: u32 =     0x1 << 2;
+pub const FEATURE_PWM: u32 =            0x1 << 3;
+pub const FEATURE_INPUT_LOG: u32 =      0x1 << 4;
+pub const FEATURE_ADC: u32 =            0x1 << 5;
+pub const FEATURE_PIN_LINKS: u32 =      0x1 << 6;
+pub const FEATURE_RESPONDERS: u32 =     0x1 << 7;
+
+// Features provided by this build
+pub const SHM_FEATURES: u32 =           FEATURE_TIMING_STATS
+                                        | FEATURE_CLIENTS
+                                        | FEATURE_OUTPUT_LOG
+                                        | FEATURE_PWM
+                                        | FEATURE_INPUT_LOG
+                                        | FEATURE_ADC
+                                        | FEATURE_PIN_LINKS
+                                        | FEATURE_RESPONDERS;
+// Features a peer must provide so that this build can work with it
+pub const SHM_REQUIRED_FEATURES: u32 =  FEATURE_TIMING_STATS
+                                        | FEATURE_CLIENTS
+                                        | FEATURE_OUTPUT_LOG
+                                        | FEATURE_PWM
+                                        | FEATURE_INPUT_LOG
+                                        | FEATURE_ADC
+                                        | FEATURE_PIN_LINKS
+                                        | FEATURE_RESPONDERS;
+
+#[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
+pub struct ShmHeader {
+    magic: u32,
+    version: u32,
+    features: u32,
+    // Size of the whole RegMemory in bytes
+    size: u32,
+}
+impl Copy for ShmHeader {}
+impl Clone for ShmHeader {
+    fn clone(&self) -> Self {
+        ShmHeader {
+            magic:      self.magic,
+            version:    self.version,
+            features:   self.features,
+            size:       self.size,
+        }
+    }
+}
+impl ShmHeader {
+    pub fn new() -> ShmHeader {
+        return ShmHeader {
+            magic:      SHM_MAGIC,
+            version:    SHM_LAYOUT_VERSION,
+            features:   SHM_FEATURES,
+            size:       std::mem::size_of::<RegMemory>() as u32,
+        };
+    }
+    pub fn version(&self) -> u32 {
+        return self.version;
+    }
+    pub fn features(&self) -> u32 {
+        return self.features;
+    }
+    pub fn has_feature(&self, feature: u32) -> bool {
+        return (self.features & feature) == feature;
+    }
+    pub fn validate(&self) -> Result<(), LayoutError> {
+        if self.magic != SHM_MAGIC {
+            return Err(LayoutError::InvalidMagic(self.magic));
+        }
+        if self.version != SHM_LAYOUT_VERSION {
+            return Err(LayoutError::VersionMismatch {
+                found: self.version,
+                expected: SHM_LAYOUT_VERSION,
+            });
+        }
+        if self.size as usize != std::mem::size_of::<RegMemory>() {
+            return Err(LayoutError::SizeMismatch {
+                found: self.size as usize,
+                expected: std::mem::size_of::<RegMemory>(),
+            });
+        }
+        if !self.has_feature(SHM_REQUIRED_FEATURES) {
+            return Err(LayoutError::MissingFeatures(
+                SHM_REQUIRED_FEATURES & !self.features
+            ));
+        }
+        return Ok(());
+    }
+}
+
+#[derive(Debug)]
+pub enum LayoutError {
+    InvalidMagic(u32),
+    VersionMismatch { found: u32, expected: u32 },
+    SizeMismatch { found: usize, expected: usize },
+    MissingFeatures(u32),
+}
+impl fmt::Display for LayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LayoutError::InvalidMagic(magic) => write!(f,
+                "Shared memory is not a SimPi mapping or was created by an older SimPi \
+                version (magic {:#010X}). Close all SimPi programs and brokers and try again.",
+                magic
+            ),
+            LayoutError::VersionMismatch { found, expected } => write!(f,
+                "Shared memory layout version {} does not match expected version {}. \
+                Make sure the broker and libwpisim are from the same SimPi release.",
+                found, expected
+            ),
+            LayoutError::SizeMismatch { found, expected } => write!(f,
+                "Shared memory size {} bytes does not match expected size {} bytes. \
+                Make sure the broker and libwpisim are from the same SimPi release.",
+                found, expected
+            ),
+            LayoutError::MissingFeatures(features) => write!(f,
+                "Shared memory peer lacks required features ({:#010X}). \
+                Make sure the broker and libwpisim are from the same SimPi release.",
+                features
+            ),
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ShmHeader__validate() {
+        assert!(ShmHeader::new().validate().is_ok());
+    }
+
+    #[test]
+    fn ShmHeader__validate__version_mismatch() {
+        let mut header = ShmHeader::new();
+        header.version = SHM_LAYOUT_VERSION + 1;
+        match header.validate() {
+            Err(LayoutError::VersionMismatch { found, expected }) => {
+                assert_eq!(found, SHM_LAYOUT_VERSION + 1);
+                assert_eq!(expected, SHM_LAYOUT_VERSION);
+            },
+            _ => panic!("Expected a version mismatch!"),
+        }
+    }
+
+    #[test]
+    fn ShmHeader__validate__invalid_magic() {
+        let mut header = ShmHeader::new();
+        header.magic = 0;
+        assert!(header.validate().is_err());
+    }
+}
@@ -9,11 +9,22 @@
 extern crate lazy_static;
 pub extern crate shared_memory;
 
+pub mod adc;
+pub mod clients;
 pub mod gpioregs;
+pub mod inlog;
+pub mod layout;
+pub mod links;
 pub mod log;
+pub mod outlog;
+pub mod pwm;
+pub mod responder;
+pub mod timing;
 
 use gpioregs::*;
+use layout::{LayoutError, ShmHeader};
 use shared_memory::*;
+use std::fmt;
 use std::path::Path;
 
 static GLOBAL_LOCK_ID: usize = 0;
@@ -26,44 +37,135 @@ impl ShMem {
     pub fn new(mem: SharedMem) -> Self {
         Self { mem }
     }
-    pub fn rlock<'a>(res: &'a Result<ShMem, SharedMemError>) -> ReadLockGuard<'_, RegMemory> {
+    pub fn rlock<'a>(res: &'a Result<ShMem, ShMemError>) -> ReadLockGuard<'_, RegMemory> {
         res.as_ref().unwrap().mem.rlock::<RegMemory>(GLOBAL_LOCK_ID).unwrap()
     }
-    pub fn wlock<'a>(res: &'a mut Result<ShMem, SharedMemError>) -> WriteLockGuard<'_, RegMemory> {
+    pub fn wlock<'a>(res: &'a mut Result<ShMem, ShMemError>) -> WriteLockGuard<'_, RegMemory> {
         res.as_mut().unwrap().mem.wlock::<RegMemory>(GLOBAL_LOCK_ID).unwrap()
     }
 }
 
-pub fn init_shared_memory() -> Result<ShMem, SharedMemError> {
-    _init_shared_memory(0)
+#[derive(Debug)]
+pub enum ShMemError {
+    SharedMem(SharedMemError),
+    Layout(LayoutError),
+}
+impl fmt::Display for ShMemError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShMemError::SharedMem(err) => write!(f, "{}", err),
+            ShMemError::Layout(err) => write!(f, "{}", err),
+        }
+    }
+}
+impl From<SharedMemError> for ShMemError {
+    fn from(err: SharedMemError) -> Self {
+        ShMemError::SharedMem(err)
+    }
+}
+impl From<LayoutError> for ShMemError {
+    fn from(err: LayoutError) -> Self {
+        ShMemError::Layout(err)
+    }
+}
+
+pub const SESSION_ENV_VAR: &str = "WPISIM_SESSION";
+const SESSION_NAME_MAX_LEN: usize = 32;
+const LINK_FILE_PREFIX: &str = "~simpi";
+const LINK_FILE_SUFFIX: &str = ".link";
+
+pub struct SessionInfo {
+    pub name: String,
+    pub link_path: String,
+    pub is_active: bool,
+}
+
+pub fn is_valid_session_name(name: &str) -> bool {
+    return name.len() <= SESSION_NAME_MAX_LEN
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+}
+
+// Returns the session name given by the environment (empty for the default
+// session).
+pub fn get_env_session_name() -> String {
+    std::env::var(SESSION_ENV_VAR).unwrap_or(String::new())
 }
 
-fn _init_shared_memory(n: usize) -> Result<ShMem, SharedMemError> {
-    let sh_path: String;
+// Returns the path of the SimPi user directory and creates it if necessary.
+pub fn get_simpi_dir() -> Option<String> {
     let win32_appdata = std::env::var("APPDATA").unwrap_or("#".to_owned());
     let linux_appdata = std::env::var("HOME").unwrap_or("#".to_owned());
-    if Path::new(&win32_appdata).exists() {
-        let simpi_dir = win32_appdata + "\\simpi";
-        if !Path::new(&simpi_dir).exists() {
-            match std::fs::create_dir(simpi_dir.clone()) {
-                Ok(_) => {},
-                Err(_) => return Err(SharedMemError::LinkDoesNotExist)
-            }
-        }
-        sh_path = simpi_dir + "\\~simpi.link";
+    let simpi_dir = if Path::new(&win32_appdata).exists() {
+        win32_appdata + "\\simpi"
     } else if Path::new(&linux_appdata).exists() {
-        let simpi_dir = linux_appdata + "/.simpi";
-        if !Path::new(&simpi_dir).exists() {
-            match std::fs::create_dir(simpi_dir.clone()) {
-                Ok(_) => {},
-                Err(_) => return Err(SharedMemError::LinkDoesNotExist)
-            }
+        linux_appdata + "/.simpi"
+    } else {
+        return None;
+    };
+    if !Path::new(&simpi_dir).exists() {
+        match std::fs::create_dir(simpi_dir.clone()) {
+            Ok(_) => {},
+            Err(_) => return None,
         }
-        sh_path = simpi_dir + "/~simpi.link";
+    }
+    Some(simpi_dir)
+}
+
+fn get_link_file_name(session: &str) -> String {
+    if session.is_empty() {
+        format!("{}{}", LINK_FILE_PREFIX, LINK_FILE_SUFFIX)
     } else {
-        return Err(SharedMemError::LinkDoesNotExist);
+        format!("{}.{}{}", LINK_FILE_PREFIX, session, LINK_FILE_SUFFIX)
     }
-    log::info("Attempting to create/open shared gpioregs mapping...");
+}
+
+// Lists all sessions which currently have a link file in the SimPi user
+// directory.
+pub fn list_sessions() -> Vec<SessionInfo> {
+    let mut sessions = vec![];
+    let simpi_dir = match get_simpi_dir() {
+        Some(dir) => dir,
+        None => return sessions,
+    };
+    let entries = match std::fs::read_dir(simpi_dir) {
+        Ok(entries) => entries,
+        Err(_) => return sessions,
+    };
+    for entry in entries.filter_map(|e| e.ok()) {
+        let file_name = entry.file_name().to_string_lossy().into_owned();
+        if !file_name.starts_with(LINK_FILE_PREFIX) || !file_name.ends_with(LINK_FILE_SUFFIX) {
+            continue;
+        }
+        let name = file_name[LINK_FILE_PREFIX.len()..file_name.len() - LINK_FILE_SUFFIX.len()]
+            .trim_start_matches('.').to_owned();
+        let link_path = entry.path().to_string_lossy().into_owned();
+        let is_active = SharedMem::open_linked(link_path.clone()).is_ok();
+        sessions.push(SessionInfo { name, link_path, is_active });
+    }
+    sessions.sort_by(|a, b| a.name.cmp(&b.name));
+    sessions
+}
+
+pub fn init_shared_memory(session: &str) -> Result<ShMem, ShMemError> {
+    _init_shared_memory(session, 0)
+}
+
+fn _init_shared_memory(session: &str, n: usize) -> Result<ShMem, ShMemError> {
+    if !is_valid_session_name(session) {
+        log::error(format!("Invalid session name '{}'!", session).as_ref());
+        return Err(ShMemError::SharedMem(SharedMemError::LinkCreateFailed(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("Invalid session name '{}'", session)
+        ))));
+    }
+    let sh_path = match get_simpi_dir() {
+        Some(simpi_dir) => {
+            Path::new(&simpi_dir).join(get_link_file_name(session))
+                .to_string_lossy().into_owned()
+        },
+        None => return Err(ShMemError::SharedMem(SharedMemError::LinkDoesNotExist)),
+    };
+    log::info(format!("Attempting to create/open shared gpioregs mapping '{}'...", sh_path).as_ref());
     let mut gpioregs = match SharedMem::create_linked(
         sh_path.clone(), LockType::Mutex, std::mem::size_of::<RegMemory>()
     ) {
@@ -76,30 +178,62 @@ fn _init_shared_memory(n: usize) -> Result<ShMem, SharedMemError> {
                 Err(SharedMemError::MapOpenFailed(err)) => {
                     if n == 0 {
                         std::fs::remove_file(sh_path.clone()).unwrap_or(());
-                        return _init_shared_memory(n + 1);
+                        return _init_shared_memory(session, n + 1);
                     } else {
-                        return Err(SharedMemError::MapOpenFailed(err));
+                        return Err(ShMemError::SharedMem(SharedMemError::MapOpenFailed(err)));
                     }
                 },
-                Err(err) => return Err(err),
+                Err(err) => return Err(ShMemError::SharedMem(err)),
             }
         },
-        Err(err) => return Err(err),
+        Err(err) => return Err(ShMemError::SharedMem(err)),
     };
 
     log::info(format!("Mapping info : {}", gpioregs).as_ref());
 
     if gpioregs.num_locks() != 1 {
         log::error("Expected to only have 1 lock in shared mapping!");
-        return Err(SharedMemError::InvalidHeader);
+        return Err(ShMemError::SharedMem(SharedMemError::InvalidHeader));
     } else {
         if gpioregs.is_owner() {
             let mut gpioregs = gpioregs.wlock::<RegMemory>(GLOBAL_LOCK_ID)?;
+            gpioregs.header = ShmHeader::new();
             gpioregs.reset();
             log::info("This process is owner of the shared mapping.");
         } else {
             log::info("This process is not owner of the shared mapping.");
+            // Check the header first, as the mapping may be too small to
+            // hold the RegMemory of this version.
+            let header = gpioregs.rlock::<ShmHeader>(GLOBAL_LOCK_ID)?.clone();
+            if let Err(err) = header.validate() {
+                log::error(format!("{}", err).as_ref());
+                return Err(ShMemError::Layout(err));
+            }
+            log::info(format!(
+                "Shared mapping has layout version {} with features {:#010X}.",
+                header.version(), header.features()
+            ).as_ref());
         }
     }
     Ok(ShMem::new(gpioregs))
 }
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn session__is_valid_session_name() {
+        assert!(is_valid_session_name(""));
+        assert!(is_valid_session_name("ci-job_42"));
+        assert!(!is_valid_session_name("../escape"));
+        assert!(!is_valid_session_name(&"x".repeat(SESSION_NAME_MAX_LEN + 1)));
+    }
+
+    #[test]
+    fn session__get_link_file_name() {
+        assert_eq!(get_link_file_name(""), "~simpi.link");
+        assert_eq!(get_link_file_name("lab1"), "~simpi.lab1.link");
+    }
+}
@@ -0,0 +1,73 @@
+/*!links.rs
+ * Module File for the pin links. Pins connected by a closed contact, e.g.
+ * the pressed key of a key matrix, are linked: an input pin linked to an
+ * output pin reads the level of the output. The links are set by the broker
+ * and resolved by the client on each read, so scanning a matrix works at
+ * any speed.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+#[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
+pub struct PinLinks {
+    // Bit mask of the pins each pin is linked to
+    links: [u32; 32],
+}
+impl Copy for PinLinks {}
+impl Clone for PinLinks {
+    fn clone(&self) -> Self {
+        PinLinks {
+            links: self.links,
+        }
+    }
+}
+impl PinLinks {
+    pub fn new() -> PinLinks {
+        return PinLinks {
+            links: [0; 32],
+        };
+    }
+    pub fn reset(&mut self) {
+        *self = PinLinks::new();
+    }
+    pub fn link(&mut self, a: u8, b: u8) {
+        if a < 32 && b < 32 && a != b {
+            self.links[a as usize] |= 0x1 << b;
+            self.links[b as usize] |= 0x1 << a;
+        }
+    }
+    // Removes all links of the pins in the mask.
+    pub fn unlink(&mut self, mask: u32) {
+        for pin in 0..32 {
+            if (mask >> pin) & 0x1 != 0 {
+                self.links[pin] = 0;
+            } else {
+                self.links[pin] &= !mask;
+            }
+        }
+    }
+    pub fn linked(&self, pin: u8) -> u32 {
+        return self.links.get(pin as usize).cloned().unwrap_or(0);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn PinLinks__unlink() {
+        let mut links = PinLinks::new();
+        links.link(5, 17);
+        links.link(6, 17);
+        links.link(6, 6);
+        assert_eq!(links.linked(17), (0x1 << 5) | (0x1 << 6));
+        assert_eq!(links.linked(6), 0x1 << 17);
+        links.unlink(0x1 << 5);
+        assert_eq!(links.linked(5), 0);
+        assert_eq!(links.linked(17), 0x1 << 6);
+    }
+}
@@ -0,0 +1,182 @@
+/*!outlog.rs
+ * Module File for the log of OUTPUT register changes. Lets the broker see
+ * changes which happen faster than it polls the registers, e.g. multiplexed
+ * displays.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+pub const OUTPUT_LOG_LEN: usize =   1024;
+
+// Returns the current time in microseconds since the UNIX epoch, see
+// clients::unix_time_ms() for why the system clock is used.
+pub fn unix_time_us() -> u64 {
+    match std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH) {
+        Ok(duration) => duration.as_micros() as u64,
+        Err(_) => 0,
+    }
+}
+
+#[derive(shared_memory::SharedMemCast, Debug, PartialEq)]
+#[repr(C)]
+pub struct OutputEvent {
+    pub time_us: u64,
+    // Value of the OUTPUT register after the change
+    pub value: u32,
+    _reserved: u32,
+}
+impl Copy for OutputEvent {}
+impl Clone for OutputEvent {
+    fn clone(&self) -> Self {
+        OutputEvent {
+            time_us:    self.time_us,
+            value:      self.value,
+            _reserved:  0,
+        }
+    }
+}
+impl OutputEvent {
+    pub fn new(time_us: u64, value: u32) -> OutputEvent {
+        return OutputEvent { time_us, value, _reserved: 0 };
+    }
+}
+
+// Ring buffer of the latest OUTPUT register changes. Readers keep their own
+// sequence number, so any number of readers can follow the log.
+#[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
+pub struct OutputLog {
+    // Number of events pushed since the log has been created
+    seq: u64,
+    events: [OutputEvent; OUTPUT_LOG_LEN],
+}
+impl Copy for OutputLog {}
+impl Clone for OutputLog {
+    fn clone(&self) -> Self {
+        OutputLog {
+            seq:    self.seq,
+            events: self.events,
+        }
+    }
+}
+impl OutputLog {
+    pub fn new() -> OutputLog {
+        return OutputLog {
+            seq:    0,
+            events: [OutputEvent::new(0, 0); OUTPUT_LOG_LEN],
+        };
+    }
+    pub fn seq(&self) -> u64 {
+        return self.seq;
+    }
+    pub fn push(&mut self, time_us: u64, value: u32) {
+        self.events[(self.seq % OUTPUT_LOG_LEN as u64) as usize] = OutputEvent::new(time_us, value);
+        self.seq += 1;
+    }
+    // Returns all events pushed after the given sequence number and advances
+    // it. If more events have been pushed than the log can hold, only the
+    // latest OUTPUT_LOG_LEN events are returned.
+    pub fn read_since(&self, seq: &mut u64) -> Vec<OutputEvent> {
+        let start = if *seq > self.seq {
+            // The log has been recreated since the last read
+            self.seq
+        } else {
+            std::cmp::max(*seq, self.seq.saturating_sub(OUTPUT_LOG_LEN as u64))
+        };
+        let events = (start..self.seq)
+            .map(|i| self.events[(i % OUTPUT_LOG_LEN as u64) as usize])
+            .collect();
+        *seq = self.seq;
+        return events;
+    }
+}
+
+// Follows the log in time windows from one read to the next, for parts which
+// show what the human eye would see of quickly changing outputs.
+#[derive(Clone, Copy, Default)]
+pub struct OutputWindow {
+    seq: u64,
+    // Start time and OUTPUT register value of the current window, a start
+    // time of 0 means no window has been read yet
+    start_us: u64,
+    output: u32,
+}
+impl OutputWindow {
+    pub fn new() -> OutputWindow {
+        return OutputWindow::default();
+    }
+    // Returns the window since the last read as segments of constant OUTPUT
+    // register value, see segments(). The first read returns the given
+    // current value only, as older events of the log belong to no window.
+    pub fn read(&mut self, log: &OutputLog, output: u32) -> Vec<(u32, u64)> {
+        let now_us = unix_time_us();
+        let events = log.read_since(&mut self.seq);
+        let segments = if self.start_us == 0 {
+            segments(now_us, now_us, output, &[])
+        } else {
+            segments(self.start_us, now_us, self.output, &events)
+        };
+        self.start_us = now_us;
+        self.output = output;
+        return segments;
+    }
+}
+
+// Splits the time window into (OUTPUT register value, duration in µs), given
+// the value at the start of the window and its changes during the window. The
+// last value counts at least a bit, so an empty window still yields it.
+fn segments(start_us: u64, end_us: u64, output: u32, events: &[OutputEvent]) -> Vec<(u32, u64)> {
+    let mut segments = vec![];
+    let mut t = start_us;
+    let mut output = output;
+    for event in events.iter() {
+        if event.time_us > t {
+            segments.push((output, event.time_us - t));
+            t = event.time_us;
+        }
+        output = event.value;
+    }
+    segments.push((output, std::cmp::max(end_us.saturating_sub(t), 1)));
+    return segments;
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn OutputLog__read_since() {
+        let mut log = OutputLog::new();
+        let mut seq = 0;
+        log.push(10, 0x1);
+        log.push(20, 0x3);
+        assert_eq!(log.read_since(&mut seq), vec![OutputEvent::new(10, 0x1), OutputEvent::new(20, 0x3)]);
+        assert_eq!(seq, 2);
+        assert!(log.read_since(&mut seq).is_empty());
+        for i in 0..(OUTPUT_LOG_LEN as u64 + 5) {
+            log.push(100 + i, i as u32);
+        }
+        let events = log.read_since(&mut seq);
+        assert_eq!(events.len(), OUTPUT_LOG_LEN);
+        assert_eq!(events[0].time_us, 105);
+        assert_eq!(seq, log.seq());
+    }
+
+    #[test]
+    fn OutputWindow__read() {
+        let mut log = OutputLog::new();
+        let mut window = OutputWindow::new();
+        log.push(10, 0x1);
+        assert_eq!(window.read(&log, 0x1), vec![(0x1, 1)]);
+        // Events before the window and changes within the same microsecond
+        // take no time
+        let events = [
+            OutputEvent::new(90, 0x2), OutputEvent::new(120, 0x3), OutputEvent::new(120, 0x4),
+            OutputEvent::new(150, 0x5),
+        ];
+        assert_eq!(segments(100, 200, 0x1, &events), vec![(0x2, 20), (0x4, 30), (0x5, 50)]);
+        assert_eq!(segments(100, 150, 0x1, &events), vec![(0x2, 20), (0x4, 30), (0x5, 1)]);
+    }
+}
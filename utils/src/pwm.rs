@@ -0,0 +1,122 @@
+/*!pwm.rs
+ * Module File for the PWM registers. PWM pins are not toggled by wpisim,
+ * instead the duty cycle of each pin is stored for the broker.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+// Default range of the hardware PWM, same as in wiringPi.
+pub const PWM_DEFAULT_RANGE: u32 =  1024;
+
+#[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
+pub struct PwmRegs {
+    // 1=PWM enabled 0=Pin is a normal in- or output
+    enabled: u32,
+    _reserved: u32,
+    // Value written by pwmWrite()/softPwmWrite() of each pin
+    value: [u32; 32],
+    // Value which corresponds to a duty cycle of 100% of each pin
+    range: [u32; 32],
+    // Length of a PWM cycle (in ns) of each pin, 0 if unknown
+    period_ns: [u32; 32],
+}
+impl Copy for PwmRegs {}
+impl Clone for PwmRegs {
+    fn clone(&self) -> Self {
+        PwmRegs {
+            enabled:    self.enabled,
+            _reserved:  0,
+            value:      self.value,
+            range:      self.range,
+            period_ns:  self.period_ns,
+        }
+    }
+}
+impl PwmRegs {
+    pub fn new() -> PwmRegs {
+        return PwmRegs {
+            enabled:    0,
+            _reserved:  0,
+            value:      [0; 32],
+            range:      [PWM_DEFAULT_RANGE; 32],
+            period_ns:  [0; 32],
+        };
+    }
+    pub fn reset(&mut self) {
+        *self = PwmRegs::new();
+    }
+    pub fn enable(&mut self, pin: u8, range: u32) {
+        self.enabled |= 0x1u32 << pin;
+        self.value[pin as usize] = 0;
+        self.range[pin as usize] = range;
+    }
+    pub fn disable(&mut self, pin: u8) {
+        self.enabled &= !(0x1u32 << pin);
+    }
+    pub fn is_enabled(&self, pin: u8) -> bool {
+        return ((self.enabled >> pin) & 0x1) != 0;
+    }
+    pub fn write(&mut self, pin: u8, value: u32) {
+        self.value[pin as usize] = value;
+    }
+    pub fn range(&self, pin: u8) -> u32 {
+        return self.range[pin as usize];
+    }
+    pub fn set_range(&mut self, pin: u8, range: u32) {
+        self.range[pin as usize] = range;
+    }
+    pub fn set_period_ns(&mut self, pin: u8, period_ns: u32) {
+        self.period_ns[pin as usize] = period_ns;
+    }
+    // Duty cycle (0.0 - 1.0) of the pin, None if PWM is not enabled on it.
+    pub fn duty(&self, pin: u8) -> Option<f64> {
+        if !self.is_enabled(pin) {
+            return None;
+        }
+        let range = self.range[pin as usize];
+        if range == 0 {
+            return Some(0.0);
+        }
+        return Some(self.value[pin as usize].min(range) as f64 / range as f64);
+    }
+    // Length of the high pulse (in us) of the pin, None if PWM is not
+    // enabled on it or its period is unknown.
+    pub fn pulse_us(&self, pin: u8) -> Option<f64> {
+        let period_ns = self.period_ns[pin as usize];
+        if period_ns == 0 {
+            return None;
+        }
+        return self.duty(pin).map(|duty| duty * period_ns as f64 / 1000.0);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn PwmRegs__duty() {
+        let mut pwm = PwmRegs::new();
+        assert_eq!(pwm.duty(18), None);
+        pwm.enable(18, 100);
+        pwm.write(18, 25);
+        assert_eq!(pwm.duty(18), Some(0.25));
+        pwm.write(18, 500);
+        assert_eq!(pwm.duty(18), Some(1.0));
+        pwm.disable(18);
+        assert_eq!(pwm.duty(18), None);
+    }
+
+    #[test]
+    fn PwmRegs__pulse_us() {
+        let mut pwm = PwmRegs::new();
+        pwm.enable(18, 2000);
+        pwm.write(18, 150);
+        assert_eq!(pwm.pulse_us(18), None);
+        pwm.set_period_ns(18, 20_000_000);
+        assert_eq!(pwm.pulse_us(18), Some(1500.0));
+    }
+}
@@ -0,0 +1,251 @@
+/*!responder.rs
+ * Module File for the responder slots. Parts with a bidirectional single-wire
+ * protocol (e.g. DHT sensors) answer the start signal of the program with a
+ * bit sequence timed in microseconds, which the broker cannot poll fast
+ * enough. The broker sets up the slot of the pin and the data to send, the
+ * client detects the start signal when the program writes the pin and works
+ * out the level of the response on each read.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+use std::iter;
+
+pub const RESPONDER_NONE: u8 =      0;
+pub const RESPONDER_DHT11: u8 =     1;
+pub const RESPONDER_DHT22: u8 =     2;
+// Humidity (2 bytes), temperature (2 bytes) and checksum
+pub const DHT_DATA_LEN: usize =     5;
+
+// Timing of the DHT response (in us), starting when the program releases
+// the line: the sensor waits, acknowledges with a low and a high pulse and
+// sends each bit as a low pulse followed by a short (0) or long (1) high
+// pulse. A last low pulse ends the response.
+const DHT_WAIT_US: u64 =            30;
+const DHT_ACK_US: u64 =             80;
+const DHT_BIT_LOW_US: u64 =         50;
+const DHT_BIT_0_US: u64 =           27;
+const DHT_BIT_1_US: u64 =           70;
+
+const LOW: u8 =                     0;
+const HIGH: u8 =                    1;
+
+#[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
+pub struct Responder {
+    // Protocol of the part on this pin, set by the broker
+    kind: u8,
+    // Data to send on the next start signal, set by the broker
+    data: [u8; DHT_DATA_LEN],
+    _reserved: [u8; 2],
+    // Data of the running response, latched by the client on the start signal
+    latched: [u8; DHT_DATA_LEN],
+    _reserved2: [u8; 3],
+    // Time the program has started to drive the line low, 0 if it does not
+    low_since_us: u64,
+    // Time the program has released the line after a valid start signal, 0
+    // if no response has been started
+    start_us: u64,
+    // Number of responses since the slot has been set up
+    responses: u32,
+    _reserved3: u32,
+}
+impl Copy for Responder {}
+impl Clone for Responder {
+    fn clone(&self) -> Self {
+        Responder {
+            kind:           self.kind,
+            data:           self.data,
+            _reserved:      [0; 2],
+            latched:        self.latched,
+            _reserved2:     [0; 3],
+            low_since_us:   self.low_since_us,
+            start_us:       self.start_us,
+            responses:      self.responses,
+            _reserved3:     0,
+        }
+    }
+}
+impl Responder {
+    pub fn new() -> Responder {
+        return Responder {
+            kind:           RESPONDER_NONE,
+            data:           [0; DHT_DATA_LEN],
+            _reserved:      [0; 2],
+            latched:        [0; DHT_DATA_LEN],
+            _reserved2:     [0; 3],
+            low_since_us:   0,
+            start_us:       0,
+            responses:      0,
+            _reserved3:     0,
+        };
+    }
+    // Shortest low pulse (in us) the sensor accepts as start signal.
+    fn start_min_us(&self) -> u64 {
+        return match self.kind {
+            RESPONDER_DHT11 => 18_000,
+            _ => 1_000,
+        };
+    }
+}
+
+// Level of the line the given time (in us) after the start of a DHT
+// response, None once the response has ended.
+fn dht_level(data: &[u8; DHT_DATA_LEN], time_us: u64) -> Option<u8> {
+    let bits = (0..DHT_DATA_LEN * 8).flat_map(|i| {
+        let bit = (data[i / 8] >> (7 - i % 8)) & 0x1;
+        iter::once((LOW, DHT_BIT_LOW_US))
+            .chain(iter::once((HIGH, if bit == 1 { DHT_BIT_1_US } else { DHT_BIT_0_US })))
+    });
+    let mut time_us = time_us;
+    let pulses = [(HIGH, DHT_WAIT_US), (LOW, DHT_ACK_US), (HIGH, DHT_ACK_US)].iter().cloned()
+        .chain(bits)
+        .chain(iter::once((LOW, DHT_BIT_LOW_US)));
+    for (level, duration_us) in pulses {
+        if time_us < duration_us {
+            return Some(level);
+        }
+        time_us -= duration_us;
+    }
+    return None;
+}
+
+// Running response, the client keeps a copy to work out the level without
+// locking the shared memory on each read.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Response {
+    data: [u8; DHT_DATA_LEN],
+    start_us: u64,
+}
+impl Response {
+    // Level of the line, None once the response has ended.
+    pub fn level(&self, now_us: u64) -> Option<u8> {
+        return dht_level(&self.data, now_us.saturating_sub(self.start_us));
+    }
+}
+
+#[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
+pub struct Responders {
+    // Slot of each pin
+    slots: [Responder; 32],
+}
+impl Copy for Responders {}
+impl Clone for Responders {
+    fn clone(&self) -> Self {
+        Responders {
+            slots: self.slots,
+        }
+    }
+}
+impl Responders {
+    pub fn new() -> Responders {
+        return Responders {
+            slots: [Responder::new(); 32],
+        };
+    }
+    pub fn reset(&mut self) {
+        *self = Responders::new();
+    }
+    // Sets up the slot of the pin, the state of the client is kept unless
+    // the protocol changes.
+    pub fn configure(&mut self, pin: u8, kind: u8, data: [u8; DHT_DATA_LEN]) {
+        if let Some(slot) = self.slots.get_mut(pin as usize) {
+            if slot.kind != kind {
+                *slot = Responder::new();
+                slot.kind = kind;
+            }
+            slot.data = data;
+        }
+    }
+    // Clears the slots of all pins which are not in the mask.
+    pub fn retain(&mut self, mask: u32) {
+        for (pin, slot) in self.slots.iter_mut().enumerate() {
+            if (mask >> pin) & 0x1 == 0 && slot.kind != RESPONDER_NONE {
+                *slot = Responder::new();
+            }
+        }
+    }
+    pub fn responses(&self, pin: u8) -> u32 {
+        return self.slots.get(pin as usize).map_or(0, |slot| slot.responses);
+    }
+    // Called by the client whenever the level the program drives the line
+    // with changes, HIGH also for releasing it (the pull-up takes over).
+    pub fn drive(&mut self, pin: u8, level: u8, now_us: u64) {
+        let slot = match self.slots.get_mut(pin as usize) {
+            Some(slot) if slot.kind != RESPONDER_NONE => slot,
+            _ => return,
+        };
+        if level == LOW {
+            // Pulling the line low aborts a running response
+            if slot.low_since_us == 0 {
+                slot.low_since_us = now_us;
+            }
+            slot.start_us = 0;
+        } else if slot.low_since_us != 0 {
+            if now_us.saturating_sub(slot.low_since_us) >= slot.start_min_us() {
+                slot.start_us = now_us;
+                slot.latched = slot.data;
+                slot.responses = slot.responses.wrapping_add(1);
+            }
+            slot.low_since_us = 0;
+        }
+    }
+    // Response started by the last start signal on the pin, None if the
+    // program has not sent one or pulls the line low again.
+    pub fn response(&self, pin: u8) -> Option<Response> {
+        let slot = self.slots.get(pin as usize)?;
+        if slot.kind == RESPONDER_NONE || slot.start_us == 0 {
+            return None;
+        }
+        return Some(Response { data: slot.latched, start_us: slot.start_us });
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn Responders__response() {
+        let mut responders = Responders::new();
+        let data = [0x02, 0x8C, 0x01, 0x5F, 0xEE];
+        responders.configure(4, RESPONDER_DHT22, data);
+        // Too short for a start signal
+        responders.drive(4, LOW, 1_000);
+        responders.drive(4, HIGH, 1_500);
+        assert_eq!(responders.response(4), None);
+        responders.drive(4, LOW, 10_000);
+        responders.drive(4, HIGH, 12_000);
+        assert_eq!(responders.responses(4), 1);
+        let response = responders.response(4).unwrap();
+        // Decode the bits from the length of the high pulses, sampled each us
+        let mut now_us = 12_000;
+        let mut highs = vec![];
+        let mut last = HIGH;
+        let mut since_us = now_us;
+        while let Some(level) = response.level(now_us) {
+            if level != last {
+                if last == HIGH {
+                    highs.push(now_us - since_us);
+                }
+                last = level;
+                since_us = now_us;
+            }
+            now_us += 1;
+        }
+        // The wait and the acknowledge pulse come first
+        assert_eq!(highs[0..2], [DHT_WAIT_US, DHT_ACK_US]);
+        let mut decoded = [0u8; DHT_DATA_LEN];
+        for (i, &high_us) in highs[2..].iter().enumerate() {
+            decoded[i / 8] = (decoded[i / 8] << 1) | (high_us > 50) as u8;
+        }
+        assert_eq!(decoded, data);
+        // Other pins are not affected
+        assert_eq!(responders.response(5), None);
+        responders.retain(0);
+        assert_eq!(responders.response(4), None);
+    }
+}
@@ -0,0 +1,136 @@
+/*!timing.rs
+ * Module File for the delay timing statistics of a simulated program.
+ * 
+ * Author: Patrick Goldinger
+ * License: GPL 3.0 (see LICENSE file for details)
+ */
+
+// Minimum accuracy (in percent) for the timing to be considered reliable.
+pub const TIMING_RELIABLE_ACCURACY: f64 = 95.0;
+
+#[derive(shared_memory::SharedMemCast)]
+#[repr(C)]
+pub struct TimingStats {
+    // Number of recorded delays
+    count: u64,
+    // Sum of the accuracies of all delays (requested / actual duration) in
+    // parts per million. Each delay counts the same, so long delays cannot
+    // hide the overshoot of short ones.
+    accuracy_ppm: u64,
+    // Sum of all overshoots (actual - requested) in microseconds
+    overshoot_us: u64,
+    // Largest single overshoot in microseconds
+    max_overshoot_us: u64,
+}
+impl Copy for TimingStats {}
+impl Clone for TimingStats {
+    fn clone(&self) -> Self {
+        TimingStats {
+            count:              self.count,
+            accuracy_ppm:       self.accuracy_ppm,
+            overshoot_us:       self.overshoot_us,
+            max_overshoot_us:   self.max_overshoot_us,
+        }
+    }
+}
+impl TimingStats {
+    pub fn new() -> TimingStats {
+        return TimingStats {
+            count:              0,
+            accuracy_ppm:       0,
+            overshoot_us:       0,
+            max_overshoot_us:   0,
+        };
+    }
+    pub fn reset(&mut self) {
+        *self = TimingStats::new();
+    }
+    pub fn record(&mut self, requested_us: u64, actual_us: u64) {
+        let overshoot = actual_us.saturating_sub(requested_us);
+        self.count += 1;
+        self.accuracy_ppm += if requested_us == 0 {
+            1_000_000
+        } else {
+            1_000_000 * requested_us / (requested_us + overshoot)
+        };
+        self.overshoot_us += overshoot;
+        if overshoot > self.max_overshoot_us {
+            self.max_overshoot_us = overshoot;
+        }
+    }
+    // Adds the delays recorded by the given stats.
+    pub fn merge(&mut self, other: &TimingStats) {
+        self.count += other.count;
+        self.accuracy_ppm += other.accuracy_ppm;
+        self.overshoot_us += other.overshoot_us;
+        if other.max_overshoot_us > self.max_overshoot_us {
+            self.max_overshoot_us = other.max_overshoot_us;
+        }
+    }
+    pub fn count(&self) -> u64 {
+        return self.count;
+    }
+    pub fn avg_overshoot_us(&self) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        return self.overshoot_us / self.count;
+    }
+    pub fn max_overshoot_us(&self) -> u64 {
+        return self.max_overshoot_us;
+    }
+    // Average ratio of requested to actually waited time, in percent.
+    pub fn accuracy(&self) -> f64 {
+        if self.count == 0 {
+            return 100.0;
+        }
+        return self.accuracy_ppm as f64 / self.count as f64 / 10_000.0;
+    }
+    pub fn is_reliable(&self) -> bool {
+        return self.accuracy() >= TIMING_RELIABLE_ACCURACY;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn TimingStats__record() {
+        let mut stats = TimingStats::new();
+        stats.record(50, 51);
+        stats.record(1000, 1063);
+        assert_eq!(stats.count(), 2);
+        assert_eq!(stats.avg_overshoot_us(), 32);
+        assert_eq!(stats.max_overshoot_us(), 63);
+    }
+
+    #[test]
+    fn TimingStats__accuracy() {
+        let mut stats = TimingStats::new();
+        assert!(stats.is_reliable());
+        stats.record(100, 160);
+        assert!(!stats.is_reliable());
+        stats.reset();
+        stats.record(20000, 20080);
+        assert!(stats.is_reliable());
+        // A long delay does not hide the overshoot of short ones
+        stats.record(10, 20);
+        stats.record(10, 20);
+        assert!(!stats.is_reliable());
+    }
+
+    #[test]
+    fn TimingStats__merge() {
+        let mut stats = TimingStats::new();
+        let mut pending = TimingStats::new();
+        stats.record(50, 51);
+        pending.record(100, 200);
+        pending.record(1000, 1063);
+        stats.merge(&pending);
+        assert_eq!(stats.count(), 3);
+        assert_eq!(stats.max_overshoot_us(), 100);
+        assert_eq!(stats.avg_overshoot_us(), 54);
+    }
+}
@@ -16,10 +16,24 @@ lazy_static! {
     static ref CORE: Mutex<lsim::LSimCore> = Mutex::new(lsim::LSimCore::new());
 }
 
+extern "C" {
+    fn atexit(callback: extern "C" fn()) -> i32;
+}
+
+extern "C" fn wpisim_teardown() {
+    // Don't block the exit of the program if the core is still in use
+    if let Ok(mut core) = CORE.try_lock() {
+        core.teardown();
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn wiringPiSetupGpio() -> i32 {
     let mut core = CORE.lock().unwrap();
     core.setup();
+    unsafe {
+        atexit(wpisim_teardown);
+    }
     return 0;
 }
 
@@ -29,12 +43,83 @@ pub extern "C" fn pinMode(pin: i32, pud: i32) {
     core.pin_mode(pin as u8, pud as u8);
 }
 
+#[no_mangle]
+pub extern "C" fn pullUpDnControl(pin: i32, pud: i32) {
+    let mut core = CORE.lock().unwrap();
+    core.pull_up_dn_control(pin as u8, pud as u8);
+}
+
 #[no_mangle]
 pub extern "C" fn digitalWrite(pin: i32, value: i32) {
     let mut core = CORE.lock().unwrap();
     core.write_pin(pin as u8, value as u8);
 }
 
+#[no_mangle]
+pub extern "C" fn pwmWrite(pin: i32, value: i32) {
+    let mut core = CORE.lock().unwrap();
+    core.pwm_write(pin as u8, value.max(0) as u32);
+}
+
+#[no_mangle]
+pub extern "C" fn pwmSetRange(range: u32) {
+    let mut core = CORE.lock().unwrap();
+    core.pwm_set_range(range);
+}
+
+// The PWM mode only changes how the pulses are spread over a cycle, which is
+// not simulated.
+#[no_mangle]
+pub extern "C" fn pwmSetMode(_mode: i32) {}
+
+#[no_mangle]
+pub extern "C" fn pwmSetClock(divisor: i32) {
+    let mut core = CORE.lock().unwrap();
+    core.pwm_set_clock(divisor.max(0) as u32);
+}
+
+#[no_mangle]
+pub extern "C" fn softPwmCreate(pin: i32, value: i32, range: i32) -> i32 {
+    let mut core = CORE.lock().unwrap();
+    return core.soft_pwm_create(pin as u8, value.max(0) as u32, range.max(0) as u32);
+}
+
+#[no_mangle]
+pub extern "C" fn softPwmWrite(pin: i32, value: i32) {
+    let mut core = CORE.lock().unwrap();
+    core.pwm_write(pin as u8, value.max(0) as u32);
+}
+
+#[no_mangle]
+pub extern "C" fn softPwmStop(pin: i32) {
+    let mut core = CORE.lock().unwrap();
+    core.soft_pwm_stop(pin as u8);
+}
+
+#[no_mangle]
+pub extern "C" fn analogRead(pin: i32) -> i32 {
+    let core = CORE.lock().unwrap();
+    return core.analog_read(pin);
+}
+
+// The DAC of the PCF8591 is not simulated.
+#[no_mangle]
+pub extern "C" fn analogWrite(_pin: i32, _value: i32) {}
+
+// MCP3004/MCP3008: 10 bit SPI ADC, pins pinBase to pinBase+7
+#[no_mangle]
+pub extern "C" fn mcp3004Setup(pinBase: i32, _spiChannel: i32) -> i32 {
+    let mut core = CORE.lock().unwrap();
+    return core.adc_setup(pinBase, 8, 10);
+}
+
+// PCF8591: 8 bit I2C ADC, pins pinBase to pinBase+3
+#[no_mangle]
+pub extern "C" fn pcf8591Setup(pinBase: i32, _i2cAddress: i32) -> i32 {
+    let mut core = CORE.lock().unwrap();
+    return core.adc_setup(pinBase, 4, 8);
+}
+
 #[no_mangle]
 pub extern "C" fn digitalRead(pin: i32) -> i32 {
     let core = CORE.lock().unwrap();
@@ -49,14 +134,17 @@ pub extern "C" fn wiringPiISR(pin: i32, mode: i32, function: extern "C" fn()) {
 
 #[no_mangle]
 pub extern "C" fn delay(howLong: u32) {
-    let core = CORE.lock().unwrap();
-    core.delay_ms(howLong as u64);
+    let actual = lsim::LSimCore::delay_ms(howLong as u64);
+    lsim::LSimCore::record_delay(howLong as u64 * 1000, actual);
 }
 
 #[no_mangle]
 pub extern "C" fn delayMicroseconds(howLong: u32) {
-    let core = CORE.lock().unwrap();
-    core.delay_us(howLong as u64);
+    if howLong == 0 {
+        return;
+    }
+    let actual = lsim::LSimCore::delay_us(howLong as u64);
+    lsim::LSimCore::record_delay(howLong as u64, actual);
 }
 
 #[no_mangle]
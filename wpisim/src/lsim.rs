@@ -8,15 +8,31 @@
 use std::{thread, time, time::Duration};
 use std::sync::{Arc, Mutex};
 use utils::{
+    clients::unix_time_ms,
     gpioregs::*,
+    inlog::INPUT_EVENT_LEAD_US,
     log,
-    shared_memory::*,
+    outlog::unix_time_us,
+    pwm::PWM_DEFAULT_RANGE,
+    responder::Response,
+    timing::TimingStats,
     ShMem,
+    ShMemError,
 };
 
 const INPUT: u8 =               0;
 const OUTPUT: u8 =              1;
 const PWM_OUTPUT: u8 =          2;
+const SOFT_PWM_OUTPUT: u8 =     4;
+// Range of software PWM pins set up by pinMode(), same as in wiringPi.
+const SOFT_PWM_RANGE: u32 =     100;
+// Length of a step of software PWM (in ns), same as in wiringPi.
+const SOFT_PWM_STEP_NS: u64 =   100_000;
+// Frequency of the oscillator the hardware PWM clock is divided from.
+const PWM_OSC_HZ: u64 =         19_200_000;
+// Divisor of the hardware PWM clock until pwmSetClock() is called.
+const PWM_DEFAULT_CLOCK: u32 =  32;
+const PUD_OFF: u8 =             0;
 const LOW: u8 =                 0;
 const HIGH: u8 =                1;
 const INT_EDGE_SETUP: u8 =      0;
@@ -25,73 +41,202 @@ const INT_EDGE_RISING: u8 =     2;
 const INT_EDGE_BOTH: u8 =       3;
 const MIN_PIN_NUM: u8 =         2;
 const MAX_PIN_NUM: u8 =         27;
+// Interval (in us) the INPUT register is checked for interrupts at.
+const ISR_POLL_US: u64 =        INPUT_EVENT_LEAD_US;
+// Pins below this are GPIO pins, pins of extension nodes start here.
+const NODE_PIN_BASE_MIN: i32 =  64;
+// Delays below this limit (in us) are busy-waited, just like in wiringPi.
+const DELAY_US_HARD_LIMIT: u64 = 100;
 
 static GLOBAL_LOCK_ID: usize = 0;
 
+lazy_static! {
+    // Delays recorded since the last heartbeat, so delays take neither the
+    // core nor the shared memory lock
+    static ref PENDING_TIMING: Mutex<TimingStats> = Mutex::new(TimingStats::new());
+}
+
 pub struct LSimCore {
-    pub reg_memory: Arc<Mutex<Result<ShMem, SharedMemError>>>,
+    pub reg_memory: Arc<Mutex<Result<ShMem, ShMemError>>>,
     pub start_time_us: time::Instant,
     pub isr_routines: Arc<Mutex<[Option<extern "C" fn()>; 32]>>,
     pub is_thread_valid: bool,
+    pub client_slot: Arc<Mutex<Option<usize>>>,
+    // Range set by pwmSetRange() and clock divisor set by pwmSetClock(), used
+    // by all hardware PWM pins
+    pub pwm_range: u32,
+    pub pwm_clock: u32,
+    // 1=Software PWM 0=Hardware PWM (ignored if PWM is disabled!)
+    pub soft_pwm_pins: u32,
+    pub adc_nodes: Vec<AdcNode>,
+    // Running response of each pin's responder, read without the lock
+    pub responses: [Option<Response>; 32],
+}
+
+// ADC chip set up by mcp3004Setup() or pcf8591Setup(). Its pins start at
+// pin_base and read the channels of the simulated ADC.
+#[derive(Clone, Copy)]
+pub struct AdcNode {
+    pub pin_base: i32,
+    pub channels: u8,
+    pub bits: u8,
+}
+
+fn get_program_name() -> String {
+    let arg0 = std::env::args().next().unwrap_or(String::from("unknown"));
+    return std::path::Path::new(&arg0).file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or(arg0);
+}
+// Returns whether a change of the pin from old to new triggers its interrupt.
+fn is_interrupt(reg_memory: &RegMemory, pin: u8, old: u8, new: u8) -> bool {
+    if reg_memory.inten.read_pin(pin) == 0 || old == new {
+        return false;
+    }
+    let v_int0 = reg_memory.int0.read_pin(pin) == 1;
+    let v_int1 = reg_memory.int1.read_pin(pin) == 1;
+    return match (v_int1, v_int0) {
+        // rising edge
+        (true, true) => new == HIGH,
+        // falling edge
+        (true, false) => new == LOW,
+        // logical change
+        (false, true) => true,
+        // low level (not supported)
+        (false, false) => false,
+    };
+}
+
+// Length of a PWM cycle (in ns), limited to what the PWM registers hold.
+fn pwm_period_ns(period_ns: u64) -> u32 {
+    return std::cmp::min(period_ns, u32::max_value() as u64) as u32;
+}
+
+// Writes a pin of the OUTPUT register and logs the change.
+fn write_output(reg_memory: &mut RegMemory, pin: u8, val: u8) {
+    let old_value = reg_memory.output.read();
+    reg_memory.output.write_pin(pin, val);
+    let value = reg_memory.output.read();
+    if value != old_value {
+        reg_memory.output_log.push(unix_time_us(), value);
+    }
+    drive_responder(reg_memory, pin);
+}
+
+// Passes the level the program drives the pin with to its responder, an
+// input releases the line, so the pull-up of the part makes it high.
+fn drive_responder(reg_memory: &mut RegMemory, pin: u8) {
+    let level = if reg_memory.config.read_pin(pin) == 1 { HIGH } else { reg_memory.output.read_pin(pin) };
+    reg_memory.responders.drive(pin, level, unix_time_us());
 }
 impl LSimCore {
     pub fn new() -> LSimCore {
         log::init("wpisim");
+        let reg_memory = utils::init_shared_memory(&utils::get_env_session_name());
+        if let Err(err) = reg_memory.as_ref() {
+            log::error(format!("Failed to attach to the broker: {}", err).as_ref());
+        }
         return LSimCore {
-            reg_memory: Arc::new(Mutex::new(utils::init_shared_memory())),
+            reg_memory: Arc::new(Mutex::new(reg_memory)),
             start_time_us: time::Instant::now(),
             isr_routines: Arc::new(Mutex::new([None; 32])),
             is_thread_valid: false,
+            client_slot: Arc::new(Mutex::new(None)),
+            pwm_range: PWM_DEFAULT_RANGE,
+            pwm_clock: PWM_DEFAULT_CLOCK,
+            soft_pwm_pins: 0,
+            adc_nodes: vec![],
+            responses: [None; 32],
         }
     }
 
     pub fn setup(&mut self) -> i32 {
         log::info("Init wpisim module...");
         self.start_time_us = time::Instant::now();
+        let pid = std::process::id();
+        let program_name = get_program_name();
+        {
+            let mut reg_memory = self.reg_memory.lock().unwrap();
+            let mut reg_memory = ShMem::wlock(&mut reg_memory);
+            reg_memory.timing.reset();
+            PENDING_TIMING.lock().unwrap().reset();
+            let slot = reg_memory.clients.register(pid, &program_name, unix_time_ms());
+            if slot.is_none() {
+                log::warning("No free client slot, broker won't list this program!");
+            }
+            *self.client_slot.lock().unwrap() = slot;
+        }
         let reg_memory = Arc::clone(&self.reg_memory);
         let isr_routines = Arc::clone(&self.isr_routines);
+        let client_slot = Arc::clone(&self.client_slot);
         thread::spawn(move || {
             let mut old_input = Reg::new();
+            let mut next_due_us: Option<u64> = None;
             loop {
-                thread::sleep(Duration::from_millis(50));
-                let reg_memory = reg_memory.lock().unwrap();
-                let reg_memory = ShMem::rlock(&reg_memory);
-                let isr_routines = isr_routines.lock().unwrap();
-                for i in MIN_PIN_NUM..=MAX_PIN_NUM {
-                    if reg_memory.inten.read_pin(i) == 1 {
-                        let v_int0 = reg_memory.int0.read_pin(i) == 1;
-                        let v_int1 = reg_memory.int1.read_pin(i) == 1;
-                        let v_inp_old = old_input.read_pin(i) == 1;
-                        let v_inp_new = reg_memory.input.read_pin(i) == 1;
-                        // rising edge
-                        if v_int1 && v_int0
-                            && !v_inp_old
-                            && v_inp_new
-                            && isr_routines[i as usize].is_some() {
-                            isr_routines[i as usize].unwrap()();
-                        }
-                        // falling edge
-                        else if v_int1 && !v_int0
-                            && v_inp_old
-                            && !v_inp_new
-                            && isr_routines[i as usize].is_some() {
-                            isr_routines[i as usize].unwrap()();
-                        }
-                        // logical change
-                        else if !v_int1 && v_int0
-                            && (v_inp_old
-                            ^ v_inp_new)
-                            && isr_routines[i as usize].is_some() {
-                            isr_routines[i as usize].unwrap()();
+                // Wake up early for scheduled changes of the INPUT register
+                let sleep_us = match next_due_us {
+                    Some(due_us) => std::cmp::min(due_us.saturating_sub(unix_time_us()), ISR_POLL_US),
+                    None => ISR_POLL_US,
+                };
+                thread::sleep(Duration::from_micros(sleep_us));
+                let isr_routines = *isr_routines.lock().unwrap();
+                let mut pending_isrs = vec![];
+                {
+                    let mut reg_memory = reg_memory.lock().unwrap();
+                    let mut reg_memory = ShMem::wlock(&mut reg_memory);
+                    // Heartbeat, re-register if the broker dropped our slot
+                    let mut client_slot = client_slot.lock().unwrap();
+                    let now_ms = unix_time_ms();
+                    let is_alive = match *client_slot {
+                        Some(index) => reg_memory.clients.heartbeat(index, pid, now_ms),
+                        None => false,
+                    };
+                    if !is_alive {
+                        *client_slot = reg_memory.clients.register(pid, &program_name, now_ms);
+                    }
+                    flush_timing(&mut reg_memory.timing);
+                    // Changes made by the broker since the last check, then
+                    // each scheduled change on its own
+                    let mut check = |reg_memory: &RegMemory, old_input: &Reg| {
+                        for i in MIN_PIN_NUM..=MAX_PIN_NUM {
+                            if let Some(isr) = isr_routines[i as usize] {
+                                if is_interrupt(reg_memory, i, old_input.read_pin(i), reg_memory.input.read_pin(i)) {
+                                    pending_isrs.push(isr);
+                                }
+                            }
                         }
+                    };
+                    check(&reg_memory, &old_input);
+                    old_input = reg_memory.input.clone();
+                    for event in reg_memory.input_log.pop_due(unix_time_us()) {
+                        reg_memory.input.write_pin(event.pin, event.value);
+                        check(&reg_memory, &old_input);
+                        old_input = reg_memory.input.clone();
                     }
+                    next_due_us = reg_memory.input_log.next_due();
+                }
+                // No lock is held, so the routines can call wiringPi functions
+                for isr in pending_isrs {
+                    isr();
                 }
-                old_input = reg_memory.input.clone();
             }
         });
         return 0;
     }
 
+    pub fn teardown(&mut self) {
+        log::info("Detaching wpisim module...");
+        let client_slot = self.client_slot.lock().unwrap().take();
+        if let Some(index) = client_slot {
+            let mut reg_memory = self.reg_memory.lock().unwrap();
+            if reg_memory.is_ok() {
+                let mut reg_memory = ShMem::wlock(&mut reg_memory);
+                flush_timing(&mut reg_memory.timing);
+                reg_memory.clients.unregister(index, std::process::id());
+            }
+        }
+    }
+
     pub fn pin_mode(&mut self, pin: u8, pud: u8) {
         let mut reg_memory = self.reg_memory.lock().unwrap();
         let mut reg_memory = ShMem::wlock(&mut reg_memory);
@@ -99,10 +244,34 @@ impl LSimCore {
             if pud == INPUT || pud == OUTPUT {
                 let mode = if pud == INPUT { 1 } else { 0 };
                 reg_memory.config.write_pin(pin, mode);
-            } else if pud == PWM_OUTPUT {
+                reg_memory.pwm.disable(pin);
+                drive_responder(&mut reg_memory, pin);
+                self.responses[pin as usize] = reg_memory.responders.response(pin);
+            } else if pud == PWM_OUTPUT || pud == SOFT_PWM_OUTPUT {
                 reg_memory.config.write_pin(pin, 0);
-                // process PWM here!!
+                let range = if pud == PWM_OUTPUT { self.pwm_range } else { SOFT_PWM_RANGE };
+                reg_memory.pwm.enable(pin, range);
+                reg_memory.pwm.set_period_ns(pin, if pud == PWM_OUTPUT {
+                    self.hw_pwm_period_ns()
+                } else {
+                    pwm_period_ns(SOFT_PWM_RANGE as u64 * SOFT_PWM_STEP_NS)
+                });
+                if pud == SOFT_PWM_OUTPUT {
+                    self.soft_pwm_pins |= 0x1 << pin;
+                } else {
+                    self.soft_pwm_pins &= !(0x1 << pin);
+                }
+                write_output(&mut reg_memory, pin, LOW);
             }
+            reg_memory.used.write_pin(pin, 1);
+        }
+    }
+
+    pub fn pull_up_dn_control(&mut self, pin: u8, pud: u8) {
+        let mut reg_memory = self.reg_memory.lock().unwrap();
+        let mut reg_memory = ShMem::wlock(&mut reg_memory);
+        if pin >= MIN_PIN_NUM && pin <= MAX_PIN_NUM {
+            reg_memory.pull.write_pin(pin, if pud == PUD_OFF { 0 } else { 1 });
         }
     }
 
@@ -110,15 +279,116 @@ impl LSimCore {
         let mut reg_memory = self.reg_memory.lock().unwrap();
         let mut reg_memory = ShMem::wlock(&mut reg_memory);
         if pin >= MIN_PIN_NUM && pin <= MAX_PIN_NUM {
-            reg_memory.output.write_pin(pin, val);
+            write_output(&mut reg_memory, pin, val);
+            self.responses[pin as usize] = reg_memory.responders.response(pin);
+        }
+    }
+
+    // PWM pins are not toggled, the broker reads the duty cycle instead. The
+    // OUTPUT register shows whether the pin is high at all.
+    pub fn pwm_write(&mut self, pin: u8, val: u32) {
+        let mut reg_memory = self.reg_memory.lock().unwrap();
+        let mut reg_memory = ShMem::wlock(&mut reg_memory);
+        if pin >= MIN_PIN_NUM && pin <= MAX_PIN_NUM && reg_memory.pwm.is_enabled(pin) {
+            let val = val.min(reg_memory.pwm.range(pin));
+            reg_memory.pwm.write(pin, val);
+            write_output(&mut reg_memory, pin, if val > 0 { HIGH } else { LOW });
+        }
+    }
+
+    // Length of a cycle of the hardware PWM (in ns)
+    fn hw_pwm_period_ns(&self) -> u32 {
+        return pwm_period_ns(self.pwm_range as u64 * self.pwm_clock as u64 * 1_000_000_000 / PWM_OSC_HZ);
+    }
+
+    pub fn pwm_set_range(&mut self, range: u32) {
+        self.pwm_range = range;
+        self.update_hw_pwm_pins();
+    }
+
+    pub fn pwm_set_clock(&mut self, divisor: u32) {
+        // The divisor register has 12 bits, just like in wiringPi
+        self.pwm_clock = std::cmp::max(divisor & 4095, 1);
+        self.update_hw_pwm_pins();
+    }
+
+    fn update_hw_pwm_pins(&mut self) {
+        let period_ns = self.hw_pwm_period_ns();
+        let mut reg_memory = self.reg_memory.lock().unwrap();
+        let mut reg_memory = ShMem::wlock(&mut reg_memory);
+        for pin in MIN_PIN_NUM..=MAX_PIN_NUM {
+            if reg_memory.pwm.is_enabled(pin) && (self.soft_pwm_pins >> pin) & 0x1 == 0 {
+                reg_memory.pwm.set_range(pin, self.pwm_range);
+                reg_memory.pwm.set_period_ns(pin, period_ns);
+            }
+        }
+    }
+
+    pub fn soft_pwm_create(&mut self, pin: u8, val: u32, range: u32) -> i32 {
+        if pin < MIN_PIN_NUM || pin > MAX_PIN_NUM || range == 0 {
+            return -1;
+        }
+        {
+            let mut reg_memory = self.reg_memory.lock().unwrap();
+            let mut reg_memory = ShMem::wlock(&mut reg_memory);
+            reg_memory.config.write_pin(pin, 0);
+            reg_memory.used.write_pin(pin, 1);
+            reg_memory.pwm.enable(pin, range);
+            reg_memory.pwm.set_period_ns(pin, pwm_period_ns(range as u64 * SOFT_PWM_STEP_NS));
+        }
+        self.soft_pwm_pins |= 0x1 << pin;
+        self.pwm_write(pin, val);
+        return 0;
+    }
+
+    pub fn soft_pwm_stop(&mut self, pin: u8) {
+        let mut reg_memory = self.reg_memory.lock().unwrap();
+        let mut reg_memory = ShMem::wlock(&mut reg_memory);
+        if pin >= MIN_PIN_NUM && pin <= MAX_PIN_NUM && (self.soft_pwm_pins >> pin) & 0x1 != 0 {
+            reg_memory.pwm.disable(pin);
+            write_output(&mut reg_memory, pin, LOW);
+        }
+    }
+
+    pub fn adc_setup(&mut self, pin_base: i32, channels: u8, bits: u8) -> i32 {
+        let pin_end = pin_base + channels as i32;
+        if pin_base < NODE_PIN_BASE_MIN {
+            log::error(format!("ADC pin base {} is out of range (min {})", pin_base, NODE_PIN_BASE_MIN).as_ref());
+            return 0;
+        }
+        if self.adc_nodes.iter().any(|node| pin_base < node.pin_base + node.channels as i32 && node.pin_base < pin_end) {
+            log::error(format!("ADC pins {}-{} overlap with an existing node", pin_base, pin_end - 1).as_ref());
+            return 0;
+        }
+        self.adc_nodes.push(AdcNode { pin_base, channels, bits });
+        return 1;
+    }
+
+    pub fn analog_read(&self, pin: i32) -> i32 {
+        let node = self.adc_nodes.iter()
+            .find(|node| pin >= node.pin_base && pin < node.pin_base + node.channels as i32);
+        match node {
+            Some(node) => {
+                let reg_memory = self.reg_memory.lock().unwrap();
+                let reg_memory = ShMem::rlock(&reg_memory);
+                return reg_memory.adc.read_bits((pin - node.pin_base) as u8, node.bits) as i32;
+            },
+            // Same as wiringPi for pins without an analog node
+            None => return 0,
         }
     }
 
     pub fn read_pin(&self, pin: u8) -> u8 {
+        // A running response overrides the level of the INPUT register, it
+        // is timed in us, so the shared memory is not even locked
+        let response = self.responses.get(pin as usize).cloned().flatten();
+        if let Some(level) = response.and_then(|r| r.level(unix_time_us())) {
+            return level;
+        }
         let reg_memory = self.reg_memory.lock().unwrap();
         let reg_memory = ShMem::rlock(&reg_memory);
         if pin >= MIN_PIN_NUM && pin <= MAX_PIN_NUM {
-            return reg_memory.input.read_pin(pin);
+            return reg_memory.read_input(pin);
         } else {
             return 0xFF;
         }
@@ -136,6 +406,7 @@ impl LSimCore {
             reg_memory.int0.write_pin(pin, v_int0);
             reg_memory.int1.write_pin(pin, v_int1);
             reg_memory.inten.write_pin(pin, 1);
+            reg_memory.used.write_pin(pin, 1);
             isr_routines[pin as usize] = Some(isr);
             return 0;
         } else {
@@ -143,14 +414,32 @@ impl LSimCore {
         }
     }
 
-    pub fn delay_ms(&self, duration: u64) {
-        let duration = time::Duration::from_millis(duration);
-        thread::sleep(duration);
+    // The delays do not need the core, so callers can release it while
+    // sleeping and ISRs are able to call wiringPi functions meanwhile.
+    // Returns the actual duration of the delay.
+    pub fn delay_ms(duration: u64) -> time::Duration {
+        let start = time::Instant::now();
+        thread::sleep(time::Duration::from_millis(duration));
+        return start.elapsed();
+    }
+
+    pub fn delay_us(duration: u64) -> time::Duration {
+        let start = time::Instant::now();
+        if duration < DELAY_US_HARD_LIMIT {
+            // Sleeping overshoots short delays by far, so busy-wait instead
+            // (same as delayMicrosecondsHard() in wiringPi).
+            let duration = time::Duration::from_micros(duration);
+            while start.elapsed() < duration {}
+        } else {
+            thread::sleep(time::Duration::from_micros(duration));
+        }
+        return start.elapsed();
     }
 
-    pub fn delay_us(&self, duration: u64) {
-        let duration = time::Duration::from_micros(duration);
-        thread::sleep(duration);
+    // The delays are written to the shared memory by the ISR thread, see
+    // flush_timing().
+    pub fn record_delay(requested_us: u64, actual: time::Duration) {
+        PENDING_TIMING.lock().unwrap().record(requested_us, actual.as_micros() as u64);
     }
 
     pub fn get_uptime_ms(&self) -> u64 {
@@ -162,7 +451,27 @@ impl LSimCore {
     }
 }
 
+// Moves the delays recorded since the last call into the given stats.
+fn flush_timing(timing: &mut TimingStats) {
+    let mut pending = PENDING_TIMING.lock().unwrap();
+    timing.merge(&pending);
+    pending.reset();
+}
+
 #[cfg(test)]
 mod tests {
-    //use super::*;
+    use super::*;
+
+    #[test]
+    fn LSimCore__delay_us() {
+        let actual = LSimCore::delay_us(50);
+        assert!(actual >= Duration::from_micros(50));
+        LSimCore::record_delay(50, actual);
+        LSimCore::record_delay(200, LSimCore::delay_us(200));
+        let mut timing = TimingStats::new();
+        flush_timing(&mut timing);
+        assert_eq!(timing.count(), 2);
+        flush_timing(&mut timing);
+        assert_eq!(timing.count(), 2);
+    }
 }